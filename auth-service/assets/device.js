@@ -0,0 +1,53 @@
+const deviceForm = document.getElementById("device-form");
+const deviceApproveButton = document.getElementById("device-form-approve");
+const deviceDenyButton = document.getElementById("device-form-deny");
+const deviceErrAlter = document.getElementById("device-err-alert");
+const deviceOkAlter = document.getElementById("device-ok-alert");
+
+// Prefill the code when coming from verification_uri_complete
+const userCodeParam = new URLSearchParams(window.location.search).get("user_code");
+if (userCodeParam !== null) {
+    deviceForm.user_code.value = userCodeParam;
+}
+
+function answerDevice(approved) {
+    const userCode = deviceForm.user_code.value;
+
+    fetch('/device/approve', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ userCode, approved }),
+    }).then(response => {
+        if (response.ok) {
+            deviceForm.user_code.value = "";
+            deviceErrAlter.style.display = "none";
+            deviceOkAlter.innerHTML = approved
+                ? "<span>The device is connected, you can go back to it.</span>"
+                : "<span>The device has been denied access.</span>";
+            deviceOkAlter.style.display = "block";
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                deviceOkAlter.style.display = "none";
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    deviceErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    deviceErrAlter.style.display = "block";
+                } else {
+                    deviceErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+}
+
+deviceApproveButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    answerDevice(true);
+});
+
+deviceDenyButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    answerDevice(false);
+});
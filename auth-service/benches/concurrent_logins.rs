@@ -0,0 +1,100 @@
+// Measures login throughput while signups are running concurrently, with the user
+// store behind the former outer `RwLock` and shared directly as it is now.
+//
+// Run with `cargo bench --bench concurrent_logins`.
+use std::{future::Future, sync::Arc, time::{Duration, Instant}};
+
+use auth_service::{
+    domain::{Email, Password, User, UserStore},
+    get_sqlite_pool,
+    services::{argon2_password_hasher::Argon2PasswordHasher, data_stores::sqlite_user_store::SqliteUserStore},
+    utils::config::PasswordHashConfig,
+};
+use secrecy::Secret;
+use tokio::{sync::RwLock, task::JoinSet};
+
+const USERS: usize = 32;
+const LOGINS_PER_USER: usize = 4;
+const PASSWORD: &str = "password123";
+
+#[tokio::main]
+async fn main() {
+    println!("{} users, each signing up then logging in {} times", USERS, LOGINS_PER_USER);
+
+    let store = Arc::new(RwLock::new(new_store().await));
+    let outer_lock = run(move |email| {
+        let store = store.clone();
+        async move {
+            // Signup used to hold the write lock across the lookup and the insert
+            {
+                let store = store.write().await;
+                assert!(store.get_user(&email).await.is_err());
+                store.add_user(new_user(&email), Some(password())).await.unwrap();
+            }
+            for _ in 0..LOGINS_PER_USER {
+                let store = store.read().await;
+                store.validate_user(&email, &password()).await.unwrap();
+                store.get_user(&email).await.unwrap();
+            }
+        }
+    })
+    .await;
+    report("outer RwLock", outer_lock);
+
+    let store: Arc<dyn UserStore + Send + Sync> = Arc::new(new_store().await);
+    let shared = run(move |email| {
+        let store = store.clone();
+        async move {
+            store.add_user(new_user(&email), Some(password())).await.unwrap();
+            for _ in 0..LOGINS_PER_USER {
+                store.validate_user(&email, &password()).await.unwrap();
+                store.get_user(&email).await.unwrap();
+            }
+        }
+    })
+    .await;
+    report("shared store", shared);
+}
+
+async fn run<F, Fut>(task: F) -> Duration
+where
+    F: Fn(Email) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let start = Instant::now();
+    let mut tasks = JoinSet::new();
+    for i in 0..USERS {
+        let email = Email::parse(Secret::new(format!("user{}@example.com", i))).unwrap();
+        tasks.spawn(task(email));
+    }
+    while let Some(result) = tasks.join_next().await {
+        result.unwrap();
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let logins = (USERS * LOGINS_PER_USER) as f64;
+    println!("{:<14} {:>8.2?} {:>8.1} logins/s", name, elapsed, logins / elapsed.as_secs_f64());
+}
+
+async fn new_store() -> SqliteUserStore {
+    let path = std::env::temp_dir().join(format!("auth-bench-{}.db", uuid::Uuid::new_v4()));
+    let pool = get_sqlite_pool(&Secret::new(format!("sqlite://{}", path.display())))
+        .await
+        .expect("Failed to create SQLite connection pool");
+    sqlx::migrate!("./migrations_sqlite")
+        .run(&pool)
+        .await
+        .expect("Failed to run SQLite migrations");
+    let password_hasher = Argon2PasswordHasher::new(&PasswordHashConfig::default(), None).unwrap();
+    SqliteUserStore::new(pool, Arc::new(password_hasher))
+}
+
+fn new_user(email: &Email) -> User {
+    User::new(email.clone(), false)
+}
+
+fn password() -> Password {
+    Password::parse(Secret::new(PASSWORD.to_owned())).unwrap()
+}
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS api_keys(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   name TEXT NOT NULL,
+   prefix TEXT NOT NULL UNIQUE,
+   key_hash TEXT NOT NULL,
+   scopes TEXT[] NOT NULL DEFAULT '{}',
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   expires_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS api_keys_email_idx ON api_keys (email);
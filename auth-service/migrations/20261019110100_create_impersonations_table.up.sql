@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS impersonations(
+   id TEXT NOT NULL PRIMARY KEY,
+   admin_email TEXT NOT NULL,
+   user_email TEXT NOT NULL,
+   reason TEXT,
+   started_at TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL,
+   ended_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS impersonations_admin_email_idx ON impersonations (admin_email);
+CREATE INDEX IF NOT EXISTS impersonations_user_email_idx ON impersonations (user_email);
@@ -0,0 +1,24 @@
+-- Emails differing only in case were separate accounts so far, they can't share a canonical
+-- email. Rather than picking a winner, stop and leave merging or renaming them to an operator:
+--   SELECT lower(email), array_agg(email) FROM users GROUP BY lower(email) HAVING count(*) > 1;
+DO $$
+DECLARE
+    duplicates BIGINT;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'users' AND column_name = 'email_canonical') THEN
+        SELECT count(*) INTO duplicates
+        FROM (SELECT 1 FROM users GROUP BY lower(email) HAVING count(*) > 1) AS duplicated;
+        IF duplicates > 0 THEN
+            RAISE EXCEPTION '% emails are used by several users that differ only in case, merge or rename them before migrating', duplicates;
+        END IF;
+    END IF;
+END $$;
+
+-- Existing rows are backfilled with the lowercased address. The canonical form computed by the
+-- application (punycode domain, local part policy) can differ, `auth-admin canonicalize-emails`
+-- recomputes it for those rows. New rows get it from the application.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_canonical TEXT;
+UPDATE users SET email_canonical = lower(email) WHERE email_canonical IS NULL;
+ALTER TABLE users ALTER COLUMN email_canonical SET NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_canonical_idx ON users (email_canonical);
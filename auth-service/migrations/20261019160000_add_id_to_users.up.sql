@@ -0,0 +1,6 @@
+-- Users are keyed by a generated id so that the email can change, existing rows get a random one
+ALTER TABLE users ADD COLUMN IF NOT EXISTS id UUID;
+UPDATE users SET id = gen_random_uuid() WHERE id IS NULL;
+ALTER TABLE users ALTER COLUMN id SET NOT NULL;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_pkey;
+ALTER TABLE users ADD PRIMARY KEY (id);
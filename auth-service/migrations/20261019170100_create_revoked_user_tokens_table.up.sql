@@ -0,0 +1,5 @@
+-- Tokens of the user issued up to revoked_at are rejected
+CREATE TABLE IF NOT EXISTS revoked_user_tokens(
+   user_id UUID NOT NULL PRIMARY KEY,
+   revoked_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Codes are only kept as a keyed hash of the login attempt id and the code. The key isn't
+-- available to SQL, so pending codes are dropped and those users have to log in again.
+DROP TABLE IF EXISTS two_fa_codes;
+CREATE TABLE two_fa_codes(
+   email TEXT NOT NULL PRIMARY KEY,
+   code_hash TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes (expires_at);
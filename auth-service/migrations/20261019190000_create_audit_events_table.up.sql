@@ -0,0 +1,27 @@
+-- Append-only, the trigger refuses to change or remove recorded events
+CREATE TABLE IF NOT EXISTS audit_events(
+   id BIGSERIAL PRIMARY KEY,
+   occurred_at TIMESTAMPTZ NOT NULL,
+   kind TEXT NOT NULL,
+   outcome TEXT NOT NULL,
+   user_id UUID,
+   email TEXT,
+   email_canonical TEXT,
+   ip TEXT,
+   user_agent TEXT,
+   detail TEXT
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_user_id_idx ON audit_events (user_id, id);
+CREATE INDEX IF NOT EXISTS audit_events_email_canonical_idx ON audit_events (email_canonical, id);
+
+CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS TRIGGER AS $$
+BEGIN
+   RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS audit_events_append_only ON audit_events;
+CREATE TRIGGER audit_events_append_only
+   BEFORE UPDATE OR DELETE ON audit_events
+   FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
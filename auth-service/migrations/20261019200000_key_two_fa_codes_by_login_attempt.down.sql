@@ -0,0 +1,8 @@
+-- Only one code per email can be kept, pending codes are dropped and those users have to log in again.
+DROP TABLE IF EXISTS two_fa_codes;
+CREATE TABLE two_fa_codes(
+   email TEXT NOT NULL PRIMARY KEY,
+   code_hash TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes (expires_at);
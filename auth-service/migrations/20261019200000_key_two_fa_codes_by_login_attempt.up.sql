@@ -0,0 +1,12 @@
+-- Codes are keyed by login attempt, so that an email can have several pending at once.
+-- Pending codes don't carry their login attempt id, those users have to log in again.
+DROP TABLE IF EXISTS two_fa_codes;
+CREATE TABLE two_fa_codes(
+   login_attempt_id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   code_hash TEXT NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX IF NOT EXISTS two_fa_codes_email_idx ON two_fa_codes (email, created_at);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes (expires_at);
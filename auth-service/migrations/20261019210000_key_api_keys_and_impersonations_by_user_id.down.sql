@@ -0,0 +1,21 @@
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS email TEXT;
+UPDATE api_keys SET email = users.email FROM users WHERE api_keys.email IS NULL AND users.id = api_keys.user_id;
+DELETE FROM api_keys WHERE email IS NULL;
+ALTER TABLE api_keys ALTER COLUMN email SET NOT NULL;
+DROP INDEX IF EXISTS api_keys_user_id_idx;
+ALTER TABLE api_keys DROP COLUMN IF EXISTS user_id;
+CREATE INDEX IF NOT EXISTS api_keys_email_idx ON api_keys (email);
+
+ALTER TABLE impersonations ADD COLUMN IF NOT EXISTS admin_email TEXT;
+ALTER TABLE impersonations ADD COLUMN IF NOT EXISTS user_email TEXT;
+UPDATE impersonations SET admin_email = users.email FROM users WHERE impersonations.admin_email IS NULL AND users.id = impersonations.admin_id;
+UPDATE impersonations SET user_email = users.email FROM users WHERE impersonations.user_email IS NULL AND users.id = impersonations.user_id;
+DELETE FROM impersonations WHERE admin_email IS NULL OR user_email IS NULL;
+ALTER TABLE impersonations ALTER COLUMN admin_email SET NOT NULL;
+ALTER TABLE impersonations ALTER COLUMN user_email SET NOT NULL;
+DROP INDEX IF EXISTS impersonations_admin_id_idx;
+DROP INDEX IF EXISTS impersonations_user_id_idx;
+ALTER TABLE impersonations DROP COLUMN IF EXISTS admin_id;
+ALTER TABLE impersonations DROP COLUMN IF EXISTS user_id;
+CREATE INDEX IF NOT EXISTS impersonations_admin_email_idx ON impersonations (admin_email);
+CREATE INDEX IF NOT EXISTS impersonations_user_email_idx ON impersonations (user_email);
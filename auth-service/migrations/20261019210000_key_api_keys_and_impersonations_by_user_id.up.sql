@@ -0,0 +1,23 @@
+-- API keys and impersonations follow the user's id, so that they stay with the user through an email change.
+-- Rows whose email no longer belongs to any user can't be attributed and are dropped.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS user_id UUID;
+UPDATE api_keys SET user_id = users.id FROM users WHERE api_keys.user_id IS NULL AND users.email = api_keys.email;
+DELETE FROM api_keys WHERE user_id IS NULL;
+ALTER TABLE api_keys ALTER COLUMN user_id SET NOT NULL;
+DROP INDEX IF EXISTS api_keys_email_idx;
+ALTER TABLE api_keys DROP COLUMN IF EXISTS email;
+CREATE INDEX IF NOT EXISTS api_keys_user_id_idx ON api_keys (user_id);
+
+ALTER TABLE impersonations ADD COLUMN IF NOT EXISTS admin_id UUID;
+ALTER TABLE impersonations ADD COLUMN IF NOT EXISTS user_id UUID;
+UPDATE impersonations SET admin_id = users.id FROM users WHERE impersonations.admin_id IS NULL AND users.email = impersonations.admin_email;
+UPDATE impersonations SET user_id = users.id FROM users WHERE impersonations.user_id IS NULL AND users.email = impersonations.user_email;
+DELETE FROM impersonations WHERE admin_id IS NULL OR user_id IS NULL;
+ALTER TABLE impersonations ALTER COLUMN admin_id SET NOT NULL;
+ALTER TABLE impersonations ALTER COLUMN user_id SET NOT NULL;
+DROP INDEX IF EXISTS impersonations_admin_email_idx;
+DROP INDEX IF EXISTS impersonations_user_email_idx;
+ALTER TABLE impersonations DROP COLUMN IF EXISTS admin_email;
+ALTER TABLE impersonations DROP COLUMN IF EXISTS user_email;
+CREATE INDEX IF NOT EXISTS impersonations_admin_id_idx ON impersonations (admin_id);
+CREATE INDEX IF NOT EXISTS impersonations_user_id_idx ON impersonations (user_id);
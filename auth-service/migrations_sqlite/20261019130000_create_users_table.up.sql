@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS users(
+   email TEXT NOT NULL PRIMARY KEY,
+   password_hash TEXT,
+   requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+   role TEXT NOT NULL DEFAULT 'user'
+);
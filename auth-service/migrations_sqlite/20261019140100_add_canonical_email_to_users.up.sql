@@ -0,0 +1,19 @@
+-- Emails differing only in case were separate accounts so far, they can't share a canonical
+-- email. Rather than picking a winner, stop and leave merging or renaming them to an operator:
+--   SELECT lower(email), group_concat(email) FROM users GROUP BY lower(email) HAVING count(*) > 1;
+-- SQLite can only raise an error from a trigger, hence the scratch table.
+CREATE TEMP TABLE duplicate_emails (email TEXT);
+CREATE TEMP TRIGGER reject_duplicate_emails BEFORE INSERT ON duplicate_emails
+BEGIN
+    SELECT RAISE(ABORT, 'some emails are used by several users that differ only in case, merge or rename them before migrating');
+END;
+INSERT INTO duplicate_emails SELECT lower(email) FROM users GROUP BY lower(email) HAVING count(*) > 1;
+DROP TRIGGER reject_duplicate_emails;
+DROP TABLE duplicate_emails;
+
+-- Existing rows are backfilled with the lowercased address. The canonical form computed by the
+-- application (punycode domain, local part policy) can differ, `auth-admin canonicalize-emails`
+-- recomputes it for those rows. New rows get it from the application.
+ALTER TABLE users ADD COLUMN email_canonical TEXT;
+UPDATE users SET email_canonical = lower(email) WHERE email_canonical IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_canonical_idx ON users (email_canonical);
@@ -0,0 +1,21 @@
+CREATE TABLE users_old(
+   email TEXT NOT NULL PRIMARY KEY,
+   password_hash TEXT,
+   requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+   role TEXT NOT NULL DEFAULT 'user',
+   email_canonical TEXT,
+   display_name TEXT,
+   created_at INTEGER NOT NULL DEFAULT 0,
+   updated_at INTEGER NOT NULL DEFAULT 0,
+   last_login_at INTEGER
+);
+
+INSERT INTO users_old (email, password_hash, requires_2fa, role, email_canonical,
+                       display_name, created_at, updated_at, last_login_at)
+SELECT email, password_hash, requires_2fa, role, email_canonical,
+       display_name, created_at, updated_at, last_login_at
+FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_old RENAME TO users;
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_canonical_idx ON users (email_canonical);
@@ -0,0 +1,27 @@
+-- SQLite can't change the primary key of a table, so users is rebuilt keyed by a generated id.
+-- Existing rows get a random version 4 UUID.
+CREATE TABLE users_new(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   email_canonical TEXT NOT NULL,
+   password_hash TEXT,
+   requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+   role TEXT NOT NULL DEFAULT 'user',
+   display_name TEXT,
+   created_at INTEGER NOT NULL,
+   updated_at INTEGER NOT NULL,
+   last_login_at INTEGER
+);
+
+INSERT INTO users_new (id, email, email_canonical, password_hash, requires_2fa, role,
+                       display_name, created_at, updated_at, last_login_at)
+SELECT lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' ||
+       substr(lower(hex(randomblob(2))), 2) || '-' || substr('89ab', 1 + (abs(random()) % 4), 1) ||
+       substr(lower(hex(randomblob(2))), 2) || '-' || lower(hex(randomblob(6))),
+       email, email_canonical, password_hash, requires_2fa, role,
+       display_name, created_at, updated_at, last_login_at
+FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_canonical_idx ON users (email_canonical);
@@ -1,12 +1,25 @@
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::{net::IpAddr, num::NonZeroUsize, sync::Arc};
 
-use crate::domain::{UserStore, BannedTokenStore, TwoFACodeStore, EmailClient};
+use chrono::Duration;
 
-pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
-pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
-pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+use crate::domain::{Email, UserStore, BannedTokenStore, TwoFACodeStore, EmailClient, ApiKeyStore, MagicLinkStore,
+                    PasswordResetStore, DeviceGrantStore, ImpersonationStore, PasswordHasher, EmailChangeStore, AuditLog};
+use crate::services::rate_limiter::RateLimiter;
+use crate::utils::constants::{LOCKOUT_SECONDS_I64, LOGIN_CODE_WINDOW_SECONDS_I64, MAX_FAILED_LOGINS_PER_IP,
+                              MAX_LOGIN_CODES_PER_EMAIL, MAX_LOGIN_CODES_PER_IP, RATE_LIMITER_CAPACITY};
+
+pub type UserStoreType = Arc<dyn UserStore + Send + Sync>;
+pub type BannedTokenStoreType = Arc<dyn BannedTokenStore + Send + Sync>;
+pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore + Send + Sync>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type ApiKeyStoreType = Arc<dyn ApiKeyStore + Send + Sync>;
+pub type MagicLinkStoreType = Arc<dyn MagicLinkStore + Send + Sync>;
+pub type PasswordResetStoreType = Arc<dyn PasswordResetStore + Send + Sync>;
+pub type DeviceGrantStoreType = Arc<dyn DeviceGrantStore + Send + Sync>;
+pub type ImpersonationStoreType = Arc<dyn ImpersonationStore + Send + Sync>;
+pub type PasswordHasherType = Arc<dyn PasswordHasher + Send + Sync>;
+pub type EmailChangeStoreType = Arc<dyn EmailChangeStore + Send + Sync>;
+pub type AuditLogType = Arc<dyn AuditLog + Send + Sync>;
 
 
 #[derive(Clone)]
@@ -14,14 +27,59 @@ pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
-    pub email_client: EmailClientType
+    pub email_client: EmailClientType,
+    pub api_key_store: ApiKeyStoreType,
+    pub magic_link_store: MagicLinkStoreType,
+    pub password_reset_store: PasswordResetStoreType,
+    pub device_grant_store: DeviceGrantStoreType,
+    pub impersonation_store: ImpersonationStoreType,
+    pub email_change_store: EmailChangeStoreType,
+    pub audit_log: AuditLogType,
+    pub failed_login_limiter: Arc<RateLimiter<IpAddr>>,
+    pub login_codes_per_email: Arc<RateLimiter<Email>>,
+    pub login_codes_per_ip: Arc<RateLimiter<IpAddr>>
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(user_store: UserStoreType, 
                banned_token_store: BannedTokenStoreType,
                two_fa_code_store: TwoFACodeStoreType,
-               email_client: EmailClientType) -> Self {
-        Self { user_store, banned_token_store, two_fa_code_store, email_client }
+               email_client: EmailClientType,
+               api_key_store: ApiKeyStoreType,
+               magic_link_store: MagicLinkStoreType,
+               password_reset_store: PasswordResetStoreType,
+               device_grant_store: DeviceGrantStoreType,
+               impersonation_store: ImpersonationStoreType,
+               email_change_store: EmailChangeStoreType,
+               audit_log: AuditLogType) -> Self {
+        Self {
+            user_store,
+            banned_token_store,
+            two_fa_code_store,
+            email_client,
+            api_key_store,
+            magic_link_store,
+            password_reset_store,
+            device_grant_store,
+            impersonation_store,
+            email_change_store,
+            audit_log,
+            failed_login_limiter: Arc::new(RateLimiter::new(
+                NonZeroUsize::new(RATE_LIMITER_CAPACITY).unwrap(),
+                MAX_FAILED_LOGINS_PER_IP,
+                Duration::seconds(LOCKOUT_SECONDS_I64),
+            )),
+            login_codes_per_email: Arc::new(RateLimiter::new(
+                NonZeroUsize::new(RATE_LIMITER_CAPACITY).unwrap(),
+                MAX_LOGIN_CODES_PER_EMAIL,
+                Duration::seconds(LOGIN_CODE_WINDOW_SECONDS_I64),
+            )),
+            login_codes_per_ip: Arc::new(RateLimiter::new(
+                NonZeroUsize::new(RATE_LIMITER_CAPACITY).unwrap(),
+                MAX_LOGIN_CODES_PER_IP,
+                Duration::seconds(LOGIN_CODE_WINDOW_SECONDS_I64),
+            ))
+        }
     }
 }
@@ -0,0 +1,122 @@
+use std::{io, process::ExitCode};
+
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use auth_service::{
+    app_state::AppState,
+    domain::{ApiKeyStoreError, Email, Password, PasswordResetToken, Role, User, UserStoreError},
+};
+
+pub async fn create_user(state: &AppState,
+                         email: String,
+                         password_stdin: bool,
+                         role: String,
+                         requires_2fa: bool) -> Result<ExitCode> {
+    let email = Email::parse(Secret::new(email))?;
+    let password = if password_stdin {
+        let mut password = String::new();
+        io::stdin().read_line(&mut password).wrap_err("failed to read the password")?;
+        Some(Password::parse(Secret::new(password.trim_end_matches(['\r', '\n']).to_owned()))?)
+    } else {
+        None
+    };
+
+    let mut user = User::new(email, requires_2fa);
+    user.role = Role::parse(&role)?;
+
+    match state.user_store.add_user(user.clone(), password).await {
+        Ok(()) => {}
+        Err(UserStoreError::UserAlreadyExists) => return Err(eyre!("{} already exists", display(&user.email))),
+        Err(e) => return Err(e.into()),
+    }
+
+    println!("created {} with id {}", display(&user.email), user.id);
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn reset_2fa(state: &AppState, email: String) -> Result<ExitCode> {
+    let user = find_user(state, email).await?;
+
+    state.two_fa_code_store.delete_two_fa_codes(&user.email).await?;
+
+    println!("discarded the pending 2FA codes of {}", display(&user.email));
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn disable_2fa(state: &AppState, email: String) -> Result<ExitCode> {
+    let user = find_user(state, email).await?;
+
+    state.user_store.update_requires_2fa(&user.id, false).await?;
+    // Codes sent before 2FA was turned off are of no use anymore
+    state.two_fa_code_store.delete_two_fa_codes(&user.email).await?;
+
+    println!("disabled 2FA for {}", display(&user.email));
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn force_password_reset(state: &AppState, email: String) -> Result<ExitCode> {
+    let user = find_user(state, email).await?;
+
+    state.user_store.clear_password(&user.id).await?;
+    // Whoever knew the old password may already hold a token or have made an API key
+    revoke_credentials(state, &user).await?;
+
+    let token = PasswordResetToken::default();
+    state.password_reset_store.add_password_reset(&user.id, token.clone()).await?;
+
+    println!("removed the password of {} and revoked their tokens and API keys", display(&user.email));
+    println!("pass this one-time reset token on to them, it's valid for a day: {}", token.as_ref().expose_secret());
+    println!("they set a new password with POST /password-reset and the token, until then they sign in with \
+              a one-time code (POST /login/code with their email, then POST /verify-2fa with the code emailed \
+              to them) or with a magic link from POST /login/magic-link");
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn unlock(state: &AppState, email: String) -> Result<ExitCode> {
+    let user = find_user(state, email).await?;
+
+    state.user_store.unlock_user(&user.id).await?;
+
+    println!("unlocked {}", display(&user.email));
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn revoke_tokens(state: &AppState, email: String) -> Result<ExitCode> {
+    let user = find_user(state, email).await?;
+
+    revoke_credentials(state, &user).await?;
+
+    println!("revoked the tokens and API keys of {}", display(&user.email));
+    Ok(ExitCode::SUCCESS)
+}
+
+// Every token issued so far and every API key, the user signs in again and makes new keys
+async fn revoke_credentials(state: &AppState, user: &User) -> Result<()> {
+    state.banned_token_store.revoke_user_tokens(&user.id, Utc::now()).await?;
+
+    for api_key in state.api_key_store.get_api_keys(&user.id).await? {
+        match state.api_key_store.delete_api_key(&user.id, &api_key.id).await {
+            // Revoked by the user in the meantime
+            Ok(()) | Err(ApiKeyStoreError::ApiKeyNotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+async fn find_user(state: &AppState, email: String) -> Result<User> {
+    let email = Email::parse(Secret::new(email))?;
+
+    match state.user_store.get_user(&email).await {
+        Ok(user) => Ok(user),
+        Err(UserStoreError::UserNotFound) => Err(eyre!("no user with email {}", display(&email))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn display(email: &Email) -> &str {
+    email.as_ref().expose_secret()
+}
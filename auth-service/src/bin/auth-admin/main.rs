@@ -0,0 +1,140 @@
+// Administration commands, run against the stores configured for the auth service
+// through the same environment variables.
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::{eyre, Result};
+use dotenvy::dotenv;
+
+use auth_service::{
+    factory::build_app_state,
+    utils::{config::{AppConfig, StoreBackend}, constants::env},
+};
+
+mod accounts;
+mod users;
+
+#[derive(Parser)]
+#[command(name = "auth-admin", about = "Administration of the auth service stores")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Imports users with plaintext or hashed passwords, users that already exist are skipped
+    Import {
+        #[arg(long, value_enum, default_value_t = Format::Jsonl)]
+        format: Format,
+        /// Reads from standard input when omitted
+        #[arg(long)]
+        input: Option<PathBuf>,
+    },
+    /// Exports every user with their password hash
+    Export {
+        #[arg(long, value_enum, default_value_t = Format::Jsonl)]
+        format: Format,
+        /// Writes to standard output when omitted
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Recomputes the canonical emails the migration to canonical emails couldn't, run it after
+    /// upgrading and after changing the email policy
+    CanonicalizeEmails,
+    /// Creates a user, passwordless unless --password-stdin is given
+    CreateUser {
+        #[arg(long)]
+        email: String,
+        /// Reads the password from standard input, so that it stays out of the shell history
+        #[arg(long)]
+        password_stdin: bool,
+        #[arg(long, default_value = "user")]
+        role: String,
+        #[arg(long)]
+        requires_2fa: bool,
+    },
+    /// Discards the pending 2FA codes of a user, who then has to log in again
+    #[command(name = "reset-2fa")]
+    Reset2FA {
+        #[arg(long)]
+        email: String,
+    },
+    /// Turns 2FA off for a user
+    #[command(name = "disable-2fa")]
+    Disable2FA {
+        #[arg(long)]
+        email: String,
+    },
+    /// Removes the password of a user, revokes their tokens and API keys and prints a one-time reset token
+    ForcePasswordReset {
+        #[arg(long)]
+        email: String,
+    },
+    /// Unlocks an account locked after too many failed logins
+    Unlock {
+        #[arg(long)]
+        email: String,
+    },
+    /// Revokes every token issued to a user so far and their API keys
+    RevokeTokens {
+        #[arg(long)]
+        email: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Jsonl,
+}
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    dotenv().ok();
+    color_eyre::install()?;
+
+    let cli = Cli::parse();
+    let config = AppConfig::from_env()?;
+    for (env_var, backend) in changed_stores(&cli.command, &config) {
+        if backend == StoreBackend::Memory {
+            return Err(eyre!("{} is memory, changes would only reach a store of this command that is gone once it \
+                              exits, not the one of the running service", env_var));
+        }
+    }
+    let app_state = build_app_state(&config).await?;
+
+    match cli.command {
+        Command::Import { format, input } => users::import(&app_state, format, input).await,
+        Command::Export { format, output } => users::export(&app_state, format, output).await,
+        Command::CanonicalizeEmails => users::canonicalize_emails(&app_state).await,
+        Command::CreateUser { email, password_stdin, role, requires_2fa } =>
+            accounts::create_user(&app_state, email, password_stdin, role, requires_2fa).await,
+        Command::Reset2FA { email } => accounts::reset_2fa(&app_state, email).await,
+        Command::Disable2FA { email } => accounts::disable_2fa(&app_state, email).await,
+        Command::ForcePasswordReset { email } => accounts::force_password_reset(&app_state, email).await,
+        Command::Unlock { email } => accounts::unlock(&app_state, email).await,
+        Command::RevokeTokens { email } => accounts::revoke_tokens(&app_state, email).await,
+    }
+}
+
+// The stores a command reads or changes, they have to be the ones the running service uses
+fn changed_stores(command: &Command, config: &AppConfig) -> Vec<(&'static str, StoreBackend)> {
+    let mut stores = vec![(env::USER_STORE_ENV_VAR, config.user_store)];
+    match command {
+        Command::Reset2FA { .. } | Command::Disable2FA { .. } => {
+            stores.push((env::TWO_FA_CODE_STORE_ENV_VAR, config.two_fa_code_store));
+        }
+        Command::ForcePasswordReset { .. } => stores.extend([
+            (env::BANNED_TOKEN_STORE_ENV_VAR, config.banned_token_store),
+            (env::API_KEY_STORE_ENV_VAR, config.api_key_store),
+            (env::PASSWORD_RESET_STORE_ENV_VAR, config.password_reset_store),
+        ]),
+        Command::RevokeTokens { .. } => stores.extend([
+            (env::BANNED_TOKEN_STORE_ENV_VAR, config.banned_token_store),
+            (env::API_KEY_STORE_ENV_VAR, config.api_key_store),
+        ]),
+        _ => {}
+    }
+    stores
+}
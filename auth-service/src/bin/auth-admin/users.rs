@@ -0,0 +1,275 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use auth_service::{
+    app_state::AppState,
+    domain::{DisplayName, Email, HashedPassword, Password, Role, User, UserId, UserStoreError},
+};
+
+use super::Format;
+
+const PAGE_SIZE: usize = 500;
+
+// Every row is imported on its own, a failed row is reported and the import carries on.
+// Rows of users that already exist are skipped, so an interrupted import can be run again.
+pub async fn import(state: &AppState, format: Format, input: Option<PathBuf>) -> Result<ExitCode> {
+    let reader: Box<dyn Read> = match input {
+        Some(path) => Box::new(File::open(&path).wrap_err(format!("failed to open {}", path.display()))?),
+        None => Box::new(io::stdin()),
+    };
+
+    let (mut imported, mut already_exist, mut failed) = (0, 0, 0);
+    for (line, row) in read_rows(format, reader)? {
+        match import_row(state, row).await {
+            Ok(true) => imported += 1,
+            Ok(false) => already_exist += 1,
+            Err(e) => {
+                failed += 1;
+                eprintln!("line {}: {:#}", line, e);
+            }
+        }
+    }
+
+    eprintln!("imported {}, already existed {}, failed {}", imported, already_exist, failed);
+    Ok(if failed == 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+// Users are exported page by page, so the whole store is never held in memory
+pub async fn export(state: &AppState, format: Format, output: Option<PathBuf>) -> Result<ExitCode> {
+    let writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(File::create(&path).wrap_err(format!("failed to create {}", path.display()))?),
+        None => Box::new(io::stdout()),
+    };
+    let mut writer = RowWriter::new(format, BufWriter::new(writer));
+
+    let mut exported = 0;
+    let mut after: Option<UserId> = None;
+    loop {
+        let users = state.user_store.list_users(after.as_ref(), PAGE_SIZE).await?;
+        for user in &users {
+            writer.write(&ExportedUser::from(user))?;
+        }
+        exported += users.len();
+
+        match users.last() {
+            Some(user) if users.len() == PAGE_SIZE => after = Some(user.id),
+            _ => break,
+        }
+    }
+    writer.flush()?;
+
+    eprintln!("exported {}", exported);
+    Ok(ExitCode::SUCCESS)
+}
+
+// Recomputes the canonical email of every user who can't be found by their own email: rows the
+// migration backfilled with a plain lowercase, or every row after a change of the email policy.
+// Users whose canonical email is already taken by another user are reported and left as they are.
+pub async fn canonicalize_emails(state: &AppState) -> Result<ExitCode> {
+    let (mut updated, mut conflicting) = (0, 0);
+    let mut after: Option<UserId> = None;
+    loop {
+        let users = state.user_store.list_users(after.as_ref(), PAGE_SIZE).await?;
+        for user in &users {
+            let conflict = match state.user_store.get_user(&user.email).await {
+                Ok(found) if found.id == user.id => continue,
+                Ok(found) => Some(found.id),
+                Err(UserStoreError::UserNotFound) => {
+                    match state.user_store.update_email(&user.id, user.email.clone()).await {
+                        Ok(_) => None,
+                        Err(UserStoreError::UserAlreadyExists) => Some(state.user_store.get_user(&user.email).await?.id),
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            match conflict {
+                None => updated += 1,
+                Some(other) => {
+                    conflicting += 1;
+                    eprintln!("user {} ({}) has the canonical email of user {}, merge or rename one of them",
+                              user.id, user.email.as_ref().expose_secret(), other);
+                }
+            }
+        }
+
+        match users.last() {
+            Some(user) if users.len() == PAGE_SIZE => after = Some(user.id),
+            _ => break,
+        }
+    }
+
+    eprintln!("updated {}, conflicting {}", updated, conflicting);
+    Ok(if conflicting == 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+// Returns whether the user was added, `false` when they already exist
+async fn import_row(state: &AppState, row: Result<ImportedUser>) -> Result<bool> {
+    let row = row?;
+
+    let email = Email::parse(Secret::new(row.email))?;
+    let mut user = User::new(email, row.requires_2fa.unwrap_or_default());
+    if let Some(id) = row.id {
+        user.id = UserId::parse(&id)?;
+    }
+    if let Some(role) = row.role {
+        user.role = Role::parse(&role)?;
+    }
+    user.display_name = row.display_name.map(|name| DisplayName::parse(&name)).transpose()?;
+    if let Some(created_at) = row.created_at {
+        user.created_at = created_at;
+    }
+    if let Some(updated_at) = row.updated_at {
+        user.updated_at = updated_at;
+    }
+    user.last_login_at = row.last_login_at;
+
+    let result = match (row.password, row.password_hash) {
+        (Some(_), Some(_)) => return Err(eyre!("only one of password and passwordHash can be set")),
+        (Some(password), None) => state.user_store.add_user(user, Some(Password::parse(password)?)).await,
+        (None, Some(password_hash)) => {
+            let password_hash = HashedPassword::parse(password_hash)?;
+            state.user_store.import_user(user, password_hash).await
+        }
+        // Passwordless accounts sign in with one-time codes only
+        (None, None) => state.user_store.add_user(user, None).await,
+    };
+
+    match result {
+        Ok(()) => Ok(true),
+        Err(UserStoreError::UserAlreadyExists) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Yields every row with the line it starts on, reading the input as the rows are taken
+fn read_rows(format: Format, reader: Box<dyn Read>) -> Result<Box<dyn Iterator<Item = (u64, Result<ImportedUser>)>>> {
+    let rows: Box<dyn Iterator<Item = _>> = match format {
+        Format::Csv => {
+            let mut reader = csv::Reader::from_reader(reader);
+            let headers = reader.headers().wrap_err("failed to read the CSV header")?.clone();
+            Box::new(reader
+                .into_records()
+                .enumerate()
+                .map(move |(i, record)| match record {
+                    Ok(record) => {
+                        let line = record.position().map_or(i as u64 + 2, |position| position.line());
+                        (line, record.deserialize(Some(&headers)).map_err(Into::into))
+                    }
+                    Err(e) => (i as u64 + 2, Err(e.into())),
+                }))
+        }
+        Format::Jsonl => Box::new(BufReader::new(reader)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|(i, line)| {
+                let row = line
+                    .map_err(Into::into)
+                    .and_then(|line| serde_json::from_str(&line).map_err(Into::into));
+                (i as u64 + 1, row)
+            })),
+    };
+
+    Ok(rows)
+}
+
+enum RowWriter<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Jsonl(W),
+}
+
+impl<W: Write> RowWriter<W> {
+    fn new(format: Format, writer: W) -> Self {
+        match format {
+            Format::Csv => Self::Csv(Box::new(csv::Writer::from_writer(writer))),
+            Format::Jsonl => Self::Jsonl(writer),
+        }
+    }
+
+    fn write(&mut self, row: &ExportedUser) -> Result<()> {
+        match self {
+            Self::Csv(writer) => writer.serialize(row)?,
+            Self::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, row)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Csv(writer) => writer.flush()?,
+            Self::Jsonl(writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+// Uses the field names of the export, so an export can be imported as is
+#[derive(Deserialize)]
+struct ImportedUser {
+    id: Option<String>,
+    email: String,
+    password: Option<Secret<String>>,
+    #[serde(rename = "passwordHash")]
+    password_hash: Option<Secret<String>>,
+    #[serde(rename = "requires2FA")]
+    requires_2fa: Option<bool>,
+    role: Option<String>,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    #[serde(rename = "createdAt")]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(rename = "lastLoginAt")]
+    last_login_at: Option<DateTime<Utc>>,
+}
+
+// Passwords only ever leave as the hash the store keeps
+#[derive(Serialize)]
+struct ExportedUser {
+    id: String,
+    email: String,
+    #[serde(rename = "passwordHash")]
+    password_hash: Option<String>,
+    #[serde(rename = "requires2FA")]
+    requires_2fa: bool,
+    role: String,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    #[serde(rename = "createdAt")]
+    created_at: DateTime<Utc>,
+    #[serde(rename = "updatedAt")]
+    updated_at: DateTime<Utc>,
+    #[serde(rename = "lastLoginAt")]
+    last_login_at: Option<DateTime<Utc>>,
+}
+
+impl From<&User> for ExportedUser {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            email: user.email.as_ref().expose_secret().to_owned(),
+            password_hash: user.password_hash.as_ref().map(|hash| hash.as_ref().expose_secret().to_owned()),
+            requires_2fa: user.requires_2fa,
+            role: user.role.as_ref().to_owned(),
+            display_name: user.display_name.as_ref().map(|name| name.as_ref().to_owned()),
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            last_login_at: user.last_login_at,
+        }
+    }
+}
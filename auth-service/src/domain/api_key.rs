@@ -0,0 +1,122 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use rand::{distributions::Alphanumeric, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use super::UserId;
+
+const API_KEY_TAG: &str = "ak";
+const PREFIX_LENGTH: usize = 8;
+const SECRET_LENGTH: usize = 32;
+
+// An API key has the shape `ak_<prefix>_<secret>`. The prefix is stored in clear
+// so that users can tell their keys apart, the whole key is only ever stored hashed.
+#[derive(Debug, Clone)]
+pub struct ApiKey(Secret<String>);
+
+impl ApiKey {
+    pub fn parse(key: Secret<String>) -> Result<Self> {
+        let parts: Vec<&str> = key.expose_secret().split('_').collect();
+        match parts.as_slice() {
+            [tag, prefix, secret]
+                if *tag == API_KEY_TAG
+                    && is_alphanumeric_of_length(prefix, PREFIX_LENGTH)
+                    && is_alphanumeric_of_length(secret, SECRET_LENGTH) =>
+            {
+                Ok(Self(key))
+            }
+            _ => Err(eyre!("Invalid API key")),
+        }
+    }
+
+    pub fn looks_like_api_key(token: &str) -> bool {
+        token.starts_with(&format!("{}_", API_KEY_TAG))
+    }
+
+    pub fn prefix(&self) -> String {
+        self.0.expose_secret()[API_KEY_TAG.len() + 1..][..PREFIX_LENGTH].to_owned()
+    }
+
+    pub fn hash(&self) -> Secret<String> {
+        Secret::new(format!("{:x}", Sha256::digest(self.0.expose_secret().as_bytes())))
+    }
+}
+
+fn is_alphanumeric_of_length(s: &str, length: usize) -> bool {
+    s.len() == length && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn random_alphanumeric(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+impl Default for ApiKey {
+    fn default() -> Self {
+        Self(Secret::new(format!(
+            "{}_{}_{}",
+            API_KEY_TAG,
+            random_alphanumeric(PREFIX_LENGTH),
+            random_alphanumeric(SECRET_LENGTH)
+        )))
+    }
+}
+
+impl AsRef<Secret<String>> for ApiKey {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    // Keys follow the user rather than their email, so they stay with the user through an email change
+    pub user_id: UserId,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: Secret<String>,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyRecord {
+    pub fn new(api_key: &ApiKey,
+               user_id: UserId,
+               name: String,
+               scopes: Vec<String>,
+               expires_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id,
+            name,
+            prefix: api_key.prefix(),
+            key_hash: api_key.hash(),
+            scopes,
+            created_at: Utc::now(),
+            expires_at,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    // Compared in constant time, so that response times don't tell how close a guess was
+    pub fn matches(&self, api_key: &ApiKey) -> bool {
+        let hash = api_key.hash();
+        let same_hash: bool = self.key_hash.expose_secret().as_bytes().ct_eq(hash.expose_secret().as_bytes()).into();
+        self.prefix == api_key.prefix() && same_hash
+    }
+
+    // A key is only good for the scopes it was created with
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
@@ -0,0 +1,155 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+
+use super::{Email, User, UserId};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditEventKind {
+    Signup,
+    Login,
+    TwoFASent,
+    TwoFAVerified,
+    Logout,
+    TokenBanned,
+    EmailChanged,
+    PasswordReset,
+    ImpersonationStarted,
+    ImpersonationEnded,
+}
+
+impl AuditEventKind {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "signup" => Ok(Self::Signup),
+            "login" => Ok(Self::Login),
+            "2fa_sent" => Ok(Self::TwoFASent),
+            "2fa_verified" => Ok(Self::TwoFAVerified),
+            "logout" => Ok(Self::Logout),
+            "token_banned" => Ok(Self::TokenBanned),
+            "email_changed" => Ok(Self::EmailChanged),
+            "password_reset" => Ok(Self::PasswordReset),
+            "impersonation_started" => Ok(Self::ImpersonationStarted),
+            "impersonation_ended" => Ok(Self::ImpersonationEnded),
+            _ => Err(eyre!("{} is not a valid audit event kind.", s)),
+        }
+    }
+}
+
+impl AsRef<str> for AuditEventKind {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Signup => "signup",
+            Self::Login => "login",
+            Self::TwoFASent => "2fa_sent",
+            Self::TwoFAVerified => "2fa_verified",
+            Self::Logout => "logout",
+            Self::TokenBanned => "token_banned",
+            Self::EmailChanged => "email_changed",
+            Self::PasswordReset => "password_reset",
+            Self::ImpersonationStarted => "impersonation_started",
+            Self::ImpersonationEnded => "impersonation_ended",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "success" => Ok(Self::Success),
+            "failure" => Ok(Self::Failure),
+            _ => Err(eyre!("{} is not a valid audit outcome.", s)),
+        }
+    }
+}
+
+impl AsRef<str> for AuditOutcome {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+        }
+    }
+}
+
+// One authentication event. The user is unknown when a login names an email without an account,
+// `detail` says how a success came about or why a failure happened. Impersonation events are about
+// the impersonated user, their `detail` is the id of the admin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEvent {
+    pub occurred_at: DateTime<Utc>,
+    pub kind: AuditEventKind,
+    pub outcome: AuditOutcome,
+    pub user_id: Option<UserId>,
+    pub email: Option<Email>,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditEventKind, outcome: AuditOutcome) -> Self {
+        Self {
+            occurred_at: Utc::now(),
+            kind,
+            outcome,
+            user_id: None,
+            email: None,
+            ip: None,
+            user_agent: None,
+            detail: None,
+        }
+    }
+
+    pub fn with_user(self, user: &User) -> Self {
+        Self { user_id: Some(user.id), ..self.with_email(&user.email) }
+    }
+
+    pub fn with_user_id(self, user_id: UserId) -> Self {
+        Self { user_id: Some(user_id), ..self }
+    }
+
+    pub fn with_email(self, email: &Email) -> Self {
+        Self { email: Some(email.clone()), ..self }
+    }
+
+    pub fn with_detail(self, detail: &str) -> Self {
+        Self { detail: Some(detail.to_owned()), ..self }
+    }
+}
+
+// An event as stored, ids increase in the order events were recorded
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub event: AuditEvent,
+}
+
+// Every set field has to match, the email on its canonical form
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuditEventFilter {
+    pub user_id: Option<UserId>,
+    pub email: Option<Email>,
+    pub kind: Option<AuditEventKind>,
+    pub outcome: Option<AuditOutcome>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditEventFilter {
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        self.user_id.is_none_or(|user_id| event.user_id == Some(user_id))
+            && self.email.as_ref().is_none_or(|email| event.email.as_ref() == Some(email))
+            && self.kind.is_none_or(|kind| event.kind == kind)
+            && self.outcome.is_none_or(|outcome| event.outcome == outcome)
+            && self.since.is_none_or(|since| event.occurred_at >= since)
+            && self.until.is_none_or(|until| event.occurred_at < until)
+    }
+}
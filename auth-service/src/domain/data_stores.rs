@@ -1,30 +1,162 @@
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::Report;
 use thiserror::Error;
 use secrecy::Secret;
 
-use super::{Email, Password, User, LoginAttemptId, TwoFACode};
+use super::{DisplayName, Email, HashedPassword, Password, User, UserId, LoginAttemptId, TwoFACode, ApiKeyRecord, MagicLinkId,
+            DeviceCode, DeviceGrant, DeviceGrantStatus, UserCode, Impersonation, EmailChange, AuditEvent, AuditEventFilter,
+            AuditRecord, PasswordResetToken};
 
+// Every store is shared without an outer lock, each implementation is responsible for its own concurrency.
 #[async_trait::async_trait]
 pub trait UserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    // Keeps the password as its hash, in place of any `password_hash` the user carries
+    async fn add_user(&self, user: User, password: Option<Password>) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
-    async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
+    // Pages through every user ordered by id, starting after `after`
+    async fn list_users(&self, after: Option<&UserId>, limit: usize) -> Result<Vec<User>, UserStoreError>;
+    // Returns the user as read for the check, past any cache, so that the lock and the 2FA setting
+    // are the current ones. Upgrades outdated password hashes once the password has been validated.
+    // Locked users fail with `UserLocked` before the password is checked. Unknown emails and
+    // passwordless users fail with `InvalidCredentials` like a wrong password.
+    async fn validate_user(&self, email: &Email, password: &Password) -> Result<User, UserStoreError>;
+    // Adds a user with a password hashed elsewhere, in place of any `password_hash` the user carries
+    async fn import_user(&self, user: User, password_hash: HashedPassword) -> Result<(), UserStoreError>;
+    // Sets or clears the display name and returns the updated user
+    async fn update_display_name(&self,
+                                 email: &Email,
+                                 display_name: Option<DisplayName>) -> Result<User, UserStoreError>;
+    // Also resets the count of failed logins
+    async fn record_login(&self, email: &Email, at: DateTime<Utc>) -> Result<(), UserStoreError>;
+    // Counts a failed password login, the `max_attempts`th in a row locks the account until `locked_until`
+    async fn record_failed_login(&self,
+                                 email: &Email,
+                                 max_attempts: u32,
+                                 locked_until: DateTime<Utc>) -> Result<(), UserStoreError>;
+    async fn unlock_user(&self, id: &UserId) -> Result<User, UserStoreError>;
+    async fn update_requires_2fa(&self, id: &UserId, requires_2fa: bool) -> Result<User, UserStoreError>;
+    // The user is left to sign in with one-time codes
+    async fn clear_password(&self, id: &UserId) -> Result<User, UserStoreError>;
+    // Also lifts the lock and resets the count of failed logins, the attempts were against the old password
+    async fn set_password(&self, id: &UserId, password: Password) -> Result<User, UserStoreError>;
+    // Fails with `UserAlreadyExists` when the new email belongs to another user
+    async fn update_email(&self, id: &UserId, email: Email) -> Result<User, UserStoreError>;
 }
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn add_banned_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
+    async fn add_banned_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
     async fn is_banned_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError>;
+    // Revokes every token of the user issued up to `at`, without having to know the tokens
+    async fn revoke_user_tokens(&self, user_id: &UserId, at: DateTime<Utc>) -> Result<(), BannedTokenStoreError>;
+    async fn is_revoked_user_token(&self,
+                                   user_id: &UserId,
+                                   issued_at: DateTime<Utc>) -> Result<bool, BannedTokenStoreError>;
+    // Until when tokens of the user are revoked, if they ever were and the revocation hasn't expired
+    async fn user_tokens_revoked_at(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>, BannedTokenStoreError>;
 }
 
+// Pending codes are keyed by their login attempt and bound to the email they were sent to,
+// so that logins from several devices can be completed side by side
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
-    async fn add_two_fa_code(&mut self, 
+    // Only a `TwoFACodeHash` of the code is kept. Each store caps the codes pending for an email,
+    // going over the cap drops the oldest.
+    async fn add_two_fa_code(&self, 
                              email: &Email, 
                              login_attempt_id: LoginAttemptId, 
                              two_fa_code: TwoFACode) -> Result<(), TwoFACodeStoreError>;
-    async fn get_two_fa_code(&self, email: &Email) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
-    async fn delete_two_fa_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    // Consumes the login attempt: checks the code and ends the attempt in one step, so that a code is
+    // only ever accepted once. A wrong code ends the attempt as well. Fails with `LoginAttemptIdNotFound`,
+    // and leaves the attempt alone, when it isn't pending or was made for another email.
+    async fn verify_two_fa_code(&self,
+                                email: &Email,
+                                login_attempt_id: &LoginAttemptId,
+                                two_fa_code: &TwoFACode) -> Result<bool, TwoFACodeStoreError>;
+    // Succeeds when the attempt isn't pending
+    async fn delete_two_fa_code(&self,
+                                email: &Email,
+                                login_attempt_id: &LoginAttemptId) -> Result<(), TwoFACodeStoreError>;
+    // Drops every code pending for the email
+    async fn delete_two_fa_codes(&self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait ApiKeyStore {
+    async fn add_api_key(&self, api_key: ApiKeyRecord) -> Result<(), ApiKeyStoreError>;
+    async fn get_api_key(&self, prefix: &str) -> Result<ApiKeyRecord, ApiKeyStoreError>;
+    async fn get_api_keys(&self, user_id: &UserId) -> Result<Vec<ApiKeyRecord>, ApiKeyStoreError>;
+    async fn delete_api_key(&self, user_id: &UserId, id: &str) -> Result<(), ApiKeyStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait MagicLinkStore {
+    async fn add_magic_link(&self,
+                            user_id: &UserId,
+                            magic_link_id: MagicLinkId) -> Result<(), MagicLinkStoreError>;
+    // Removes the magic link so that it can only be used once
+    async fn take_magic_link(&self, magic_link_id: &MagicLinkId) -> Result<UserId, MagicLinkStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait PasswordResetStore {
+    async fn add_password_reset(&self,
+                                user_id: &UserId,
+                                token: PasswordResetToken) -> Result<(), PasswordResetStoreError>;
+    // Removes the reset so that its token can only be used once
+    async fn take_password_reset(&self, token: &PasswordResetToken) -> Result<UserId, PasswordResetStoreError>;
+}
+
+// A grant changes state in one step each time, so that concurrent approvals and polls can't both win
+#[async_trait::async_trait]
+pub trait DeviceGrantStore {
+    // Fails with `UserCodeAlreadyExists` when another grant holds the user code
+    async fn add_device_grant(&self, device_grant: DeviceGrant) -> Result<(), DeviceGrantStoreError>;
+    // Approves or denies the grant of the user code. Fails with `DeviceGrantNotFound` unless it's pending.
+    async fn decide_device_grant(&self,
+                                 user_code: &UserCode,
+                                 status: DeviceGrantStatus) -> Result<(), DeviceGrantStoreError>;
+    // Returns the grant as it was before the poll. A pending grant records `now` as its last poll,
+    // a decided one is removed so that its device code is used only once. Fails with `DeviceGrantExpired`
+    // for a grant kept past its expiry, stores that drop expired grants report `DeviceGrantNotFound`.
+    async fn poll_device_grant(&self,
+                               device_code: &DeviceCode,
+                               now: DateTime<Utc>) -> Result<DeviceGrant, DeviceGrantStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait ImpersonationStore {
+    async fn add_impersonation(&self, impersonation: Impersonation) -> Result<(), ImpersonationStoreError>;
+    async fn get_impersonation(&self, id: &str) -> Result<Impersonation, ImpersonationStoreError>;
+    // Impersonations where the user was the admin or the impersonated user, oldest first
+    async fn get_impersonations(&self, user_id: &UserId) -> Result<Vec<Impersonation>, ImpersonationStoreError>;
+    async fn end_impersonation(&self, id: &str, ended_at: DateTime<Utc>) -> Result<(), ImpersonationStoreError>;
+}
+
+// Pending changes expire after `EMAIL_CHANGE_TTL_SECONDS_I64`, a new request replaces the pending one
+#[async_trait::async_trait]
+pub trait EmailChangeStore {
+    // Only a `TwoFACodeHash` of the code is kept, keyed like the 2FA codes
+    async fn add_email_change(&self, email_change: EmailChange, code: &TwoFACode) -> Result<(), EmailChangeStoreError>;
+    async fn get_email_change(&self, user_id: &UserId) -> Result<EmailChange, EmailChangeStoreError>;
+    // Checks the code of the pending change, without counting a wrong one
+    async fn verify_email_change_code(&self, user_id: &UserId, code: &TwoFACode) -> Result<bool, EmailChangeStoreError>;
+    // Counts a wrong confirmation code, the `max_attempts`th drops the pending change.
+    // Returns whether the change was dropped.
+    async fn record_failed_attempt(&self, user_id: &UserId, max_attempts: u32) -> Result<bool, EmailChangeStoreError>;
+    async fn delete_email_change(&self, user_id: &UserId) -> Result<(), EmailChangeStoreError>;
+}
+
+// Append-only, events are never updated or deleted
+#[async_trait::async_trait]
+pub trait AuditLog {
+    async fn record(&self, event: AuditEvent) -> Result<(), AuditLogError>;
+    // Newest first, starting before the record with id `before`
+    async fn query(&self,
+                   filter: &AuditEventFilter,
+                   before: Option<i64>,
+                   limit: usize) -> Result<Vec<AuditRecord>, AuditLogError>;
 }
 
 #[derive(Debug, Error)]
@@ -35,6 +167,8 @@ pub enum UserStoreError {
     UserNotFound,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("User locked")]
+    UserLocked,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -46,6 +180,7 @@ impl PartialEq for UserStoreError {
             (Self::UserAlreadyExists, Self::UserAlreadyExists)
                 | (Self::UserNotFound, Self::UserNotFound)
                 | (Self::InvalidCredentials, Self::InvalidCredentials)
+                | (Self::UserLocked, Self::UserLocked)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
@@ -74,3 +209,126 @@ impl PartialEq for TwoFACodeStoreError {
         )
     }
 }
+
+#[derive(Debug, Error)]
+pub enum ApiKeyStoreError {
+    #[error("API key already exists")]
+    ApiKeyAlreadyExists,
+    #[error("API key not found")]
+    ApiKeyNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for ApiKeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ApiKeyAlreadyExists, Self::ApiKeyAlreadyExists)
+                | (Self::ApiKeyNotFound, Self::ApiKeyNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MagicLinkStoreError {
+    #[error("Magic link not found")]
+    MagicLinkNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for MagicLinkStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::MagicLinkNotFound, Self::MagicLinkNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordResetStoreError {
+    #[error("Password reset not found")]
+    PasswordResetNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasswordResetStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::PasswordResetNotFound, Self::PasswordResetNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DeviceGrantStoreError {
+    #[error("Device grant not found")]
+    DeviceGrantNotFound,
+    #[error("Device grant expired")]
+    DeviceGrantExpired,
+    #[error("User code already exists")]
+    UserCodeAlreadyExists,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for DeviceGrantStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::DeviceGrantNotFound, Self::DeviceGrantNotFound)
+                | (Self::DeviceGrantExpired, Self::DeviceGrantExpired)
+                | (Self::UserCodeAlreadyExists, Self::UserCodeAlreadyExists)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ImpersonationStoreError {
+    #[error("Impersonation not found")]
+    ImpersonationNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for ImpersonationStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ImpersonationNotFound, Self::ImpersonationNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum EmailChangeStoreError {
+    #[error("Email change not found")]
+    EmailChangeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for EmailChangeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::EmailChangeNotFound, Self::EmailChangeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AuditLogError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
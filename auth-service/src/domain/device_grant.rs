@@ -0,0 +1,114 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
+
+use super::UserId;
+
+// Consonants only, so that user codes are easy to type and never spell words (RFC 8628, section 6.1)
+const USER_CODE_CHARSET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct DeviceCode(Secret<String>);
+
+impl DeviceCode {
+    pub fn parse(code: Secret<String>) -> Result<Self> {
+        let parsed_code =
+            uuid::Uuid::parse_str(code.expose_secret()).wrap_err("Invalid device code")?;
+        Ok(Self(Secret::new(parsed_code.to_string())))
+    }
+}
+
+impl Default for DeviceCode {
+    fn default() -> Self {
+        Self(Secret::new(uuid::Uuid::new_v4().to_string()))
+    }
+}
+
+impl PartialEq for DeviceCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl Eq for DeviceCode {}
+
+impl AsRef<Secret<String>> for DeviceCode {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// The short code the user types in the browser, stored without its separator
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UserCode(String);
+
+impl UserCode {
+    pub fn parse(code: &str) -> Result<Self> {
+        let normalized: String = code
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if normalized.len() == USER_CODE_LENGTH
+            && normalized.bytes().all(|c| USER_CODE_CHARSET.contains(&c)) {
+            Ok(Self(normalized))
+        } else {
+            Err(eyre!("Invalid user code"))
+        }
+    }
+
+    // Formats the code as XXXX-XXXX for display
+    pub fn display(&self) -> String {
+        let (first, second) = self.0.split_at(USER_CODE_LENGTH / 2);
+        format!("{}-{}", first, second)
+    }
+}
+
+impl Default for UserCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        Self((0..USER_CODE_LENGTH)
+            .map(|_| USER_CODE_CHARSET[rng.gen_range(0..USER_CODE_CHARSET.len())] as char)
+            .collect())
+    }
+}
+
+impl AsRef<str> for UserCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceGrantStatus {
+    Pending,
+    Approved(UserId),
+    Denied,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceGrant {
+    pub device_code: DeviceCode,
+    pub user_code: UserCode,
+    pub status: DeviceGrantStatus,
+    pub expires_at: DateTime<Utc>,
+    pub last_polled_at: Option<DateTime<Utc>>,
+}
+
+impl DeviceGrant {
+    pub fn new(expires_in: Duration) -> Self {
+        Self {
+            device_code: DeviceCode::default(),
+            user_code: UserCode::default(),
+            status: DeviceGrantStatus::Pending,
+            expires_at: Utc::now() + expires_in,
+            last_polled_at: None,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
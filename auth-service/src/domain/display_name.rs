@@ -0,0 +1,22 @@
+use color_eyre::eyre::{eyre, Result};
+
+const MAX_DISPLAY_NAME_CHARS: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplayName(String);
+
+impl DisplayName {
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() || s.chars().count() > MAX_DISPLAY_NAME_CHARS || s.chars().any(char::is_control) {
+            return Err(eyre!("display name must be 1 to {} printable characters", MAX_DISPLAY_NAME_CHARS));
+        }
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl AsRef<str> for DisplayName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
@@ -1,20 +1,25 @@
-use std::hash::Hash;
+use std::{hash::Hash, sync::OnceLock};
 use validator::validate_email;
 use color_eyre::eyre::{eyre, Result};
 use secrecy::{ExposeSecret, Secret};
 
+// Keeps the address as the user entered it for display, and a canonical form
+// that decides whether two addresses belong to the same account.
 #[derive(Debug, Clone)]
-pub struct Email(Secret<String>);
+pub struct Email {
+    display: Secret<String>,
+    canonical: Secret<String>,
+}
 
 impl PartialEq for Email {
     fn eq(&self, other: &Self) -> bool {
-        self.0.expose_secret() == other.0.expose_secret()
+        self.canonical.expose_secret() == other.canonical.expose_secret()
     }
 }
 
 impl Hash for Email {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.0.expose_secret().hash(state);
+        self.canonical.expose_secret().hash(state);
     }
 }
 
@@ -22,16 +27,87 @@ impl Eq for Email {}
 
 impl Email {
     pub fn parse(s: Secret<String>) -> Result<Email> {
-        if validate_email(s.expose_secret()) {
-            Ok(Self(s))
-        } else {
-            Err(eyre!(format!("{} is not a valid email.", s.expose_secret())))
+        Self::parse_with_policy(s, EmailPolicy::current())
+    }
+
+    pub fn parse_with_policy(s: Secret<String>, policy: &EmailPolicy) -> Result<Email> {
+        let display = s.expose_secret().trim();
+        if !validate_email(display) {
+            return Err(eyre!(format!("{} is not a valid email.", s.expose_secret())));
         }
+
+        let canonical = canonicalize(display, policy)
+            .ok_or_else(|| eyre!(format!("{} is not a valid email.", s.expose_secret())))?;
+
+        Ok(Self {
+            display: Secret::new(display.to_owned()),
+            canonical: Secret::new(canonical),
+        })
+    }
+
+    // Lookups and uniqueness checks use this form, never the display one
+    pub fn canonical(&self) -> &Secret<String> {
+        &self.canonical
     }
 }
 
 impl AsRef<Secret<String>> for Email {
     fn as_ref(&self) -> &Secret<String> {
-        &self.0
+        &self.display
+    }
+}
+
+// How the local part of an address is compared, domains are always compared
+// lowercased and in punycode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmailPolicy {
+    // RFC 5321 makes the local part case-sensitive, but virtually no provider treats it that way
+    pub case_insensitive_local_part: bool,
+    // Drops a `+tag` suffix, so `alice+news@example.com` is the account of `alice@example.com`
+    pub ignore_plus_tag: bool,
+}
+
+impl Default for EmailPolicy {
+    fn default() -> Self {
+        Self {
+            case_insensitive_local_part: true,
+            ignore_plus_tag: false,
+        }
     }
-}
\ No newline at end of file
+}
+
+static EMAIL_POLICY: OnceLock<EmailPolicy> = OnceLock::new();
+
+impl EmailPolicy {
+    // Sets the policy used by `Email::parse`, it can only be set once, before any email is parsed
+    pub fn install(policy: EmailPolicy) -> Result<()> {
+        let installed = *EMAIL_POLICY.get_or_init(|| policy);
+        if installed != policy {
+            return Err(eyre!("a different email policy is already installed"));
+        }
+        Ok(())
+    }
+
+    pub fn current() -> &'static EmailPolicy {
+        EMAIL_POLICY.get_or_init(EmailPolicy::default)
+    }
+}
+
+fn canonicalize(email: &str, policy: &EmailPolicy) -> Option<String> {
+    let (local_part, domain) = email.rsplit_once('@')?;
+
+    let local_part = match local_part.split_once('+') {
+        Some((local_part, _tag)) if policy.ignore_plus_tag && !local_part.is_empty() => local_part,
+        _ => local_part,
+    };
+    let local_part = if policy.case_insensitive_local_part {
+        local_part.to_lowercase()
+    } else {
+        local_part.to_owned()
+    };
+
+    // Lowercases the domain as well as converting internationalized domains to punycode
+    let domain = idna::domain_to_ascii(domain).ok()?;
+
+    Some(format!("{}@{}", local_part, domain))
+}
@@ -0,0 +1,15 @@
+use super::{Email, UserId};
+
+// A change of email waiting for the new address to be confirmed with the code sent to it. The stores
+// keep the code apart, as a `TwoFACodeHash`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailChange {
+    pub user_id: UserId,
+    pub new_email: Email,
+}
+
+impl EmailChange {
+    pub fn new(user_id: UserId, new_email: Email) -> Self {
+        Self { user_id, new_email }
+    }
+}
@@ -19,6 +19,42 @@ pub enum AuthAPIError {
     Invalid2FACode,
     #[error("InvalidLoginAttamptId")]
     InvalidLoginAttamptId,
+    #[error("Invalid API key request")]
+    InvalidApiKeyRequest,
+    #[error("API key not found")]
+    ApiKeyNotFound,
+    #[error("Invalid user code")]
+    InvalidUserCode,
+    #[error("Authorization pending")]
+    AuthorizationPending,
+    #[error("Slow down")]
+    SlowDown,
+    #[error("Access denied")]
+    AccessDenied,
+    #[error("Expired token")]
+    ExpiredToken,
+    #[error("Invalid grant")]
+    InvalidGrant,
+    #[error("Unsupported grant type")]
+    UnsupportedGrantType,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("Invalid password hash")]
+    InvalidPasswordHash,
+    #[error("Invalid display name")]
+    InvalidDisplayName,
+    #[error("Invalid confirmation code")]
+    InvalidConfirmationCode,
+    #[error("Account locked")]
+    AccountLocked,
+    #[error("Too many failed logins")]
+    TooManyFailedLogins,
+    #[error("Too many login codes")]
+    TooManyLoginCodes,
+    #[error("Invalid audit query")]
+    InvalidAuditQuery,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report)
 }
\ No newline at end of file
@@ -0,0 +1,40 @@
+use argon2::password_hash::PasswordHash;
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+
+// Password hash as the user stores keep it, made by the password hasher or by a legacy system
+// users are migrated from. Argon2 and scrypt hashes are PHC strings, bcrypt hashes use the modular crypt format.
+#[derive(Debug, Clone)]
+pub struct HashedPassword(Secret<String>);
+
+impl PartialEq for HashedPassword {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl HashedPassword {
+    pub fn parse(s: Secret<String>) -> Result<HashedPassword> {
+        if is_bcrypt_hash(s.expose_secret()) || is_supported_phc_hash(s.expose_secret()) {
+            Ok(Self(s))
+        } else {
+            Err(eyre!("password hash is not an argon2, scrypt or bcrypt hash"))
+        }
+    }
+}
+
+pub fn is_bcrypt_hash(s: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| s.starts_with(prefix)) && s.len() == 60
+}
+
+fn is_supported_phc_hash(s: &str) -> bool {
+    PasswordHash::new(s).is_ok_and(|hash| {
+        ["argon2id", "argon2i", "argon2d", "scrypt"].contains(&hash.algorithm.as_str()) && hash.hash.is_some()
+    })
+}
+
+impl AsRef<Secret<String>> for HashedPassword {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Duration, Utc};
+
+use super::UserId;
+
+// Audit record of an admin acting as another user
+#[derive(Debug, Clone)]
+pub struct Impersonation {
+    pub id: String,
+    pub admin_id: UserId,
+    pub user_id: UserId,
+    pub reason: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl Impersonation {
+    pub fn new(admin_id: UserId, user_id: UserId, reason: Option<String>, ttl: Duration) -> Self {
+        let started_at = Utc::now();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            admin_id,
+            user_id,
+            reason,
+            started_at,
+            expires_at: started_at + ttl,
+            ended_at: None,
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, Secret};
+
+#[derive(Debug, Clone)]
+pub struct MagicLinkId(Secret<String>);
+
+impl MagicLinkId {
+    pub fn parse(id: Secret<String>) -> Result<Self> {
+        let parsed_id =
+            uuid::Uuid::parse_str(id.expose_secret()).wrap_err("Invalid magic link id")?;
+        Ok(Self(Secret::new(parsed_id.to_string())))
+    }
+}
+
+impl Default for MagicLinkId {
+    fn default() -> Self {
+        Self(Secret::new(uuid::Uuid::new_v4().to_string()))
+    }
+}
+
+impl PartialEq for MagicLinkId {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl Eq for MagicLinkId {}
+
+impl AsRef<Secret<String>> for MagicLinkId {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
@@ -6,6 +6,19 @@ pub mod user;
 pub mod login_attempt_id;
 pub mod two_fa_code;
 pub mod email_client;
+pub mod api_key;
+pub mod magic_link_id;
+pub mod device_grant;
+pub mod role;
+pub mod impersonation;
+pub mod password_hasher;
+pub mod hashed_password;
+pub mod display_name;
+pub mod user_id;
+pub mod email_change;
+pub mod audit_event;
+pub mod personal_data;
+pub mod password_reset_token;
 
 pub use data_stores::*;
 pub use email::*;
@@ -15,6 +28,19 @@ pub use password::*;
 pub use login_attempt_id::*;
 pub use two_fa_code::*;
 pub use email_client::*;
+pub use api_key::*;
+pub use magic_link_id::*;
+pub use device_grant::*;
+pub use role::*;
+pub use impersonation::*;
+pub use password_hasher::*;
+pub use hashed_password::*;
+pub use display_name::*;
+pub use user_id::*;
+pub use email_change::*;
+pub use audit_event::*;
+pub use personal_data::*;
+pub use password_reset_token::*;
 
 
 
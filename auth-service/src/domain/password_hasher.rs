@@ -0,0 +1,12 @@
+use color_eyre::eyre::Result;
+use super::{HashedPassword, Password};
+
+// Hashes passwords before any user store persists them, so no backend holds plaintext
+#[async_trait::async_trait]
+pub trait PasswordHasher {
+    async fn hash_password(&self, password: &Password) -> Result<HashedPassword>;
+    // Accepts any supported algorithm, so hashes imported from other systems keep working
+    async fn verify_password(&self, password_hash: &HashedPassword, candidate: &Password) -> Result<()>;
+    // Whether the hash should be replaced by one with the current algorithm and parameters
+    fn needs_rehash(&self, password_hash: &HashedPassword) -> bool;
+}
@@ -0,0 +1,34 @@
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, Secret};
+
+// One-time token that lets a user set a new password, handed out by auth-admin
+#[derive(Debug, Clone)]
+pub struct PasswordResetToken(Secret<String>);
+
+impl PasswordResetToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        let parsed_token =
+            uuid::Uuid::parse_str(token.expose_secret()).wrap_err("Invalid password reset token")?;
+        Ok(Self(Secret::new(parsed_token.to_string())))
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        Self(Secret::new(uuid::Uuid::new_v4().to_string()))
+    }
+}
+
+impl PartialEq for PasswordResetToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl Eq for PasswordResetToken {}
+
+impl AsRef<Secret<String>> for PasswordResetToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
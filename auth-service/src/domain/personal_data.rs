@@ -0,0 +1,19 @@
+use color_eyre::eyre::Report;
+use serde_json::Value;
+use thiserror::Error;
+
+use super::User;
+
+// Contributes one section of a user's personal data export, keyed by `section()`.
+// Secrets such as password hashes and one-time codes are never part of it.
+#[async_trait::async_trait]
+pub trait PersonalDataExporter {
+    fn section(&self) -> &'static str;
+    async fn export(&self, user: &User) -> Result<Value, PersonalDataExportError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PersonalDataExportError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
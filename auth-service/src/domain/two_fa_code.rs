@@ -1,6 +1,11 @@
+use hmac::{Hmac, Mac};
 use rand::prelude::*;
 use color_eyre::eyre::{eyre, Context, Result};
 use secrecy::{Secret, ExposeSecret};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use super::{LoginAttemptId, UserId};
 
 const LOW_RANGE_VALUE:u32 = 100000;
 const HIGH_RANGE_VALUE:u32 = 999999;
@@ -40,3 +45,57 @@ impl AsRef<Secret<String>> for TwoFACode {
         &self.0
     }
 }
+
+// The stores only keep this keyed hash of a code and what it was sent for, a login attempt or a
+// change of email. A plain hash wouldn't do, anyone reading the store could hash the few possible codes.
+#[derive(Debug, Clone)]
+pub struct TwoFACodeHash(Secret<String>);
+
+impl TwoFACodeHash {
+    pub fn new(login_attempt_id: &LoginAttemptId, code: &TwoFACode, key: &Secret<String>) -> Self {
+        Self::keyed(login_attempt_id.as_ref().expose_secret(), code, key)
+    }
+
+    // Prefixed, so that it never equals the hash of a login attempt
+    pub fn for_email_change(user_id: &UserId, code: &TwoFACode, key: &Secret<String>) -> Self {
+        Self::keyed(&format!("email_change:{}", user_id), code, key)
+    }
+
+    fn keyed(sent_for: &str, code: &TwoFACode, key: &Secret<String>) -> Self {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.expose_secret().as_bytes())
+            .expect("HMAC takes keys of any length");
+        mac.update(sent_for.as_bytes());
+        mac.update(b":");
+        mac.update(code.as_ref().expose_secret().as_bytes());
+        Self(Secret::new(format!("{:x}", mac.finalize().into_bytes())))
+    }
+
+    pub fn parse(hash: Secret<String>) -> Result<Self> {
+        let is_sha256_hex = hash.expose_secret().len() == 64
+            && hash.expose_secret().chars().all(|c| c.is_ascii_hexdigit());
+        if is_sha256_hex {
+            Ok(Self(hash))
+        } else {
+            Err(eyre!("Invalid 2FA code hash"))
+        }
+    }
+
+    // Compared in constant time, so that response times don't tell how close a guess was
+    pub fn matches(&self, login_attempt_id: &LoginAttemptId, code: &TwoFACode, key: &Secret<String>) -> bool {
+        self.equals(&Self::new(login_attempt_id, code, key))
+    }
+
+    pub fn matches_email_change(&self, user_id: &UserId, code: &TwoFACode, key: &Secret<String>) -> bool {
+        self.equals(&Self::for_email_change(user_id, code, key))
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        self.0.expose_secret().as_bytes().ct_eq(other.0.expose_secret().as_bytes()).into()
+    }
+}
+
+impl AsRef<Secret<String>> for TwoFACodeHash {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
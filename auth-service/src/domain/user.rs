@@ -1,19 +1,43 @@
-use super::{Email, Password};
+use chrono::{DateTime, Utc};
+
+use super::{DisplayName, Email, HashedPassword, Role, UserId};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct User {
+    pub id: UserId,
     pub email: Email,
-    pub password: Password,
+    // Passwordless accounts sign in with one-time codes only
+    pub password_hash: Option<HashedPassword>,
     pub requires_2fa: bool,
+    pub role: Role,
+    pub display_name: Option<DisplayName>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    // Consecutive failed password logins, reset by a successful login or when the account locks
+    pub failed_login_attempts: u32,
+    pub locked_until: Option<DateTime<Utc>>,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
+    pub fn new(email: Email, requires_2fa: bool) -> Self {
+        let now = Utc::now();
         Self {
+            id: UserId::default(),
             email,
-            password,
+            password_hash: None,
             requires_2fa,
+            role: Role::default(),
+            display_name: None,
+            created_at: now,
+            updated_at: now,
+            last_login_at: None,
+            failed_login_attempts: 0,
+            locked_until: None,
         }
     }
-}
 
+    pub fn is_locked(&self, now: DateTime<Utc>) -> bool {
+        self.locked_until.is_some_and(|locked_until| locked_until > now)
+    }
+}
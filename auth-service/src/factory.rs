@@ -0,0 +1,250 @@
+use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use reqwest::Client;
+use secrecy::Secret;
+use redis::aio::ConnectionManager;
+use sqlx::{PgPool, SqlitePool};
+
+use crate::{
+    app_state::{AppState, ApiKeyStoreType, AuditLogType, BannedTokenStoreType, DeviceGrantStoreType, EmailClientType,
+                EmailChangeStoreType, ImpersonationStoreType, MagicLinkStoreType, PasswordHasherType, PasswordResetStoreType,
+                TwoFACodeStoreType, UserStoreType},
+    domain::{Email, EmailPolicy, UserStore},
+    get_postgres_pool,
+    get_sqlite_pool,
+    get_redis_client,
+    services::{argon2_password_hasher::Argon2PasswordHasher,
+               data_stores::{cached_user_store::CachedUserStore,
+                             hashmap_api_key_store::HashmapApiKeyStore,
+                             hashmap_device_grant_store::HashmapDeviceGrantStore,
+                             hashmap_email_change_store::HashmapEmailChangeStore,
+                             hashmap_impersonation_store::HashmapImpersonationStore,
+                             hashmap_magic_link_store::HashmapMagicLinkStore,
+                             hashmap_password_reset_store::HashmapPasswordResetStore,
+                             hashmap_two_fa_store::HashmapTwoFACodeStore,
+                             hashmap_user_store::HashmapUserStore,
+                             hashset_banned_token_store::HashsetBannedTokenStore,
+                             postgres_api_key_store::PostgresApiKeyStore,
+                             postgres_audit_log::PostgresAuditLog,
+                             postgres_banned_token_store::PostgresBannedTokenStore,
+                             postgres_impersonation_store::PostgresImpersonationStore,
+                             postgres_two_fa_store::PostgresTwoFACodeStore,
+                             postgres_user_store::PostgresUserStore,
+                             redis_banned_token_store::RedisBannedTokenStore,
+                             redis_device_grant_store::RedisDeviceGrantStore,
+                             redis_email_change_store::RedisEmailChangeStore,
+                             redis_magic_link_store::RedisMagicLinkStore,
+                             redis_password_reset_store::RedisPasswordResetStore,
+                             redis_two_fa_store::RedisTwoFACodeStore,
+                             sqlite_banned_token_store::SqliteBannedTokenStore,
+                             sqlite_two_fa_store::SqliteTwoFACodeStore,
+                             sqlite_user_store::SqliteUserStore,
+                             vec_audit_log::VecAuditLog},
+               mock_email_client::MockEmailClient,
+               postgres_sweeper,
+               sqlite_sweeper,
+               postmark_email_client::PostmarkEmailClient},
+    utils::{config::{AppConfig, EmailClientBackend, StoreBackend, UserCacheConfig},
+            constants::{env, prod, DATABASE_URL, PASSWORD_PEPPER, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME, SQLITE_DATABASE_URL,
+                        SWEEP_INTERVAL_SECONDS_U64, TWO_FA_CODE_SECRET}},
+};
+
+// Builds the application state with the backends selected by the configuration.
+// Postgres, Redis and SQLite are only connected to when at least one store uses them.
+pub async fn build_app_state(config: &AppConfig) -> Result<AppState> {
+    // Installed before any store is built, every email parsed from here on is canonicalized with it
+    EmailPolicy::install(config.email_policy)?;
+
+    let pg_pool = if config.uses(StoreBackend::Postgres) {
+        Some(configure_postgresql().await?)
+    } else {
+        None
+    };
+
+    let redis_connection = if config.uses(StoreBackend::Redis) {
+        Some(configure_redis().await?)
+    } else {
+        None
+    };
+
+    let sqlite_pool = if config.uses(StoreBackend::Sqlite) {
+        Some(configure_sqlite().await?)
+    } else {
+        None
+    };
+
+    let password_hasher: PasswordHasherType =
+        Arc::new(Argon2PasswordHasher::new(&config.password_hash, PASSWORD_PEPPER.clone())?);
+
+    let user_store: UserStoreType = match config.user_store {
+        StoreBackend::Memory => Arc::new(HashmapUserStore::new(password_hasher)),
+        StoreBackend::Postgres => {
+            cached(PostgresUserStore::new(postgres(&pg_pool)?, password_hasher), config.user_cache)
+        }
+        StoreBackend::Sqlite => cached(SqliteUserStore::new(sqlite(&sqlite_pool)?, password_hasher), config.user_cache),
+        backend => return Err(unsupported("user store", backend)),
+    };
+
+    let banned_token_store: BannedTokenStoreType = match config.banned_token_store {
+        StoreBackend::Memory => Arc::new(HashsetBannedTokenStore::default()),
+        StoreBackend::Postgres => Arc::new(PostgresBannedTokenStore::new(postgres(&pg_pool)?)),
+        StoreBackend::Redis => Arc::new(RedisBannedTokenStore::new(redis(&redis_connection)?)),
+        StoreBackend::Sqlite => Arc::new(SqliteBannedTokenStore::new(sqlite(&sqlite_pool)?)),
+    };
+
+    let max_pending = NonZeroUsize::new(config.two_fa_max_pending_attempts as usize)
+        .ok_or(eyre!("{} must be at least 1", env::TWO_FA_MAX_PENDING_ATTEMPTS_ENV_VAR))?;
+    let two_fa_code_store: TwoFACodeStoreType = match config.two_fa_code_store {
+        StoreBackend::Memory => Arc::new(HashmapTwoFACodeStore::new(max_pending, TWO_FA_CODE_SECRET.clone())),
+        StoreBackend::Postgres => Arc::new(PostgresTwoFACodeStore::new(postgres(&pg_pool)?, max_pending, TWO_FA_CODE_SECRET.clone())),
+        StoreBackend::Redis => Arc::new(RedisTwoFACodeStore::new(redis(&redis_connection)?, max_pending, TWO_FA_CODE_SECRET.clone())),
+        StoreBackend::Sqlite => Arc::new(SqliteTwoFACodeStore::new(sqlite(&sqlite_pool)?, max_pending, TWO_FA_CODE_SECRET.clone())),
+    };
+
+    // Postgres and SQLite don't expire rows on their own like Redis does
+    let sweep_interval = Duration::from_secs(SWEEP_INTERVAL_SECONDS_U64);
+    if config.banned_token_store == StoreBackend::Postgres || config.two_fa_code_store == StoreBackend::Postgres {
+        postgres_sweeper::spawn_expired_rows_sweeper(postgres(&pg_pool)?, sweep_interval);
+    }
+    if config.banned_token_store == StoreBackend::Sqlite || config.two_fa_code_store == StoreBackend::Sqlite {
+        sqlite_sweeper::spawn_expired_rows_sweeper(sqlite(&sqlite_pool)?, sweep_interval);
+    }
+
+    let api_key_store: ApiKeyStoreType = match config.api_key_store {
+        StoreBackend::Memory => Arc::new(HashmapApiKeyStore::default()),
+        StoreBackend::Postgres => Arc::new(PostgresApiKeyStore::new(postgres(&pg_pool)?)),
+        backend => return Err(unsupported("API key store", backend)),
+    };
+
+    let magic_link_store: MagicLinkStoreType = match config.magic_link_store {
+        StoreBackend::Memory => Arc::new(HashmapMagicLinkStore::default()),
+        StoreBackend::Redis => Arc::new(RedisMagicLinkStore::new(redis(&redis_connection)?)),
+        backend => return Err(unsupported("magic link store", backend)),
+    };
+
+    let password_reset_store: PasswordResetStoreType = match config.password_reset_store {
+        StoreBackend::Memory => Arc::new(HashmapPasswordResetStore::default()),
+        StoreBackend::Redis => Arc::new(RedisPasswordResetStore::new(redis(&redis_connection)?)),
+        backend => return Err(unsupported("password reset store", backend)),
+    };
+
+    let device_grant_store: DeviceGrantStoreType = match config.device_grant_store {
+        StoreBackend::Memory => Arc::new(HashmapDeviceGrantStore::default()),
+        StoreBackend::Redis => Arc::new(RedisDeviceGrantStore::new(redis(&redis_connection)?)),
+        backend => return Err(unsupported("device grant store", backend)),
+    };
+
+    let impersonation_store: ImpersonationStoreType = match config.impersonation_store {
+        StoreBackend::Memory => Arc::new(HashmapImpersonationStore::default()),
+        StoreBackend::Postgres => Arc::new(PostgresImpersonationStore::new(postgres(&pg_pool)?)),
+        backend => return Err(unsupported("impersonation store", backend)),
+    };
+
+    let email_change_store: EmailChangeStoreType = match config.email_change_store {
+        StoreBackend::Memory => Arc::new(HashmapEmailChangeStore::new(TWO_FA_CODE_SECRET.clone())),
+        StoreBackend::Redis => Arc::new(RedisEmailChangeStore::new(redis(&redis_connection)?, TWO_FA_CODE_SECRET.clone())),
+        backend => return Err(unsupported("email change store", backend)),
+    };
+
+    let audit_log: AuditLogType = match config.audit_log {
+        StoreBackend::Memory => Arc::new(VecAuditLog::default()),
+        StoreBackend::Postgres => Arc::new(PostgresAuditLog::new(postgres(&pg_pool)?)),
+        backend => return Err(unsupported("audit log", backend)),
+    };
+
+    let email_client: EmailClientType = match config.email_client {
+        EmailClientBackend::Mock => Arc::new(MockEmailClient),
+        EmailClientBackend::Postmark => Arc::new(configure_postmark_email_client()?),
+    };
+
+    Ok(AppState::new(user_store,
+                     banned_token_store,
+                     two_fa_code_store,
+                     email_client,
+                     api_key_store,
+                     magic_link_store,
+                     password_reset_store,
+                     device_grant_store,
+                     impersonation_store,
+                     email_change_store,
+                     audit_log))
+}
+
+// The memory user store isn't worth caching, the others are wrapped unless the cache is disabled
+fn cached(user_store: impl UserStore + Send + Sync + 'static, config: UserCacheConfig) -> UserStoreType {
+    match NonZeroUsize::new(config.capacity as usize) {
+        Some(capacity) => {
+            Arc::new(CachedUserStore::new(user_store, capacity, Duration::from_secs(config.ttl_seconds.into())))
+        }
+        None => Arc::new(user_store),
+    }
+}
+
+fn unsupported(store: &str, backend: StoreBackend) -> color_eyre::eyre::Report {
+    eyre!("{:?} is not a supported backend for the {}", backend, store)
+}
+
+fn postgres(pg_pool: &Option<PgPool>) -> Result<PgPool> {
+    pg_pool.clone().ok_or(eyre!("Postgres connection pool was not configured"))
+}
+
+fn sqlite(sqlite_pool: &Option<SqlitePool>) -> Result<SqlitePool> {
+    sqlite_pool.clone().ok_or(eyre!("SQLite connection pool was not configured"))
+}
+
+fn redis(redis_connection: &Option<ConnectionManager>) -> Result<ConnectionManager> {
+    redis_connection.clone().ok_or(eyre!("Redis connection was not configured"))
+}
+
+async fn configure_postgresql() -> Result<PgPool> {
+    // Create a new database connection pool
+    let database_url = DATABASE_URL.to_owned();
+    let pg_pool = get_postgres_pool(&database_url)
+        .await
+        .wrap_err("Failed to create Postgres connection pool!")?;
+
+    // Run database migrations
+    sqlx::migrate!()
+        .run(&pg_pool)
+        .await
+        .wrap_err("Failed to run migrations")?;
+    Ok(pg_pool)
+}
+
+async fn configure_sqlite() -> Result<SqlitePool> {
+    let sqlite_pool = get_sqlite_pool(&SQLITE_DATABASE_URL)
+        .await
+        .wrap_err("Failed to create SQLite connection pool!")?;
+
+    // SQLite has its own migrations since the schema differs from the Postgres one
+    sqlx::migrate!("./migrations_sqlite")
+        .run(&sqlite_pool)
+        .await
+        .wrap_err("Failed to run SQLite migrations")?;
+    Ok(sqlite_pool)
+}
+
+// The connection manager multiplexes every store over one async connection
+// and reconnects on its own after Redis restarts, so clones are cheap to hand out.
+async fn configure_redis() -> Result<ConnectionManager> {
+    get_redis_client(REDIS_HOST_NAME.to_owned())
+        .wrap_err("Failed to get Redis client")?
+        .get_connection_manager()
+        .await
+        .wrap_err("Failed to get Redis connection manager")
+}
+
+fn configure_postmark_email_client() -> Result<PostmarkEmailClient> {
+    let http_client = Client::builder()
+        .timeout(prod::email_client::TIMEOUT)
+        .build()
+        .wrap_err("Failed to build HTTP client")?;
+
+    Ok(PostmarkEmailClient::new(
+        prod::email_client::BASE_URL.to_owned(),
+        Email::parse(Secret::new(prod::email_client::SENDER.to_owned()))?,
+        POSTMARK_AUTH_TOKEN.to_owned(),
+        http_client,
+    ))
+}
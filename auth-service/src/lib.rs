@@ -1,4 +1,4 @@
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 use serde::{Deserialize, Serialize};
 use tower_http::{services::ServeDir, cors::CorsLayer, trace::TraceLayer};
 use http::Method;
@@ -8,26 +8,34 @@ use secrecy::{Secret, ExposeSecret};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
-    routing::post,
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    middleware::AddExtension,
+    routing::{delete, get, post},
     serve::Serve,
     Json, 
     Router
 };
 use app_state::AppState;
 use domain::AuthAPIError;
-use routes::{signup, login, verify_token, logout, verify_2fa};
-
-use sqlx::{PgPool, postgres::PgPoolOptions};
+use routes::{signup, login, verify_token, logout, verify_2fa, create_api_key, list_api_keys, revoke_api_key,
+             login_magic_link, confirm_magic_link, magic_link_callback, login_code, reset_password,
+             device_code, device_approve, device_token,
+             impersonate, end_impersonation, import_users, get_me, update_me, export_me,
+             change_email, confirm_email_change, list_audit_events};
+
+use std::str::FromStr;
+use sqlx::{PgPool, postgres::PgPoolOptions, SqlitePool, sqlite::{SqliteConnectOptions, SqlitePoolOptions}};
 use redis::{Client, RedisResult};
 
 pub mod app_state;
 pub mod domain;
+pub mod factory;
 pub mod routes;
 pub mod services;
 pub mod utils;
 
 pub struct Application {
-    pub server: Serve<Router, Router>,
+    pub server: Serve<IntoMakeServiceWithConnectInfo<Router, SocketAddr>, AddExtension<Router, ConnectInfo<SocketAddr>>>,
     pub address: String,
 }
 
@@ -36,8 +44,8 @@ impl Application {
         let allowed_origins = ["http://localhost:8000".parse()?];
   
         let cors = CorsLayer::new()
-                                        // Allow GET and POST requests
-                                        .allow_methods([Method::GET, Method::POST])
+                                        // Allow GET, POST, PATCH and DELETE requests
+                                        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
                                         // Allow cookies to be included in requests
                                         .allow_credentials(true)
                                         .allow_origin(allowed_origins);
@@ -51,16 +59,34 @@ impl Application {
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(signup))
             .route("/login", post(login))
+            .route("/login/code", post(login_code))
+            .route("/login/magic-link", post(login_magic_link))
+            .route("/login/magic-link/callback", get(confirm_magic_link).post(magic_link_callback))
             .route("/verify-2fa", post(verify_2fa))
+            .route("/password-reset", post(reset_password))
             .route("/logout", post(logout))
             .route("/verify-token", post(verify_token))
+            .route("/me", get(get_me).patch(update_me))
+            .route("/me/export", get(export_me))
+            .route("/change-email", post(change_email))
+            .route("/change-email/confirm", post(confirm_email_change))
+            .route("/device/code", post(device_code))
+            .route("/device/approve", post(device_approve))
+            .route("/device/token", post(device_token))
+            .route("/admin/impersonate", post(impersonate))
+            .route("/admin/impersonate/end", post(end_impersonation))
+            .route("/admin/users/import", post(import_users))
+            .route("/admin/audit-events", get(list_audit_events))
+            .route("/api-keys", post(create_api_key).get(list_api_keys))
+            .route("/api-keys/:id", delete(revoke_api_key))
             .with_state(app_state)
             .layer(cors)
             .layer(trac);
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // The peer address is recorded in the audit log
+        let server = axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>());
 
         let app = Application {
             server,
@@ -93,6 +119,25 @@ impl IntoResponse for AuthAPIError {
             AuthAPIError::InvalidCookie => (StatusCode::BAD_REQUEST, "Invalid Cookie"),
             AuthAPIError::Invalid2FACode => (StatusCode::BAD_REQUEST, "Invalid 2FA code"),
             AuthAPIError::InvalidLoginAttamptId => (StatusCode::BAD_REQUEST, "Invalid login attempt id"),
+            AuthAPIError::InvalidApiKeyRequest => (StatusCode::BAD_REQUEST, "Invalid API key request"),
+            AuthAPIError::ApiKeyNotFound => (StatusCode::NOT_FOUND, "API key not found"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
+            AuthAPIError::InvalidPasswordHash => (StatusCode::BAD_REQUEST, "Invalid password hash"),
+            AuthAPIError::InvalidDisplayName => (StatusCode::BAD_REQUEST, "Invalid display name"),
+            AuthAPIError::InvalidConfirmationCode => (StatusCode::BAD_REQUEST, "Invalid confirmation code"),
+            AuthAPIError::AccountLocked => (StatusCode::LOCKED, "Account locked"),
+            AuthAPIError::TooManyFailedLogins => (StatusCode::TOO_MANY_REQUESTS, "Too many failed logins"),
+            AuthAPIError::TooManyLoginCodes => (StatusCode::TOO_MANY_REQUESTS, "Too many login codes"),
+            AuthAPIError::InvalidAuditQuery => (StatusCode::BAD_REQUEST, "Invalid audit query"),
+            AuthAPIError::InvalidUserCode => (StatusCode::BAD_REQUEST, "Invalid user code"),
+            // Device flow errors use the error codes of RFC 8628, section 3.5
+            AuthAPIError::AuthorizationPending => (StatusCode::BAD_REQUEST, "authorization_pending"),
+            AuthAPIError::SlowDown => (StatusCode::BAD_REQUEST, "slow_down"),
+            AuthAPIError::AccessDenied => (StatusCode::BAD_REQUEST, "access_denied"),
+            AuthAPIError::ExpiredToken => (StatusCode::BAD_REQUEST, "expired_token"),
+            AuthAPIError::InvalidGrant => (StatusCode::BAD_REQUEST, "invalid_grant"),
+            AuthAPIError::UnsupportedGrantType => (StatusCode::BAD_REQUEST, "unsupported_grant_type"),
             AuthAPIError::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
         };
         let body = Json(ErrorResponse {
@@ -121,6 +166,12 @@ pub async fn get_postgres_pool(url: &Secret<String>) -> Result<PgPool, sqlx::Err
     PgPoolOptions::new().max_connections(5).connect(url.expose_secret()).await
 }
 
+pub async fn get_sqlite_pool(url: &Secret<String>) -> Result<SqlitePool, sqlx::Error> {
+    // Create the database file on first start so no setup step is needed
+    let options = SqliteConnectOptions::from_str(url.expose_secret())?.create_if_missing(true);
+    SqlitePoolOptions::new().max_connections(5).connect_with(options).await
+}
+
 pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     let redis_url = format!("redis://{}/", redis_hostname);
     redis::Client::open(redis_url)
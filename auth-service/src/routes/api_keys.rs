@@ -0,0 +1,120 @@
+use axum::{extract::{Path, State}, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use secrecy::ExposeSecret;
+
+use crate::{
+    app_state::AppState,
+    domain::{ApiKey, ApiKeyRecord, ApiKeyStoreError, AuthAPIError},
+};
+
+use super::{account_owner, authenticated_user};
+
+const MAX_API_KEY_NAME_LENGTH: usize = 100;
+
+#[tracing::instrument(name = "Create API key", skip_all)]
+pub async fn create_api_key(State(state): State<AppState>,
+                            jar: CookieJar,
+                            Json(request): Json<CreateApiKeyRequest>) ->
+                            Result<impl IntoResponse, AuthAPIError> {
+    // A key would outlive an impersonation, so only the user themselves can create one
+    let user_id = account_owner(&jar, &state).await?.id;
+
+    let name = request.name.trim().to_owned();
+    if name.is_empty() || name.chars().count() > MAX_API_KEY_NAME_LENGTH {
+        return Err(AuthAPIError::InvalidApiKeyRequest);
+    }
+
+    let scopes = request.scopes.unwrap_or_default();
+    if scopes.iter().any(|scope| scope.is_empty() || scope.contains(char::is_whitespace)) {
+        return Err(AuthAPIError::InvalidApiKeyRequest);
+    }
+
+    if request.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(AuthAPIError::InvalidApiKeyRequest);
+    }
+
+    let api_key = ApiKey::default();
+    let record = ApiKeyRecord::new(&api_key, user_id, name, scopes, request.expires_at);
+
+    if let Err(e) = state.api_key_store.add_api_key(record.clone()).await {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    // The plaintext key is only ever returned here, we keep nothing but its hash
+    let response = Json(CreateApiKeyResponse {
+        key: api_key.as_ref().expose_secret().to_owned(),
+        api_key: record.into(),
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+#[tracing::instrument(name = "List API keys", skip_all)]
+pub async fn list_api_keys(State(state): State<AppState>,
+                           jar: CookieJar) -> Result<impl IntoResponse, AuthAPIError> {
+    let user_id = authenticated_user(&jar, &state).await?.id;
+
+    let api_keys = match state.api_key_store.get_api_keys(&user_id).await {
+        Ok(api_keys) => api_keys,
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let response: Vec<ApiKeyResponse> = api_keys.into_iter().map(ApiKeyResponse::from).collect();
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[tracing::instrument(name = "Revoke API key", skip_all)]
+pub async fn revoke_api_key(State(state): State<AppState>,
+                            jar: CookieJar,
+                            Path(id): Path<String>) -> Result<impl IntoResponse, AuthAPIError> {
+    // Like creating keys, revoking them is left to the user, an impersonating admin only lists them
+    let user_id = account_owner(&jar, &state).await?.id;
+
+    match state.api_key_store.delete_api_key(&user_id, &id).await {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(ApiKeyStoreError::ApiKeyNotFound) => Err(AuthAPIError::ApiKeyNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Option<Vec<String>>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKeyRecord> for ApiKeyResponse {
+    fn from(record: ApiKeyRecord) -> Self {
+        Self {
+            id: record.id,
+            name: record.name,
+            prefix: record.prefix,
+            scopes: record.scopes,
+            created_at: record.created_at,
+            expires_at: record.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyResponse {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
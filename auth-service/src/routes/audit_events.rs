@@ -0,0 +1,119 @@
+use axum::{extract::{Query, State}, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventFilter, AuditEventKind, AuditOutcome, AuditRecord, AuthAPIError, Email, UserId},
+};
+
+use super::authenticated_admin;
+
+const DEFAULT_AUDIT_EVENTS_PAGE_SIZE: usize = 50;
+const MAX_AUDIT_EVENTS_PAGE_SIZE: usize = 500;
+
+#[tracing::instrument(name = "List audit events", skip_all)]
+pub async fn list_audit_events(State(state): State<AppState>,
+                               jar: CookieJar,
+                               Query(request): Query<ListAuditEventsRequest>) ->
+                               Result<impl IntoResponse, AuthAPIError> {
+    authenticated_admin(&jar, &state).await?;
+
+    let filter = AuditEventFilter {
+        user_id: request.user_id
+            .map(|user_id| UserId::parse(&user_id))
+            .transpose()
+            .map_err(|_| AuthAPIError::InvalidAuditQuery)?,
+        email: request.email
+            .map(|email| Email::parse(Secret::new(email)))
+            .transpose()
+            .map_err(|_| AuthAPIError::InvalidAuditQuery)?,
+        kind: request.kind
+            .map(|kind| AuditEventKind::parse(&kind))
+            .transpose()
+            .map_err(|_| AuthAPIError::InvalidAuditQuery)?,
+        outcome: request.outcome
+            .map(|outcome| AuditOutcome::parse(&outcome))
+            .transpose()
+            .map_err(|_| AuthAPIError::InvalidAuditQuery)?,
+        since: request.since,
+        until: request.until,
+    };
+
+    let limit = request.limit.unwrap_or(DEFAULT_AUDIT_EVENTS_PAGE_SIZE);
+    if limit == 0 || limit > MAX_AUDIT_EVENTS_PAGE_SIZE {
+        return Err(AuthAPIError::InvalidAuditQuery);
+    }
+
+    let records = state.audit_log
+        .query(&filter, request.before, limit)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // A full page may be followed by more events, an empty next page ends the listing
+    let next_before = match records.last() {
+        Some(record) if records.len() == limit => Some(record.id),
+        _ => None,
+    };
+
+    let response = Json(ListAuditEventsResponse {
+        events: records.into_iter().map(AuditEventResponse::from).collect(),
+        next_before,
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ListAuditEventsRequest {
+    #[serde(rename = "userId")]
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+    pub kind: Option<String>,
+    pub outcome: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub before: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListAuditEventsResponse {
+    pub events: Vec<AuditEventResponse>,
+    #[serde(rename = "nextBefore")]
+    pub next_before: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEventResponse {
+    pub id: i64,
+    #[serde(rename = "occurredAt")]
+    pub occurred_at: DateTime<Utc>,
+    pub kind: String,
+    pub outcome: String,
+    #[serde(rename = "userId")]
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl From<AuditRecord> for AuditEventResponse {
+    fn from(record: AuditRecord) -> Self {
+        let event = record.event;
+        Self {
+            id: record.id,
+            occurred_at: event.occurred_at,
+            kind: event.kind.as_ref().to_owned(),
+            outcome: event.outcome.as_ref().to_owned(),
+            user_id: event.user_id.map(|user_id| user_id.to_string()),
+            email: event.email.map(|email| email.as_ref().expose_secret().to_owned()),
+            ip: event.ip.map(|ip| ip.to_string()),
+            user_agent: event.user_agent,
+            detail: event.detail,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::Deserialize;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuditOutcome, AuthAPIError, Email, EmailChange, EmailChangeStoreError, TwoFACode, UserStoreError},
+    utils::{auth::generate_auth_cookie_after_revocation, constants::MAX_EMAIL_CHANGE_ATTEMPTS},
+};
+
+use super::{account_owner, audit, RequestContext, RouteResponse};
+
+#[tracing::instrument(name = "Change email", skip_all)]
+pub async fn change_email(State(state): State<AppState>,
+                          jar: CookieJar,
+                          Json(request): Json<ChangeEmailRequest>) ->
+                          Result<impl IntoResponse, AuthAPIError> {
+    let user = account_owner(&jar, &state).await?;
+    let new_email =
+        Email::parse(request.new_email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match state.user_store.get_user(&new_email).await {
+        Ok(_) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let email_change = EmailChange::new(user.id, new_email);
+    let code = TwoFACode::default();
+    if let Err(e) = state.email_change_store.add_email_change(email_change.clone(), &code).await {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    // The code proves ownership of the new address, the notice lets the owner of the old one react
+    state.email_client
+        .send_email(&email_change.new_email,
+                    "Confirm your new email",
+                    code.as_ref().expose_secret())
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    state.email_client
+        .send_email(&user.email,
+                    "Your email is being changed",
+                    &format!("A change of your email to {} was requested",
+                             email_change.new_email.as_ref().expose_secret()))
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(RouteResponse {
+        message: "A confirmation code has been sent to the new email".to_owned(),
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Confirm email change", skip_all)]
+pub async fn confirm_email_change(State(state): State<AppState>,
+                                  context: RequestContext,
+                                  jar: CookieJar,
+                                  Json(request): Json<ConfirmEmailChangeRequest>) ->
+                                  (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let user = match account_owner(&jar, &state).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let code = match TwoFACode::parse(request.code) {
+        Ok(code) => code,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidConfirmationCode)),
+    };
+
+    let email_change = match state.email_change_store.get_email_change(&user.id).await {
+        Ok(email_change) => email_change,
+        Err(EmailChangeStoreError::EmailChangeNotFound) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let matches = match state.email_change_store.verify_email_change_code(&user.id, &code).await {
+        Ok(matches) => matches,
+        Err(EmailChangeStoreError::EmailChangeNotFound) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+    if !matches {
+        // Guessing is capped, past it the change has to be requested again with a new code
+        let dropped = match state.email_change_store
+            .record_failed_attempt(&user.id, MAX_EMAIL_CHANGE_ATTEMPTS)
+            .await
+        {
+            Ok(dropped) => dropped,
+            Err(EmailChangeStoreError::EmailChangeNotFound) => true,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        };
+        let event = context.event(AuditEventKind::EmailChanged, AuditOutcome::Failure)
+            .with_user(&user)
+            .with_detail(if dropped { "too_many_attempts" } else { "incorrect_code" });
+        audit(&state, event).await;
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+
+    // Dropping the pending change first lets a code confirm one change only, even when confirmations race.
+    // If the update then fails, the change has to be requested again.
+    match state.email_change_store.delete_email_change(&user.id).await {
+        Ok(()) => {}
+        Err(EmailChangeStoreError::EmailChangeNotFound) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // Everything else held about the user, like API keys and impersonations, is keyed by id and stays with them
+    let updated_user = match state.user_store.update_email(&user.id, email_change.new_email).await {
+        Ok(updated_user) => updated_user,
+        // Someone signed up with the address since the change was requested
+        Err(UserStoreError::UserAlreadyExists) => return (jar, Err(AuthAPIError::UserAlreadyExists)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    audit(&state, context.event(AuditEventKind::EmailChanged, AuditOutcome::Success).with_user(&updated_user)).await;
+
+    // Every session of the old address ends, the one the change was confirmed with gets a fresh token
+    let revoked_at = Utc::now();
+    if let Err(e) = state.banned_token_store.revoke_user_tokens(&user.id, revoked_at).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+    let event = context.event(AuditEventKind::TokenBanned, AuditOutcome::Success)
+        .with_user(&updated_user)
+        .with_detail("email_change");
+    audit(&state, event).await;
+
+    let auth_cookie = match generate_auth_cookie_after_revocation(&updated_user.id, revoked_at) {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let response = Json(RouteResponse {
+        message: "Email changed".to_owned(),
+    });
+    (jar.add(auth_cookie), Ok((StatusCode::OK, response)))
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub code: Secret<String>,
+}
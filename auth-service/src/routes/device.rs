@@ -0,0 +1,153 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Form, Json};
+use axum_extra::extract::CookieJar;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, DeviceCode, DeviceGrant, DeviceGrantStatus, DeviceGrantStoreError, UserCode, UserStoreError},
+    utils::{auth::generate_auth_token,
+            constants::{BASE_URL, DEVICE_CODE_POLL_INTERVAL_SECONDS_I64, DEVICE_CODE_TTL_SECONDS_I64,
+                        TTL_SECONDS_I64}},
+};
+
+use super::account_owner;
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const MAX_USER_CODE_ATTEMPTS: usize = 3;
+
+#[tracing::instrument(name = "Device code", skip_all)]
+pub async fn device_code(State(state): State<AppState>) -> Result<impl IntoResponse, AuthAPIError> {
+    // User codes are short enough to collide now and then, a taken one is drawn again
+    let mut attempts = 0;
+    let device_grant = loop {
+        let device_grant = DeviceGrant::new(Duration::seconds(DEVICE_CODE_TTL_SECONDS_I64));
+        attempts += 1;
+        match state.device_grant_store.add_device_grant(device_grant.clone()).await {
+            Ok(()) => break device_grant,
+            Err(DeviceGrantStoreError::UserCodeAlreadyExists) if attempts < MAX_USER_CODE_ATTEMPTS => continue,
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        }
+    };
+
+    let verification_uri = format!("{}/device.html", BASE_URL.as_str());
+    let response = Json(DeviceCodeResponse {
+        device_code: device_grant.device_code.as_ref().expose_secret().to_owned(),
+        user_code: device_grant.user_code.display(),
+        verification_uri_complete: format!("{}?user_code={}", verification_uri, device_grant.user_code.display()),
+        verification_uri,
+        expires_in: DEVICE_CODE_TTL_SECONDS_I64,
+        interval: DEVICE_CODE_POLL_INTERVAL_SECONDS_I64,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Device approve", skip_all)]
+pub async fn device_approve(State(state): State<AppState>,
+                            jar: CookieJar,
+                            Json(request): Json<DeviceApproveRequest>) ->
+                            Result<impl IntoResponse, AuthAPIError> {
+    // The device gets a token of its own, which an impersonating admin mustn't be able to mint
+    let user_id = account_owner(&jar, &state).await?.id;
+
+    let user_code = UserCode::parse(&request.user_code).map_err(|_| AuthAPIError::InvalidUserCode)?;
+
+    let status = if request.approved {
+        DeviceGrantStatus::Approved(user_id)
+    } else {
+        DeviceGrantStatus::Denied
+    };
+
+    // Only a pending grant can be decided, a second approval or denial is refused
+    match state.device_grant_store.decide_device_grant(&user_code, status).await {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(DeviceGrantStoreError::DeviceGrantNotFound) => Err(AuthAPIError::InvalidUserCode),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[tracing::instrument(name = "Device token", skip_all)]
+pub async fn device_token(State(state): State<AppState>,
+                          Form(request): Form<DeviceTokenRequest>) ->
+                          Result<impl IntoResponse, AuthAPIError> {
+    if request.grant_type != DEVICE_CODE_GRANT_TYPE {
+        return Err(AuthAPIError::UnsupportedGrantType);
+    }
+
+    let device_code = DeviceCode::parse(request.device_code).map_err(|_| AuthAPIError::InvalidGrant)?;
+
+    // A decided grant is removed by the poll that reads it, so the device code is single use
+    let now = Utc::now();
+    let device_grant = match state.device_grant_store.poll_device_grant(&device_code, now).await {
+        Ok(device_grant) => device_grant,
+        Err(DeviceGrantStoreError::DeviceGrantExpired) => return Err(AuthAPIError::ExpiredToken),
+        Err(DeviceGrantStoreError::DeviceGrantNotFound) => return Err(AuthAPIError::InvalidGrant),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let user_id = match device_grant.status {
+        DeviceGrantStatus::Approved(user_id) => user_id,
+        DeviceGrantStatus::Denied => return Err(AuthAPIError::AccessDenied),
+        DeviceGrantStatus::Pending => {
+            let too_fast = device_grant.last_polled_at.is_some_and(|last_polled_at| {
+                now - last_polled_at < Duration::seconds(DEVICE_CODE_POLL_INTERVAL_SECONDS_I64)
+            });
+
+            return Err(if too_fast {
+                AuthAPIError::SlowDown
+            } else {
+                AuthAPIError::AuthorizationPending
+            });
+        }
+    };
+
+    let user = match state.user_store.get_user_by_id(&user_id).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::AccessDenied),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let access_token = generate_auth_token(&user.id).map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(DeviceTokenResponse {
+        access_token,
+        token_type: "Bearer".to_owned(),
+        expires_in: TTL_SECONDS_I64,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Field names follow RFC 8628 rather than the camel case used by the other routes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    pub expires_in: i64,
+    pub interval: i64,
+}
+
+#[derive(Deserialize)]
+pub struct DeviceApproveRequest {
+    #[serde(rename = "userCode")]
+    pub user_code: String,
+    pub approved: bool,
+}
+
+// Sent form encoded, as RFC 8628 has it
+#[derive(Deserialize)]
+pub struct DeviceTokenRequest {
+    pub grant_type: String,
+    pub device_code: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
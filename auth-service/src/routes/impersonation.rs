@@ -0,0 +1,134 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuditOutcome, AuthAPIError, Email, Impersonation, UserId, UserStoreError},
+    utils::{auth::{generate_impersonation_token, validate_token},
+            constants::IMPERSONATION_TTL_SECONDS_I64},
+};
+
+use super::{audit, authenticated_admin, authenticated_claims, RequestContext};
+
+#[tracing::instrument(name = "Impersonate", skip_all)]
+pub async fn impersonate(State(state): State<AppState>,
+                         context: RequestContext,
+                         jar: CookieJar,
+                         Json(request): Json<ImpersonateRequest>) ->
+                         Result<impl IntoResponse, AuthAPIError> {
+    // An impersonation token never grants the admin role of the impersonated user
+    let admin = authenticated_admin(&jar, &state).await?;
+    let email =
+        Email::parse(request.email.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::UserNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let impersonation = Impersonation::new(admin.id,
+                                           user.id,
+                                           request.reason,
+                                           Duration::seconds(IMPERSONATION_TTL_SECONDS_I64));
+
+    let token = generate_impersonation_token(&impersonation, &user.id, &admin.id)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let expires_at = impersonation.expires_at;
+
+    // No token is handed out unless the impersonation made it into the audit trail
+    if let Err(e) = state.impersonation_store
+        .add_impersonation(impersonation)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    let event = context.event(AuditEventKind::ImpersonationStarted, AuditOutcome::Success)
+        .with_user(&user)
+        .with_detail(&admin.id.to_string());
+    audit(&state, event).await;
+    tracing::info!("impersonation started");
+
+    let response = Json(ImpersonateResponse { token, expires_at });
+    Ok((StatusCode::CREATED, response))
+}
+
+#[tracing::instrument(name = "End impersonation", skip_all)]
+pub async fn end_impersonation(State(state): State<AppState>,
+                               context: RequestContext,
+                               jar: CookieJar,
+                               Json(request): Json<EndImpersonationRequest>) ->
+                               Result<impl IntoResponse, AuthAPIError> {
+    let admin_claims = authenticated_claims(&jar, &state).await?;
+
+    let claims = match validate_token(request.token.expose_secret(), state.banned_token_store.clone()).await {
+        Ok(claims) => claims,
+        Err(_) => return Err(AuthAPIError::InvalidToken),
+    };
+
+    let (actor, impersonation_id) = match (claims.act, claims.jti) {
+        (Some(actor), Some(impersonation_id)) => (actor, impersonation_id),
+        _ => return Err(AuthAPIError::InvalidToken),
+    };
+
+    // Only the admin who started the impersonation can end it this way
+    if actor.sub != admin_claims.sub {
+        return Err(AuthAPIError::Forbidden);
+    }
+
+    if let Err(e) = state
+        .banned_token_store
+        .add_banned_token(request.token)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    let user_id = UserId::parse(&claims.sub).ok();
+    if let Some(user_id) = user_id {
+        let event = context.event(AuditEventKind::TokenBanned, AuditOutcome::Success)
+            .with_user_id(user_id)
+            .with_detail("impersonation_end");
+        audit(&state, event).await;
+    }
+
+    if let Err(e) = state.impersonation_store
+        .end_impersonation(&impersonation_id, Utc::now())
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    if let Some(user_id) = user_id {
+        let event = context.event(AuditEventKind::ImpersonationEnded, AuditOutcome::Success)
+            .with_user_id(user_id)
+            .with_detail(&actor.sub);
+        audit(&state, event).await;
+    }
+
+    tracing::info!("impersonation ended");
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct ImpersonateRequest {
+    pub email: Secret<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImpersonateResponse {
+    pub token: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct EndImpersonationRequest {
+    pub token: Secret<String>,
+}
@@ -0,0 +1,67 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, HashedPassword, User, UserStoreError},
+};
+
+use super::authenticated_admin;
+
+// Imports users migrated from another system together with their existing password hashes.
+// They are upgraded to the current argon2 parameters on their first login.
+#[tracing::instrument(name = "Import users", skip_all)]
+pub async fn import_users(State(state): State<AppState>,
+                          jar: CookieJar,
+                          Json(request): Json<ImportUsersRequest>) ->
+                          Result<impl IntoResponse, AuthAPIError> {
+    authenticated_admin(&jar, &state).await?;
+
+    // Everything is validated up front so that a bad entry doesn't leave a partial import
+    let mut users = Vec::with_capacity(request.users.len());
+    for imported in request.users {
+        let email = Email::parse(imported.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+        let password_hash =
+            HashedPassword::parse(imported.password_hash).map_err(|_| AuthAPIError::InvalidPasswordHash)?;
+        users.push((User::new(email, imported.requires_2fa), password_hash));
+    }
+
+    let mut imported = 0;
+    let mut already_exists = Vec::new();
+    for (user, password_hash) in users {
+        let email = user.email.as_ref().expose_secret().to_owned();
+        match state.user_store.import_user(user, password_hash).await {
+            Ok(()) => imported += 1,
+            Err(UserStoreError::UserAlreadyExists) => already_exists.push(email),
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        }
+    }
+
+    tracing::info!(imported, skipped = already_exists.len(), "users imported");
+
+    let response = Json(ImportUsersResponse { imported, already_exists });
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ImportUsersRequest {
+    pub users: Vec<ImportedUser>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportedUser {
+    pub email: Secret<String>,
+    #[serde(rename = "passwordHash")]
+    pub password_hash: Secret<String>,
+    #[serde(rename = "requires2FA", default)]
+    pub requires_2fa: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportUsersResponse {
+    pub imported: usize,
+    #[serde(rename = "alreadyExists")]
+    pub already_exists: Vec<String>,
+}
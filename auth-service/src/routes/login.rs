@@ -5,12 +5,16 @@ use secrecy::{ExposeSecret, Secret};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, LoginAttemptId, TwoFACode},
+    domain::{AuditEventKind, AuditOutcome, AuthAPIError, Email, Password, LoginAttemptId, TwoFACode, User, UserId,
+             UserStoreError},
     utils::auth::generate_auth_cookie
 };
 
+use super::{audit, count_failed_login, is_failed_login_limited, record_login, RequestContext};
+
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(State(state): State<AppState>,
+                   context: RequestContext,
                    jar: CookieJar,
                    Json(request): Json<LoginRequest>) -> 
                    (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -27,34 +31,70 @@ pub async fn login(State(state): State<AppState>,
         return (jar, Err(AuthAPIError::InvalidCredentials));
     };
 
-    let user_store = state.user_store.read().await;
-
-    if let Err(_) = user_store.validate_user(&email, &password).await {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    // Checked before anything else, so a limited client learns nothing more about the account
+    if is_failed_login_limited(&state, &context) {
+        let event = context.event(AuditEventKind::Login, AuditOutcome::Failure)
+            .with_email(&email)
+            .with_detail("rate_limited");
+        audit(&state, event).await;
+        return (jar, Err(AuthAPIError::TooManyFailedLogins));
     }
 
+    let user_store = &state.user_store;
+
+    // The profile may come from the cache, `validate_user` reads the user again past it
     let user = match user_store.get_user(&email).await {
         Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => {
+            count_failed_login(&state, &context, None).await;
+            let event = context.event(AuditEventKind::Login, AuditOutcome::Failure)
+                .with_email(&email)
+                .with_detail("unknown_user");
+            audit(&state, event).await;
+            return (jar, Err(AuthAPIError::IncorrectCredentials));
+        }
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
+    // Checks the password together with the current lock and 2FA setting, so that an unlock or a change
+    // of the 2FA setting made by auth-admin applies right away.
+    let user = match user_store.validate_user(&email, &password).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserLocked) => {
+            let event = context.event(AuditEventKind::Login, AuditOutcome::Failure)
+                .with_user(&user)
+                .with_detail("account_locked");
+            audit(&state, event).await;
+            return (jar, Err(AuthAPIError::AccountLocked));
+        }
+        Err(_) => {
+            count_failed_login(&state, &context, Some(&user)).await;
+            let event = context.event(AuditEventKind::Login, AuditOutcome::Failure)
+                .with_user(&user)
+                .with_detail("incorrect_password");
+            audit(&state, event).await;
+            return (jar, Err(AuthAPIError::IncorrectCredentials));
+        }
+    };
+
     // Handle request based on user's 2FA configuration
     match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+        true => handle_2fa(&user, &state, &context, jar).await,
+        false => {
+            record_login(&state, &context, &user, "password").await;
+            handle_no_2fa(&user.id, jar).await
+        }
     }
 }
 
 #[tracing::instrument(name = "handle_2fa", skip_all)]
-async fn handle_2fa(email: &Email, state: &AppState, jar: CookieJar) -> 
+pub(super) async fn handle_2fa(user: &User, state: &AppState, context: &RequestContext, jar: CookieJar) ->
     (CookieJar, Result<(StatusCode, Json<LoginResponse>), AuthAPIError>) {
-    
+    let email = &user.email;
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::default();
 
     if let Err(e) = state.two_fa_code_store
-        .write()
-        .await
         .add_two_fa_code(email, login_attempt_id.to_owned(), two_fa_code.to_owned())
         .await
     {
@@ -66,7 +106,9 @@ async fn handle_2fa(email: &Email, state: &AppState, jar: CookieJar) ->
         .await
     {
         return (jar, Err(AuthAPIError::UnexpectedError(e)));
-    }  
+    }
+
+    audit(state, context.event(AuditEventKind::TwoFASent, AuditOutcome::Success).with_user(user)).await;
 
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
             message: "2FA required".to_owned(),
@@ -76,9 +118,9 @@ async fn handle_2fa(email: &Email, state: &AppState, jar: CookieJar) ->
 }
 
 #[tracing::instrument(name = "handle_no_2fa", skip_all)]
-async fn handle_no_2fa(email: &Email, jar: CookieJar) -> 
+async fn handle_no_2fa(user_id: &UserId, jar: CookieJar) -> 
     (CookieJar, Result<(StatusCode, Json<LoginResponse>), AuthAPIError>) {
-    let result = generate_auth_cookie(email);
+    let result = generate_auth_cookie(user_id);
     let auth_cookie = match result {
         Ok(cookie) => cookie,
         Err(_) =>  return (jar, Err(AuthAPIError::InvalidCookie))
@@ -0,0 +1,66 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::Deserialize;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuditOutcome, AuthAPIError, Email, LoginAttemptId, UserStoreError},
+};
+
+use super::{audit, handle_2fa, LoginResponse, RequestContext, TwoFactorAuthResponse};
+
+// Code-only login: the code is sent by email and the login is completed through `/verify-2fa`
+#[tracing::instrument(name = "Login with code", skip_all)]
+pub async fn login_code(State(state): State<AppState>,
+                        context: RequestContext,
+                        jar: CookieJar,
+                        Json(request): Json<LoginCodeRequest>) ->
+                        (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = if let Ok(oemail) = Email::parse(request.email.clone()) {
+        oemail
+    } else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+
+    // Codes are limited per email and per client IP, so that the route can't flood a mailbox. Unknown
+    // emails count the same, the limit mustn't tell them apart from accounts.
+    let now = Utc::now();
+    let ip_limited = context.ip.is_some_and(|ip| state.login_codes_per_ip.is_limited(&ip, now));
+    if ip_limited || state.login_codes_per_email.is_limited(&email, now) {
+        let event = context.event(AuditEventKind::TwoFASent, AuditOutcome::Failure)
+            .with_email(&email)
+            .with_detail("rate_limited");
+        audit(&state, event).await;
+        return (jar, Err(AuthAPIError::TooManyLoginCodes));
+    }
+    state.login_codes_per_email.record(email.clone(), now);
+    if let Some(ip) = context.ip {
+        state.login_codes_per_ip.record(ip, now);
+    }
+
+    let result = state.user_store.get_user(&email).await;
+    match result {
+        Ok(user) => handle_2fa(&user, &state, &context, jar).await,
+        // Unknown emails get a login attempt id that can never be verified,
+        // so that the route can't be used to probe for accounts
+        Err(UserStoreError::UserNotFound) => {
+            let event = context.event(AuditEventKind::TwoFASent, AuditOutcome::Failure)
+                .with_email(&email)
+                .with_detail("unknown_user");
+            audit(&state, event).await;
+            let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
+                message: "2FA required".to_owned(),
+                login_attempt_id: LoginAttemptId::default().as_ref().expose_secret().to_owned(),
+            }));
+            (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
+        }
+        Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginCodeRequest {
+    pub email: Secret<String>,
+}
@@ -1,15 +1,19 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
 use axum_extra::extract::{cookie, CookieJar};
+use chrono::Utc;
 use secrecy::Secret;
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
+    domain::{AuditEventKind, AuditOutcome, AuthAPIError, UserId},
     utils::{auth::validate_token, constants::JWT_COOKIE_NAME}
 };
 
+use super::{audit, RequestContext};
+
 #[tracing::instrument(name = "logout", skip_all)]
 pub async fn logout(State(state): State<AppState>,
+    context: RequestContext,
     jar: CookieJar) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let cookie = match jar.get(JWT_COOKIE_NAME) {
         Some(cookie) => cookie,
@@ -18,7 +22,7 @@ pub async fn logout(State(state): State<AppState>,
 
     // Validate token
     let token = cookie.value().to_owned();
-    let _ = match validate_token(&token, state.banned_token_store.clone()).await {
+    let claims = match validate_token(&token, state.banned_token_store.clone()).await {
         Ok(claims) => claims,
         Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
     };
@@ -26,14 +30,32 @@ pub async fn logout(State(state): State<AppState>,
     // Add token to banned list
     if let Err(e) = state
         .banned_token_store
-        .write()
-        .await
         .add_banned_token(Secret::new(token.to_owned()))
         .await
     {
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
+    // Impersonation tokens are audited under the impersonated user
+    if let Ok(user_id) = UserId::parse(&claims.sub) {
+        audit(&state, context.event(AuditEventKind::Logout, AuditOutcome::Success).with_user_id(user_id)).await;
+        let event = context.event(AuditEventKind::TokenBanned, AuditOutcome::Success)
+            .with_user_id(user_id)
+            .with_detail("logout");
+        audit(&state, event).await;
+    }
+
+    // Logging out of an impersonation ends it
+    if let (Some(_), Some(impersonation_id)) = (claims.act, claims.jti) {
+        if let Err(e) = state
+            .impersonation_store
+            .end_impersonation(&impersonation_id, Utc::now())
+            .await
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        }
+    }
+
     // Remove jwt cookie
     let jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
 
@@ -0,0 +1,161 @@
+use axum::{extract::{Query, State},
+           http::{header, StatusCode},
+           response::{Html, IntoResponse, Redirect},
+           Form, Json};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, MagicLinkId, UserStoreError},
+    utils::{auth::{generate_auth_cookie, generate_magic_link_token, validate_magic_link_token},
+            constants::BASE_URL},
+};
+
+use crate::routes::RouteResponse;
+
+use super::{record_login, RequestContext};
+
+#[tracing::instrument(name = "Login with magic link", skip_all)]
+pub async fn login_magic_link(State(state): State<AppState>,
+                              Json(request): Json<MagicLinkRequest>) ->
+                              Result<impl IntoResponse, AuthAPIError> {
+    let email =
+        Email::parse(request.email.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Unknown emails get the same response, so that the route can't be used to probe for accounts
+    let response = Json(RouteResponse {
+        message: "If the account exists, a login link has been sent".to_owned(),
+    });
+
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok((StatusCode::OK, response)),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let magic_link_id = MagicLinkId::default();
+    let token = generate_magic_link_token(&user.id, &magic_link_id)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    if let Err(e) = state.magic_link_store
+        .add_magic_link(&user.id, magic_link_id)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    let link = format!("{}/login/magic-link/callback?token={}", BASE_URL.as_str(), token);
+    if let Err(e) = state.email_client
+        .send_email(&email, "Your login link", &link)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e));
+    }
+
+    Ok((StatusCode::OK, response))
+}
+
+// Following the link only shows a page asking to confirm the login, the token is used up by the form
+// it posts. Mail scanners and link previews that fetch the link don't log anyone in or burn the link.
+#[tracing::instrument(name = "Magic link confirmation", skip_all)]
+pub async fn confirm_magic_link(Query(request): Query<MagicLinkCallbackRequest>) ->
+                                Result<impl IntoResponse, AuthAPIError> {
+    // Only a token signed by the service makes it into the page, it holds nothing that needs escaping
+    validate_magic_link_token(request.token.expose_secret()).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let page = MAGIC_LINK_CONFIRMATION_PAGE.replace("{token}", request.token.expose_secret());
+    // The page carries the token, it's neither cached nor leaked through the referrer
+    let headers = [(header::CACHE_CONTROL, "no-store"), (header::REFERRER_POLICY, "no-referrer")];
+    Ok((headers, Html(page)))
+}
+
+#[tracing::instrument(name = "Magic link callback", skip_all)]
+pub async fn magic_link_callback(State(state): State<AppState>,
+                                 context: RequestContext,
+                                 jar: CookieJar,
+                                 Form(request): Form<MagicLinkCallbackRequest>) ->
+                                 (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let claims = match validate_magic_link_token(request.token.expose_secret()) {
+        Ok(claims) => claims,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let magic_link_id = match MagicLinkId::parse(Secret::new(claims.jti)) {
+        Ok(magic_link_id) => magic_link_id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let user_id = match state.magic_link_store
+        .take_magic_link(&magic_link_id)
+        .await
+    {
+        Ok(user_id) => user_id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let user = match state.user_store.get_user_by_id(&user_id).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    if user.id.to_string() != claims.sub {
+        return (jar, Err(AuthAPIError::InvalidToken));
+    }
+
+    record_login(&state, &context, &user, "magic_link").await;
+
+    // Following the link proves ownership of the mailbox, which is what email 2FA checks as well
+    let auth_cookie = match generate_auth_cookie(&user.id) {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let updated_jar = jar.add(auth_cookie);
+    (updated_jar, Ok(Redirect::to("/")))
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkCallbackRequest {
+    pub token: Secret<String>,
+}
+
+const MAGIC_LINK_CONFIRMATION_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Auth - Log in</title>
+    <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/bootstrap@5.2.2/dist/css/bootstrap.min.css">
+</head>
+
+<body>
+    <section class="position-relative py-4 py-xl-5">
+        <div class="container">
+            <div class="row mb-3">
+                <div class="col-md-8 col-xl-6 text-center mx-auto">
+                    <h2>Log in</h2>
+                    <p class="text-muted">Continue to log in with the link sent to your email. The link works once.</p>
+                </div>
+            </div>
+            <div class="row d-flex justify-content-center">
+                <div class="col-md-6 col-xl-4">
+                    <form class="text-center" method="post" action="/login/magic-link/callback">
+                        <input type="hidden" name="token" value="{token}">
+                        <button class="btn btn-dark d-block w-100" type="submit">Log in</button>
+                    </form>
+                </div>
+            </div>
+        </div>
+    </section>
+</body>
+
+</html>
+"#;
@@ -0,0 +1,108 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use secrecy::ExposeSecret;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, DisplayName, User, UserStoreError},
+    services::personal_data_exporters::personal_data_exporters,
+};
+
+use super::{account_owner, authenticated_user};
+
+#[tracing::instrument(name = "Get current user", skip_all)]
+pub async fn get_me(State(state): State<AppState>, jar: CookieJar) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticated_user(&jar, &state).await?;
+
+    Ok((StatusCode::OK, Json(MeResponse::from(user))))
+}
+
+#[tracing::instrument(name = "Update current user", skip_all)]
+pub async fn update_me(State(state): State<AppState>,
+                       jar: CookieJar,
+                       Json(request): Json<UpdateMeRequest>) ->
+                       Result<impl IntoResponse, AuthAPIError> {
+    // The profile is the user's own, an admin impersonating them only reads it
+    let email = account_owner(&jar, &state).await?.email;
+
+    let user = match request.display_name {
+        Some(display_name) => {
+            let display_name = display_name
+                .map(|name| DisplayName::parse(&name))
+                .transpose()
+                .map_err(|_| AuthAPIError::InvalidDisplayName)?;
+            state.user_store.update_display_name(&email, display_name).await
+        }
+        None => state.user_store.get_user(&email).await,
+    }
+    .map_err(user_store_error)?;
+
+    Ok((StatusCode::OK, Json(MeResponse::from(user))))
+}
+
+// Everything held about the caller, one section per subsystem. An admin impersonating the
+// user can't take it away.
+#[tracing::instrument(name = "Export current user's personal data", skip_all)]
+pub async fn export_me(State(state): State<AppState>, jar: CookieJar) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = account_owner(&jar, &state).await?;
+
+    let mut archive = Map::new();
+    archive.insert("exportedAt".to_owned(), Value::String(Utc::now().to_rfc3339()));
+    for exporter in personal_data_exporters(&state) {
+        let section = exporter.export(&user).await.map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        archive.insert(exporter.section().to_owned(), section);
+    }
+
+    Ok((StatusCode::OK, Json(Value::Object(archive))))
+}
+
+fn user_store_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+// Missing fields are left unchanged, `null` clears them
+#[derive(Deserialize)]
+pub struct UpdateMeRequest {
+    #[serde(rename = "displayName", default, deserialize_with = "present")]
+    pub display_name: Option<Option<String>>,
+}
+
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeResponse {
+    pub email: String,
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    pub role: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(rename = "lastLoginAt")]
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+impl From<User> for MeResponse {
+    fn from(user: User) -> Self {
+        Self {
+            email: user.email.as_ref().expose_secret().to_owned(),
+            display_name: user.display_name.map(|name| name.as_ref().to_owned()),
+            requires_2fa: user.requires_2fa,
+            role: user.role.as_ref().to_owned(),
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            last_login_at: user.last_login_at,
+        }
+    }
+}
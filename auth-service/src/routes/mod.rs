@@ -1,13 +1,45 @@
+use std::{convert::Infallible, net::{IpAddr, SocketAddr}};
+
+use axum::{async_trait, extract::{ConnectInfo, FromRequestParts}, http::{header::USER_AGENT, request::Parts}};
+use axum_extra::extract::CookieJar;
+use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditEventKind, AuditOutcome, AuthAPIError, Role, User, UserId, UserStoreError},
+    utils::{auth::{validate_token, Claims},
+            constants::{JWT_COOKIE_NAME, LOCKOUT_SECONDS_I64, MAX_FAILED_LOGIN_ATTEMPTS}}
+};
+
+mod api_keys;
+mod audit_events;
+mod change_email;
+mod device;
+mod impersonation;
+mod import_users;
 mod login;
+mod login_code;
 mod logout;
+mod magic_link;
+mod me;
+mod password_reset;
 mod signup;
 mod verify_2fa;
 mod verify_token;
 
+pub use api_keys::*;
+pub use audit_events::*;
+pub use change_email::*;
+pub use device::*;
+pub use impersonation::*;
+pub use import_users::*;
 pub use login::*;
+pub use login_code::*;
 pub use logout::*;
+pub use magic_link::*;
+pub use me::*;
+pub use password_reset::*;
 pub use signup::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
@@ -17,4 +49,131 @@ pub struct RouteResponse {
     pub message: String,
 }
 
+// Validates the JWT cookie of an authenticated request
+async fn authenticated_claims(jar: &CookieJar, state: &AppState) -> Result<Claims, AuthAPIError> {
+    let cookie = match jar.get(JWT_COOKIE_NAME) {
+        Some(cookie) => cookie,
+        None => return Err(AuthAPIError::MissingToken),
+    };
+
+    match validate_token(cookie.value(), state.banned_token_store.clone()).await {
+        Ok(claims) => Ok(claims),
+        Err(_) => Err(AuthAPIError::InvalidToken),
+    }
+}
+
+// Resolves the user behind the JWT cookie of an authenticated request
+async fn authenticated_user(jar: &CookieJar, state: &AppState) -> Result<User, AuthAPIError> {
+    let claims = authenticated_claims(jar, state).await?;
+
+    user_from_claims(&claims, state).await
+}
+
+// Like `authenticated_user`, but refuses impersonation tokens. Routes that mint credentials or act
+// on the account itself use it, an admin impersonating the user mustn't outlive the impersonation.
+async fn account_owner(jar: &CookieJar, state: &AppState) -> Result<User, AuthAPIError> {
+    let claims = authenticated_claims(jar, state).await?;
+
+    if claims.act.is_some() {
+        return Err(AuthAPIError::Forbidden);
+    }
+
+    user_from_claims(&claims, state).await
+}
+
+// Resolves the admin behind the JWT cookie, impersonation tokens never act as an admin
+async fn authenticated_admin(jar: &CookieJar, state: &AppState) -> Result<User, AuthAPIError> {
+    let claims = authenticated_claims(jar, state).await?;
+
+    if claims.act.is_some() {
+        return Err(AuthAPIError::Forbidden);
+    }
+
+    match user_from_claims(&claims, state).await {
+        Ok(user) if user.role == Role::Admin => Ok(user),
+        Ok(_) | Err(AuthAPIError::InvalidToken) => Err(AuthAPIError::Forbidden),
+        Err(e) => Err(e),
+    }
+}
+
+// A token outliving its user, or minted before `sub` held the user id, is no longer valid
+async fn user_from_claims(claims: &Claims, state: &AppState) -> Result<User, AuthAPIError> {
+    let user_id = UserId::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    match state.user_store.get_user_by_id(&user_id).await {
+        Ok(user) => Ok(user),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Stamps the user's last login and audits it, a failure is logged rather than failing the login
+async fn record_login(state: &AppState, context: &RequestContext, user: &User, method: &str) {
+    if let Err(e) = state.user_store.record_login(&user.email, Utc::now()).await {
+        tracing::warn!("failed to record login: {:?}", e);
+    }
+
+    let event = context.event(AuditEventKind::Login, AuditOutcome::Success).with_user(user).with_detail(method);
+    audit(state, event).await;
+}
+
+// Whether the client IP has used up its failed logins, password and one-time code logins alike
+fn is_failed_login_limited(state: &AppState, context: &RequestContext) -> bool {
+    context.ip.is_some_and(|ip| state.failed_login_limiter.is_limited(&ip, Utc::now()))
+}
+
+// A wrong password or one-time code counts against the client IP, whether or not the account exists,
+// and towards locking the account of a known user. Like `record_login`, a failure to record only
+// loses track of one attempt.
+async fn count_failed_login(state: &AppState, context: &RequestContext, user: Option<&User>) {
+    if let Some(ip) = context.ip {
+        state.failed_login_limiter.record(ip, Utc::now());
+    }
+
+    let Some(user) = user else { return };
+    let locked_until = Utc::now() + Duration::seconds(LOCKOUT_SECONDS_I64);
+    if let Err(e) = state.user_store
+        .record_failed_login(&user.email, MAX_FAILED_LOGIN_ATTEMPTS, locked_until)
+        .await
+    {
+        tracing::warn!("failed to record failed login: {:?}", e);
+    }
+}
 
+// Like `record_login`, a failure to audit is logged rather than failing the request
+async fn audit(state: &AppState, event: AuditEvent) {
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::warn!("failed to record audit event: {:?}", e);
+    }
+}
+
+// Where a request came from, for the audit log. The IP is the peer address of the connection.
+pub struct RequestContext {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl RequestContext {
+    fn event(&self, kind: AuditEventKind, outcome: AuditOutcome) -> AuditEvent {
+        AuditEvent {
+            ip: self.ip,
+            user_agent: self.user_agent.clone(),
+            ..AuditEvent::new(kind, outcome)
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            ip: parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(address)| address.ip()),
+            user_agent: parts.headers
+                .get(USER_AGENT)
+                .and_then(|user_agent| user_agent.to_str().ok())
+                .map(str::to_owned),
+        })
+    }
+}
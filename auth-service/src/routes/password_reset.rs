@@ -0,0 +1,54 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use secrecy::Secret;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuditOutcome, AuthAPIError, Password, PasswordResetStoreError, PasswordResetToken,
+             UserStoreError},
+};
+
+use super::{audit, RequestContext, RouteResponse};
+
+// Sets a new password with a token from `auth-admin force-password-reset`
+#[tracing::instrument(name = "Reset password", skip_all)]
+pub async fn reset_password(State(state): State<AppState>,
+                            context: RequestContext,
+                            Json(request): Json<ResetPasswordRequest>) ->
+                            Result<impl IntoResponse, AuthAPIError> {
+    let token = PasswordResetToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    // Checked before the token is taken, so that a rejected password doesn't use it up
+    let password = Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user_id = match state.password_reset_store.take_password_reset(&token).await {
+        Ok(user_id) => user_id,
+        Err(PasswordResetStoreError::PasswordResetNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let user = match state.user_store.set_password(&user_id, password).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    // Tokens issued since the reset was forced were issued without the new password
+    state.banned_token_store
+        .revoke_user_tokens(&user.id, Utc::now())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    audit(&state, context.event(AuditEventKind::PasswordReset, AuditOutcome::Success).with_user(&user)).await;
+
+    let response = Json(RouteResponse {
+        message: "Password has been reset".to_owned(),
+    });
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: Secret<String>,
+    pub password: Secret<String>,
+}
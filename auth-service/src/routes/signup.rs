@@ -4,31 +4,42 @@ use secrecy::Secret;
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, User},
+    domain::{AuditEventKind, AuditOutcome, AuthAPIError, Email, Password, User, UserStoreError},
 };
 
 use crate::routes::RouteResponse;
 
+use super::{audit, RequestContext};
+
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup(State(state): State<AppState>,
+                    context: RequestContext,
                     Json(request): Json<SignupRequest>) -> 
                     Result<impl IntoResponse, AuthAPIError> {
     let email =
         Email::parse(request.email.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
-    let password =
-        Password::parse(request.password.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    // Without a password the account can only sign in with one-time codes
+    let password = request.password
+        .map(Password::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
  
-    let user = User::new(email, password, request.requires_2fa);
-
-    let mut user_store = state.user_store.write().await;
-
-    if user_store.get_user(&user.email).await.is_ok() {
-        return Err(AuthAPIError::UserAlreadyExists);
+    let user = User::new(email, request.requires_2fa);
+
+    // The insert is the duplicate check, a separate lookup first would race with concurrent signups
+    match state.user_store.add_user(user.clone(), password).await {
+        Ok(()) => {}
+        Err(UserStoreError::UserAlreadyExists) => {
+            let event = context.event(AuditEventKind::Signup, AuditOutcome::Failure)
+                .with_email(&user.email)
+                .with_detail("user_already_exists");
+            audit(&state, event).await;
+            return Err(AuthAPIError::UserAlreadyExists);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
     }
 
-    if let Err(e) = user_store.add_user(user).await {
-        return Err(AuthAPIError::UnexpectedError(e.into()));
-    }
+    audit(&state, context.event(AuditEventKind::Signup, AuditOutcome::Success).with_user(&user)).await;
 
     let response = Json(RouteResponse {
         message: "User created successfully!".to_string(),
@@ -40,7 +51,7 @@ pub async fn signup(State(state): State<AppState>,
 #[derive(Deserialize)]
 pub struct SignupRequest {
     pub email: Secret<String>,
-    pub password: Secret<String>,
+    pub password: Option<Secret<String>>,
     #[serde(rename = "requires2FA")]
     pub requires_2fa: bool,
 }
@@ -1,14 +1,19 @@
 use axum::{extract::State, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use serde::Deserialize;
 use secrecy::Secret;
 
 use crate::{app_state::AppState,
-            domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
+            domain::{AuditEventKind, AuditOutcome, AuthAPIError, Email, LoginAttemptId, TwoFACode,
+                     TwoFACodeStoreError, UserStoreError},
             utils::auth::generate_auth_cookie};
 
+use super::{audit, count_failed_login, is_failed_login_limited, record_login, RequestContext};
+
 #[tracing::instrument(name = "verify_2fa", skip_all)]
 pub async fn verify_2fa(State(state): State<AppState>,
+                        context: RequestContext,
                         jar: CookieJar,
                         Json(request): Json<Verify2FARequest>) -> 
     (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -28,24 +33,65 @@ pub async fn verify_2fa(State(state): State<AppState>,
         Err(_) => return (jar, Err(AuthAPIError::Invalid2FACode))
     };
 
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    let result = two_fa_code_store.get_two_fa_code(&email).await;
-    let (slaid, stfc) = match result {
-        Ok((l, t)) => (l, t),
-        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials))
+    // Wrong codes count like wrong passwords, against the client IP and towards locking the account
+    if is_failed_login_limited(&state, &context) {
+        let event = context.event(AuditEventKind::TwoFAVerified, AuditOutcome::Failure)
+            .with_email(&email)
+            .with_detail("rate_limited");
+        audit(&state, event).await;
+        return (jar, Err(AuthAPIError::TooManyFailedLogins));
+    }
+
+    // Unknown emails only ever hold login attempt ids that can't be verified
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => Some(user),
+        Err(UserStoreError::UserNotFound) => None,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
     };
 
-    if slaid != login_attempt_id || stfc != two_fa_code {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    // Checked before the code is consumed, so that the code still works once the lock is lifted
+    if let Some(user) = user.as_ref().filter(|user| user.is_locked(Utc::now())) {
+        let event = context.event(AuditEventKind::TwoFAVerified, AuditOutcome::Failure)
+            .with_user(user)
+            .with_detail("account_locked");
+        audit(&state, event).await;
+        return (jar, Err(AuthAPIError::AccountLocked));
     }
 
-    if two_fa_code_store
-         .delete_two_fa_code(&email)
-         .await
-         .is_err() {
+    let two_fa_code_store = &state.two_fa_code_store;
+    // A wrong code ends the login attempt too, the user logs in again for a new code.
+    // Other pending login attempts of the user are left to complete on their own.
+    let result = two_fa_code_store
+        .verify_two_fa_code(&email, &login_attempt_id, &two_fa_code)
+        .await;
+    let failure = match result {
+        Ok(true) => None,
+        Ok(false) => Some("incorrect_code"),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Some("no_pending_code"),
+        Err(_) => Some("unexpected_error"),
+    };
+    if let Some(failure) = failure {
+        // A failing store isn't the client's doing
+        if failure != "unexpected_error" {
+            count_failed_login(&state, &context, user.as_ref()).await;
+        }
+        let event = context.event(AuditEventKind::TwoFAVerified, AuditOutcome::Failure);
+        let event = match &user {
+            Some(user) => event.with_user(user),
+            None => event.with_email(&email),
+        };
+        audit(&state, event.with_detail(failure)).await;
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
-    let result = generate_auth_cookie(&email);
+
+    // The user was deleted while the code was pending
+    let Some(user) = user else {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    };
+    audit(&state, context.event(AuditEventKind::TwoFAVerified, AuditOutcome::Success).with_user(&user)).await;
+    record_login(&state, &context, &user, "2fa").await;
+
+    let result = generate_auth_cookie(&user.id);
     let auth_cookie = match result {
         Ok(cookie) => cookie,
         Err(e) =>  return (jar, Err(AuthAPIError::UnexpectedError(e.into())))
@@ -1,19 +1,63 @@
 use axum::{extract::State, http::StatusCode, Json};
+use chrono::Utc;
 use serde::Deserialize;
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
+use crate::{app_state::AppState,
+            domain::{ApiKey, AuthAPIError, UserStoreError},
+            utils::auth::{validate_api_key, validate_token}};
 
 #[tracing::instrument(name = "Verify_Token", skip_all)]
 pub async fn verify_token(
     State(state): State<AppState>,
     Json(request): Json<VerifyTokenRequest>) -> Result<StatusCode, AuthAPIError> {
+    if ApiKey::looks_like_api_key(&request.token) {
+        return verify_api_key(&state, &request).await;
+    }
+
     match validate_token(&request.token, state.banned_token_store.clone()).await {
         Ok(_) => Ok(StatusCode::OK),
         Err(_) => Err(AuthAPIError::InvalidToken),
     }
 }
 
+// API keys are accepted in place of a JWT. A key is only as good as its owner: it stops verifying
+// while the owner is locked, and once the owner's tokens are revoked after the key was created.
+// A key only ever grants its scopes, so a request for an API key must name the scope it needs.
+async fn verify_api_key(state: &AppState, request: &VerifyTokenRequest) -> Result<StatusCode, AuthAPIError> {
+    let record = validate_api_key(&request.token, state.api_key_store.clone())
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let owner = match state.user_store.get_user_by_id(&record.user_id).await {
+        Ok(owner) => owner,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if state
+        .banned_token_store
+        .is_revoked_user_token(&owner.id, record.created_at)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+    {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    if owner.is_locked(Utc::now()) {
+        return Err(AuthAPIError::AccountLocked);
+    }
+
+    match &request.scope {
+        None => Err(AuthAPIError::InvalidApiKeyRequest),
+        Some(scope) if !record.has_scope(scope) => Err(AuthAPIError::Forbidden),
+        Some(_) => Ok(StatusCode::OK),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct VerifyTokenRequest {
     token: String,
-}
\ No newline at end of file
+    // Checked against the scopes of an API key, required for one. A JWT stands for the user and
+    // has every scope.
+    scope: Option<String>,
+}
@@ -0,0 +1,144 @@
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHash,
+    PasswordHasher as _, PasswordVerifier, Version,
+};
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    domain::{is_bcrypt_hash, HashedPassword, Password, PasswordHasher},
+    utils::config::PasswordHashConfig,
+};
+
+// Recorded as the argon2 key id of peppered hashes, so that hashes made before the pepper was set
+// can still be told apart and verified without it
+const PEPPER_KEY_ID: &[u8] = b"pepper";
+
+// Hashes new passwords with argon2id, and verifies argon2, scrypt and bcrypt hashes
+pub struct Argon2PasswordHasher {
+    params: Params,
+    // Mixed into every hash as the argon2 secret, it never gets stored next to the hashes
+    pepper: Option<Secret<String>>,
+}
+
+impl Argon2PasswordHasher {
+    pub fn new(config: &PasswordHashConfig, pepper: Option<Secret<String>>) -> Result<Self> {
+        let mut params = ParamsBuilder::new();
+        params.m_cost(config.memory_kib).t_cost(config.iterations).p_cost(config.parallelism);
+        if pepper.is_some() {
+            params.keyid(KeyId::new(PEPPER_KEY_ID).map_err(|e| eyre!("invalid argon2 key id: {}", e))?);
+        }
+        let params = params.build().map_err(|e| eyre!("invalid argon2 parameters: {}", e))?;
+        Ok(Self { params, pepper })
+    }
+
+    fn argon2<'a>(pepper: &'a Option<Secret<String>>, params: Params) -> Result<Argon2<'a>> {
+        match pepper {
+            Some(pepper) => Argon2::new_with_secret(pepper.expose_secret().as_bytes(),
+                                                    Algorithm::Argon2id,
+                                                    Version::V0x13,
+                                                    params)
+                .map_err(|e| eyre!("invalid password pepper: {}", e)),
+            None => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordHasher for Argon2PasswordHasher {
+    #[tracing::instrument(name = "Computing password hash", skip_all)]
+    async fn hash_password(&self, password: &Password) -> Result<HashedPassword> {
+        let current_span: tracing::Span = tracing::Span::current();
+        let password = password.as_ref().to_owned();
+        let pepper = self.pepper.clone();
+        let params = self.params.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            current_span.in_scope(|| {
+                let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
+                let password_hash = Self::argon2(&pepper, params)?
+                    .hash_password(password.expose_secret().as_bytes(), &salt)
+                    .map_err(|e| eyre!("failed to hash password: {}", e))?
+                    .to_string();
+
+                HashedPassword::parse(Secret::new(password_hash))
+            })
+        })
+        .await;
+
+        result?
+    }
+
+    #[tracing::instrument(name = "Verify password hash", skip_all)]
+    async fn verify_password(&self, password_hash: &HashedPassword, candidate: &Password) -> Result<()> {
+        let current_span: tracing::Span = tracing::Span::current();
+        let password_hash = password_hash.as_ref().to_owned();
+        let candidate = candidate.as_ref().to_owned();
+        let pepper = self.pepper.clone();
+        let params = self.params.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            current_span.in_scope(|| {
+                let candidate = candidate.expose_secret().as_bytes();
+
+                // Imported legacy hashes were never peppered
+                if is_bcrypt_hash(password_hash.expose_secret()) {
+                    return match bcrypt::verify(candidate, password_hash.expose_secret()) {
+                        Ok(true) => Ok(()),
+                        Ok(false) => Err(eyre!("invalid password")),
+                        Err(e) => Err(eyre!("failed to verify bcrypt hash: {}", e)),
+                    };
+                }
+
+                let expected_password_hash: PasswordHash<'_> =
+                    PasswordHash::new(password_hash.expose_secret())
+                        .map_err(|e| eyre!("failed to parse password hash: {}", e))?;
+
+                if expected_password_hash.algorithm == scrypt::ALG_ID {
+                    return scrypt::Scrypt
+                        .verify_password(candidate, &expected_password_hash)
+                        .wrap_err("failed to verify scrypt hash");
+                }
+
+                // The cost parameters are read from the hash, so changing them keeps old hashes valid
+                let peppered = Params::try_from(&expected_password_hash)
+                    .map_err(|e| eyre!("failed to parse argon2 parameters: {}", e))?
+                    .keyid() == PEPPER_KEY_ID;
+                if peppered && pepper.is_none() {
+                    return Err(eyre!("password hash is peppered but no pepper is set"));
+                }
+
+                let result = Self::argon2(&pepper, params.clone())?
+                    .verify_password(candidate, &expected_password_hash);
+                // Hashes without the key id were made before the pepper was set, or imported, or made
+                // before the key id was recorded. They are rehashed with the pepper once verified.
+                if result.is_err() && !peppered && pepper.is_some() {
+                    return Self::argon2(&None, params)?
+                        .verify_password(candidate, &expected_password_hash)
+                        .wrap_err("failed to verify password hash");
+                }
+                result.wrap_err("failed to verify password hash")
+            })
+        })
+        .await;
+
+        result?
+    }
+
+    fn needs_rehash(&self, password_hash: &HashedPassword) -> bool {
+        let password_hash = match PasswordHash::new(password_hash.as_ref().expose_secret()) {
+            Ok(password_hash) => password_hash,
+            Err(_) => return true,
+        };
+
+        password_hash.algorithm != Algorithm::Argon2id.ident()
+            || password_hash.version != Some(Version::V0x13.into())
+            || Params::try_from(&password_hash).map_or(true, |params| {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+                    // Also catches hashes made before the pepper was set
+                    || params.keyid() != self.params.keyid()
+            })
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+
+use crate::domain::{DisplayName, Email, HashedPassword, Password, User, UserId, UserStore, UserStoreError};
+
+// Read-through cache in front of another user store. Lookups by email and by id are served
+// from a bounded LRU cache whose entries expire after `ttl`, writes go to the inner store
+// and then refresh or drop the cached user. Writes made elsewhere, by another instance or
+// by auth-admin, are only seen once the entry expires, or on the next `validate_user`.
+pub struct CachedUserStore<S: UserStore> {
+    inner: S,
+    ttl: Duration,
+    cache: Mutex<Cache>,
+}
+
+struct Cache {
+    users: LruCache<Email, CachedUser>,
+    emails: HashMap<UserId, Email>,
+    // Bumped by every write, a lookup that raced with one doesn't cache what it read
+    generation: u64,
+}
+
+struct CachedUser {
+    user: User,
+    cached_at: Instant,
+}
+
+impl<S: UserStore> CachedUserStore<S> {
+    pub fn new(inner: S, capacity: NonZeroUsize, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: Mutex::new(Cache {
+                users: LruCache::new(capacity),
+                emails: HashMap::new(),
+                generation: 0,
+            }),
+        }
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, Cache> {
+        // The cache is always left consistent, a panic while holding the lock doesn't corrupt it
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn cached_by_email(&self, email: &Email) -> Option<User> {
+        let mut cache = self.cache();
+        let expired = match cache.users.get(email) {
+            Some(cached) if cached.cached_at.elapsed() < self.ttl => return Some(cached.user.clone()),
+            Some(_) => true,
+            None => false,
+        };
+        if expired {
+            cache.remove(email);
+        }
+        None
+    }
+
+    fn cached_by_id(&self, id: &UserId) -> Option<User> {
+        let email = self.cache().emails.get(id).cloned()?;
+        self.cached_by_email(&email)
+    }
+
+    fn generation(&self) -> u64 {
+        self.cache().generation
+    }
+
+    // Caches a user read from the inner store, unless a write happened since `generation`
+    fn fill(&self, user: &User, generation: u64) {
+        let mut cache = self.cache();
+        if cache.generation == generation {
+            cache.insert(user.clone());
+        }
+    }
+
+    // Caches the user returned by a write
+    fn refresh(&self, user: &User) {
+        let mut cache = self.cache();
+        cache.generation += 1;
+        cache.insert(user.clone());
+    }
+
+    fn invalidate(&self, email: &Email) {
+        let mut cache = self.cache();
+        cache.generation += 1;
+        cache.remove(email);
+    }
+
+    fn invalidate_id(&self, id: &UserId) {
+        let mut cache = self.cache();
+        cache.generation += 1;
+        cache.remove_id(id);
+    }
+}
+
+impl Cache {
+    fn insert(&mut self, user: User) {
+        // Drops the user's entry under a previous email
+        self.remove_id(&user.id);
+        let (id, email) = (user.id, user.email.clone());
+        let cached = CachedUser { user, cached_at: Instant::now() };
+        // Hands back the least recently used user, or another user cached under the same email
+        if let Some((_, evicted)) = self.users.push(email.clone(), cached) {
+            self.emails.remove(&evicted.user.id);
+        }
+        self.emails.insert(id, email);
+    }
+
+    fn remove(&mut self, email: &Email) {
+        if let Some(cached) = self.users.pop(email) {
+            self.emails.remove(&cached.user.id);
+        }
+    }
+
+    fn remove_id(&mut self, id: &UserId) {
+        if let Some(email) = self.emails.remove(id) {
+            self.users.pop(&email);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: UserStore + Send + Sync> UserStore for CachedUserStore<S> {
+    async fn add_user(&self, user: User, password: Option<Password>) -> Result<(), UserStoreError> {
+        self.inner.add_user(user, password).await
+    }
+
+    #[tracing::instrument(name = "Retrieving user through CachedUserStore", skip_all)]
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        if let Some(user) = self.cached_by_email(email) {
+            return Ok(user);
+        }
+
+        let generation = self.generation();
+        let user = self.inner.get_user(email).await?;
+        self.fill(&user, generation);
+        Ok(user)
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id through CachedUserStore", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        if let Some(user) = self.cached_by_id(id) {
+            return Ok(user);
+        }
+
+        let generation = self.generation();
+        let user = self.inner.get_user_by_id(id).await?;
+        self.fill(&user, generation);
+        Ok(user)
+    }
+
+    async fn list_users(&self, after: Option<&UserId>, limit: usize) -> Result<Vec<User>, UserStoreError> {
+        self.inner.list_users(after, limit).await
+    }
+
+    // Always checked against the inner store, the cache is refreshed with the user it read
+    #[tracing::instrument(name = "Validating user past CachedUserStore", skip_all)]
+    async fn validate_user(&self, email: &Email, password: &Password) -> Result<User, UserStoreError> {
+        let generation = self.generation();
+        let user = self.inner.validate_user(email, password).await?;
+        self.fill(&user, generation);
+        Ok(user)
+    }
+
+    async fn import_user(&self, user: User, password_hash: HashedPassword) -> Result<(), UserStoreError> {
+        self.inner.import_user(user, password_hash).await
+    }
+
+    async fn update_display_name(&self,
+                                 email: &Email,
+                                 display_name: Option<DisplayName>) -> Result<User, UserStoreError> {
+        let result = self.inner.update_display_name(email, display_name).await;
+        self.refresh_or_invalidate(email, &result);
+        result
+    }
+
+    // Applied to the cached user as well, so that repeat logins keep hitting the cache
+    async fn record_login(&self, email: &Email, at: DateTime<Utc>) -> Result<(), UserStoreError> {
+        let result = self.inner.record_login(email, at).await;
+        let mut cache = self.cache();
+        cache.generation += 1;
+        match (&result, cache.users.get_mut(email)) {
+            (Ok(()), Some(cached)) => {
+                cached.user.last_login_at = Some(at);
+                cached.user.failed_login_attempts = 0;
+            }
+            (Ok(()), None) => {}
+            (Err(_), _) => cache.remove(email),
+        }
+        result
+    }
+
+    async fn record_failed_login(&self,
+                                 email: &Email,
+                                 max_attempts: u32,
+                                 locked_until: DateTime<Utc>) -> Result<(), UserStoreError> {
+        let result = self.inner.record_failed_login(email, max_attempts, locked_until).await;
+        self.invalidate(email);
+        result
+    }
+
+    async fn unlock_user(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let result = self.inner.unlock_user(id).await;
+        self.refresh_or_invalidate_id(id, &result);
+        result
+    }
+
+    async fn update_requires_2fa(&self, id: &UserId, requires_2fa: bool) -> Result<User, UserStoreError> {
+        let result = self.inner.update_requires_2fa(id, requires_2fa).await;
+        self.refresh_or_invalidate_id(id, &result);
+        result
+    }
+
+    async fn clear_password(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let result = self.inner.clear_password(id).await;
+        self.refresh_or_invalidate_id(id, &result);
+        result
+    }
+
+    async fn set_password(&self, id: &UserId, password: Password) -> Result<User, UserStoreError> {
+        let result = self.inner.set_password(id, password).await;
+        self.refresh_or_invalidate_id(id, &result);
+        result
+    }
+
+    async fn update_email(&self, id: &UserId, email: Email) -> Result<User, UserStoreError> {
+        let result = self.inner.update_email(id, email).await;
+        self.refresh_or_invalidate_id(id, &result);
+        result
+    }
+}
+
+impl<S: UserStore> CachedUserStore<S> {
+    fn refresh_or_invalidate(&self, email: &Email, result: &Result<User, UserStoreError>) {
+        match result {
+            Ok(user) => self.refresh(user),
+            Err(_) => self.invalidate(email),
+        }
+    }
+
+    fn refresh_or_invalidate_id(&self, id: &UserId, result: &Result<User, UserStoreError>) {
+        match result {
+            Ok(user) => self.refresh(user),
+            Err(_) => self.invalidate_id(id),
+        }
+    }
+}
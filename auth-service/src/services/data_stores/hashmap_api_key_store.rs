@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{ApiKeyRecord, ApiKeyStore, ApiKeyStoreError, UserId};
+
+#[derive(Default)]
+pub struct HashmapApiKeyStore {
+    // Keyed by the visible prefix of the API key
+    api_keys: RwLock<HashMap<String, ApiKeyRecord>>,
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for HashmapApiKeyStore {
+    #[tracing::instrument(name = "Adding API key to HashmapApiKeyStore", skip_all)]
+    async fn add_api_key(&self, api_key: ApiKeyRecord) -> Result<(), ApiKeyStoreError> {
+        let mut api_keys = self.api_keys.write().await;
+        if api_keys.contains_key(&api_key.prefix) {
+            return Err(ApiKeyStoreError::ApiKeyAlreadyExists);
+        }
+        api_keys.insert(api_key.prefix.clone(), api_key);
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving API key from HashmapApiKeyStore", skip_all)]
+    async fn get_api_key(&self, prefix: &str) -> Result<ApiKeyRecord, ApiKeyStoreError> {
+        match self.api_keys.read().await.get(prefix) {
+            Some(api_key) => Ok(api_key.clone()),
+            None => Err(ApiKeyStoreError::ApiKeyNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Retrieving API keys of user from HashmapApiKeyStore", skip_all)]
+    async fn get_api_keys(&self, user_id: &UserId) -> Result<Vec<ApiKeyRecord>, ApiKeyStoreError> {
+        let mut api_keys: Vec<ApiKeyRecord> = self.api_keys
+            .read()
+            .await
+            .values()
+            .filter(|api_key| &api_key.user_id == user_id)
+            .cloned()
+            .collect();
+        api_keys.sort_by_key(|api_key| api_key.created_at);
+        Ok(api_keys)
+    }
+
+    #[tracing::instrument(name = "Deleting API key from HashmapApiKeyStore", skip_all)]
+    async fn delete_api_key(&self, user_id: &UserId, id: &str) -> Result<(), ApiKeyStoreError> {
+        let mut api_keys = self.api_keys.write().await;
+        let prefix = match api_keys
+            .values()
+            .find(|api_key| &api_key.user_id == user_id && api_key.id == id) {
+            Some(api_key) => api_key.prefix.clone(),
+            None => return Err(ApiKeyStoreError::ApiKeyNotFound),
+        };
+        api_keys.remove(&prefix);
+        Ok(())
+    }
+
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use secrecy::ExposeSecret;
+use tokio::sync::RwLock;
+
+use crate::domain::{DeviceCode, DeviceGrant, DeviceGrantStatus, DeviceGrantStore, DeviceGrantStoreError, UserCode};
+
+// Both maps sit behind one lock, so that a grant and its user code are always added and removed together
+#[derive(Default)]
+pub struct HashmapDeviceGrantStore {
+    grants: RwLock<DeviceGrants>,
+}
+
+#[derive(Default)]
+struct DeviceGrants {
+    device_grants: HashMap<String, DeviceGrant>,
+    device_codes: HashMap<UserCode, String>,
+}
+
+#[async_trait::async_trait]
+impl DeviceGrantStore for HashmapDeviceGrantStore {
+    async fn add_device_grant(&self, device_grant: DeviceGrant) -> Result<(), DeviceGrantStoreError> {
+        let mut grants = self.grants.write().await;
+        let DeviceGrants { device_grants, device_codes } = &mut *grants;
+        // Grants that were never polled to the end would otherwise stay forever, there is no TTL to drop them
+        device_grants.retain(|_, device_grant| !device_grant.is_expired());
+        device_codes.retain(|_, device_code| device_grants.contains_key(device_code));
+
+        if device_codes.contains_key(&device_grant.user_code) {
+            return Err(DeviceGrantStoreError::UserCodeAlreadyExists);
+        }
+
+        let device_code = device_grant.device_code.as_ref().expose_secret().to_owned();
+        device_codes.insert(device_grant.user_code.clone(), device_code.clone());
+        device_grants.insert(device_code, device_grant);
+        Ok(())
+    }
+
+    async fn decide_device_grant(&self,
+                                 user_code: &UserCode,
+                                 status: DeviceGrantStatus) -> Result<(), DeviceGrantStoreError> {
+        let mut grants = self.grants.write().await;
+        let DeviceGrants { device_grants, device_codes } = &mut *grants;
+        match device_codes.get(user_code).and_then(|code| device_grants.get_mut(code)) {
+            Some(device_grant) if !device_grant.is_expired() && device_grant.status == DeviceGrantStatus::Pending => {
+                device_grant.status = status;
+                Ok(())
+            }
+            _ => Err(DeviceGrantStoreError::DeviceGrantNotFound),
+        }
+    }
+
+    async fn poll_device_grant(&self,
+                               device_code: &DeviceCode,
+                               now: DateTime<Utc>) -> Result<DeviceGrant, DeviceGrantStoreError> {
+        let mut grants = self.grants.write().await;
+        let device_grant = match grants.device_grants.get_mut(device_code.as_ref().expose_secret()) {
+            Some(device_grant) if device_grant.is_expired() => return Err(DeviceGrantStoreError::DeviceGrantExpired),
+            Some(device_grant) => device_grant,
+            None => return Err(DeviceGrantStoreError::DeviceGrantNotFound),
+        };
+
+        if device_grant.status == DeviceGrantStatus::Pending {
+            let polled = device_grant.clone();
+            device_grant.last_polled_at = Some(now);
+            return Ok(polled);
+        }
+
+        let user_code = device_grant.user_code.clone();
+        grants.device_codes.remove(&user_code);
+        grants.device_grants
+            .remove(device_code.as_ref().expose_secret())
+            .ok_or(DeviceGrantStoreError::DeviceGrantNotFound)
+    }
+}
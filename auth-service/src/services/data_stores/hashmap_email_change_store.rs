@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use secrecy::Secret;
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{EmailChange, EmailChangeStore, EmailChangeStoreError, TwoFACode, TwoFACodeHash, UserId},
+    utils::constants::EMAIL_CHANGE_TTL_SECONDS_I64,
+};
+
+pub struct HashmapEmailChangeStore {
+    code_secret: Secret<String>,
+    email_changes: RwLock<HashMap<UserId, PendingEmailChange>>,
+}
+
+impl HashmapEmailChangeStore {
+    pub fn new(code_secret: Secret<String>) -> Self {
+        Self { code_secret, email_changes: RwLock::new(HashMap::new()) }
+    }
+}
+
+struct PendingEmailChange {
+    email_change: EmailChange,
+    code_hash: TwoFACodeHash,
+    expires_at: DateTime<Utc>,
+    failed_attempts: u32,
+}
+
+impl PendingEmailChange {
+    fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailChangeStore for HashmapEmailChangeStore {
+    async fn add_email_change(&self, email_change: EmailChange, code: &TwoFACode) -> Result<(), EmailChangeStoreError> {
+        let code_hash = TwoFACodeHash::for_email_change(&email_change.user_id, code, &self.code_secret);
+        let expires_at = Utc::now() + Duration::seconds(EMAIL_CHANGE_TTL_SECONDS_I64);
+        let pending = PendingEmailChange { email_change, code_hash, expires_at, failed_attempts: 0 };
+        self.email_changes.write().await.insert(pending.email_change.user_id, pending);
+        Ok(())
+    }
+
+    async fn get_email_change(&self, user_id: &UserId) -> Result<EmailChange, EmailChangeStoreError> {
+        match self.email_changes.read().await.get(user_id) {
+            Some(pending) if !pending.is_expired() => Ok(pending.email_change.clone()),
+            _ => Err(EmailChangeStoreError::EmailChangeNotFound),
+        }
+    }
+
+    async fn verify_email_change_code(&self, user_id: &UserId, code: &TwoFACode) -> Result<bool, EmailChangeStoreError> {
+        match self.email_changes.read().await.get(user_id) {
+            Some(pending) if !pending.is_expired() => {
+                Ok(pending.code_hash.matches_email_change(user_id, code, &self.code_secret))
+            }
+            _ => Err(EmailChangeStoreError::EmailChangeNotFound),
+        }
+    }
+
+    async fn record_failed_attempt(&self, user_id: &UserId, max_attempts: u32) -> Result<bool, EmailChangeStoreError> {
+        let mut email_changes = self.email_changes.write().await;
+        let failed_attempts = match email_changes.get_mut(user_id) {
+            Some(pending) if !pending.is_expired() => {
+                pending.failed_attempts += 1;
+                pending.failed_attempts
+            }
+            _ => return Err(EmailChangeStoreError::EmailChangeNotFound),
+        };
+
+        if failed_attempts >= max_attempts {
+            email_changes.remove(user_id);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn delete_email_change(&self, user_id: &UserId) -> Result<(), EmailChangeStoreError> {
+        match self.email_changes.write().await.remove(user_id) {
+            Some(_) => Ok(()),
+            None => Err(EmailChangeStoreError::EmailChangeNotFound),
+        }
+    }
+}
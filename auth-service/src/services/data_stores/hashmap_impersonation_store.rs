@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+
+use crate::domain::{Impersonation, ImpersonationStore, ImpersonationStoreError, UserId};
+
+#[derive(Default)]
+pub struct HashmapImpersonationStore {
+    impersonations: RwLock<HashMap<String, Impersonation>>,
+}
+
+#[async_trait::async_trait]
+impl ImpersonationStore for HashmapImpersonationStore {
+    async fn add_impersonation(&self, impersonation: Impersonation) -> Result<(), ImpersonationStoreError> {
+        self.impersonations.write().await.insert(impersonation.id.clone(), impersonation);
+        Ok(())
+    }
+
+    async fn get_impersonation(&self, id: &str) -> Result<Impersonation, ImpersonationStoreError> {
+        match self.impersonations.read().await.get(id) {
+            Some(impersonation) => Ok(impersonation.clone()),
+            None => Err(ImpersonationStoreError::ImpersonationNotFound),
+        }
+    }
+
+    async fn get_impersonations(&self, user_id: &UserId) -> Result<Vec<Impersonation>, ImpersonationStoreError> {
+        let mut impersonations: Vec<Impersonation> = self.impersonations
+            .read()
+            .await
+            .values()
+            .filter(|impersonation| &impersonation.admin_id == user_id || &impersonation.user_id == user_id)
+            .cloned()
+            .collect();
+        impersonations.sort_by_key(|impersonation| impersonation.started_at);
+        Ok(impersonations)
+    }
+
+    async fn end_impersonation(&self, id: &str, ended_at: DateTime<Utc>) -> Result<(), ImpersonationStoreError> {
+        match self.impersonations.write().await.get_mut(id) {
+            Some(impersonation) => {
+                // Keep the first end time, the record is an audit trail
+                impersonation.ended_at.get_or_insert(ended_at);
+                Ok(())
+            }
+            None => Err(ImpersonationStoreError::ImpersonationNotFound),
+        }
+    }
+}
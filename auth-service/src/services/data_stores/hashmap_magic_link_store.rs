@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use secrecy::ExposeSecret;
+use tokio::sync::RwLock;
+
+use crate::domain::{MagicLinkId, MagicLinkStore, MagicLinkStoreError, UserId};
+use crate::utils::constants::MAGIC_LINK_TTL_SECONDS_I64;
+
+#[derive(Default)]
+pub struct HashmapMagicLinkStore {
+    // The user of each link and when it expires
+    magic_links: RwLock<HashMap<String, (UserId, DateTime<Utc>)>>,
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for HashmapMagicLinkStore {
+    async fn add_magic_link(&self,
+        user_id: &UserId,
+        magic_link_id: MagicLinkId) ->
+        Result<(), MagicLinkStoreError> {
+        let mut magic_links = self.magic_links.write().await;
+        // Links that were never used would otherwise stay forever, there is no TTL to drop them
+        let now = Utc::now();
+        magic_links.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let expires_at = now + Duration::seconds(MAGIC_LINK_TTL_SECONDS_I64);
+        magic_links.insert(magic_link_id.as_ref().expose_secret().to_owned(), (*user_id, expires_at));
+        Ok(())
+    }
+
+    async fn take_magic_link(&self, magic_link_id: &MagicLinkId) ->
+        Result<UserId, MagicLinkStoreError> {
+        match self.magic_links.write().await.remove(magic_link_id.as_ref().expose_secret()) {
+            Some((user_id, expires_at)) if expires_at > Utc::now() => Ok(user_id),
+            _ => Err(MagicLinkStoreError::MagicLinkNotFound)
+        }
+    }
+}
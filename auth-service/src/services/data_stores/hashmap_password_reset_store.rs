@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use secrecy::ExposeSecret;
+use tokio::sync::RwLock;
+
+use crate::domain::{PasswordResetStore, PasswordResetStoreError, PasswordResetToken, UserId};
+use crate::utils::constants::PASSWORD_RESET_TTL_SECONDS_I64;
+
+#[derive(Default)]
+pub struct HashmapPasswordResetStore {
+    // The user of each token and when it expires
+    password_resets: RwLock<HashMap<String, (UserId, DateTime<Utc>)>>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetStore for HashmapPasswordResetStore {
+    async fn add_password_reset(&self,
+        user_id: &UserId,
+        token: PasswordResetToken) ->
+        Result<(), PasswordResetStoreError> {
+        let mut password_resets = self.password_resets.write().await;
+        // Tokens that were never used would otherwise stay forever, there is no TTL to drop them
+        let now = Utc::now();
+        password_resets.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let expires_at = now + Duration::seconds(PASSWORD_RESET_TTL_SECONDS_I64);
+        password_resets.insert(token.as_ref().expose_secret().to_owned(), (*user_id, expires_at));
+        Ok(())
+    }
+
+    async fn take_password_reset(&self, token: &PasswordResetToken) ->
+        Result<UserId, PasswordResetStoreError> {
+        match self.password_resets.write().await.remove(token.as_ref().expose_secret()) {
+            Some((user_id, expires_at)) if expires_at > Utc::now() => Ok(user_id),
+            _ => Err(PasswordResetStoreError::PasswordResetNotFound)
+        }
+    }
+}
@@ -1,35 +1,106 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, VecDeque},
+    num::NonZeroUsize,
+};
 
-use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::RwLock;
+
+use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeHash, TwoFACodeStore, TwoFACodeStoreError};
 
-#[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    two_fa_codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    max_pending: NonZeroUsize,
+    code_secret: Secret<String>,
+    two_fa_codes: RwLock<TwoFACodes>,
+}
+
+#[derive(Default)]
+struct TwoFACodes {
+    // Keyed by login attempt id
+    codes: HashMap<String, (Email, TwoFACodeHash)>,
+    // The pending login attempts of each email, oldest first
+    attempts: HashMap<Email, VecDeque<String>>,
+}
+
+impl HashmapTwoFACodeStore {
+    pub fn new(max_pending: NonZeroUsize, code_secret: Secret<String>) -> Self {
+        Self {
+            max_pending,
+            code_secret,
+            two_fa_codes: RwLock::new(TwoFACodes::default()),
+        }
+    }
+}
+
+impl TwoFACodes {
+    fn remove(&mut self, email: &Email, login_attempt_id: &str) {
+        if let Some(attempts) = self.attempts.get_mut(email) {
+            attempts.retain(|attempt| attempt != login_attempt_id);
+            if attempts.is_empty() {
+                self.attempts.remove(email);
+            }
+        }
+        self.codes.remove(login_attempt_id);
+    }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
-    async fn add_two_fa_code(&mut self, 
+    async fn add_two_fa_code(&self, 
         email: &Email, 
         login_attempt_id: LoginAttemptId, 
         two_fa_code: TwoFACode) -> 
         Result<(), TwoFACodeStoreError> {
-        self.two_fa_codes.insert(email.clone(), (login_attempt_id, two_fa_code));
+        let code_hash = TwoFACodeHash::new(&login_attempt_id, &two_fa_code, &self.code_secret);
+        let login_attempt_id = login_attempt_id.as_ref().expose_secret().to_owned();
+
+        let mut two_fa_codes = self.two_fa_codes.write().await;
+        two_fa_codes.codes.insert(login_attempt_id.clone(), (email.clone(), code_hash));
+        let attempts = two_fa_codes.attempts.entry(email.clone()).or_default();
+        attempts.push_back(login_attempt_id);
+        let evicted: Vec<String> = attempts.drain(..attempts.len().saturating_sub(self.max_pending.get())).collect();
+        for login_attempt_id in evicted {
+            two_fa_codes.codes.remove(&login_attempt_id);
+        }
         Ok(())
     }
 
-    async fn get_two_fa_code(&self, email: &Email) ->
-        Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.two_fa_codes.get(email) {
-            Some(tcode) => Ok(tcode.clone()),
-            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound)   
+    async fn verify_two_fa_code(&self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        two_fa_code: &TwoFACode) ->
+        Result<bool, TwoFACodeStoreError> {
+        // Checked and removed under one write lock
+        let mut two_fa_codes = self.two_fa_codes.write().await;
+        let id = login_attempt_id.as_ref().expose_secret();
+        let code_hash = match two_fa_codes.codes.get(id) {
+            Some((pending_email, code_hash)) if pending_email == email => code_hash.clone(),
+            _ => return Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        };
+        two_fa_codes.remove(email, id);
+        Ok(code_hash.matches(login_attempt_id, two_fa_code, &self.code_secret))
+    }
+
+    // Deleting a code that isn't there succeeds, like in the other stores
+    async fn delete_two_fa_code(&self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId) ->
+        Result<(), TwoFACodeStoreError> {
+        let mut two_fa_codes = self.two_fa_codes.write().await;
+        let login_attempt_id = login_attempt_id.as_ref().expose_secret();
+        match two_fa_codes.codes.get(login_attempt_id) {
+            Some((pending_email, _)) if pending_email == email => {}
+            _ => return Ok(()),
         }
-    }  
+        two_fa_codes.remove(email, login_attempt_id);
+        Ok(())
+    }
 
-    async fn delete_two_fa_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        match self.two_fa_codes.remove(email) {
-            Some(_) => Ok(()),
-            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    async fn delete_two_fa_codes(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let mut two_fa_codes = self.two_fa_codes.write().await;
+        for login_attempt_id in two_fa_codes.attempts.remove(email).unwrap_or_default() {
+            two_fa_codes.codes.remove(&login_attempt_id);
         }
+        Ok(())
     }
-}
\ No newline at end of file
+}
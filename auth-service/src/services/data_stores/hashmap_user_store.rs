@@ -1,45 +1,244 @@
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, HashMap};
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+
+use crate::{
+    app_state::PasswordHasherType,
+    domain::{DisplayName, Email, HashedPassword, Password, User, UserId, UserStore, UserStoreError},
+};
 
-#[derive(Default)]
 pub struct HashmapUserStore {
-    users: HashMap<Email, User>,
+    users: RwLock<HashMap<Email, User>>,
+    password_hasher: PasswordHasherType,
+}
+
+impl HashmapUserStore {
+    pub fn new(password_hasher: PasswordHasherType) -> Self {
+        Self {
+            users: RwLock::new(HashMap::new()),
+            password_hasher,
+        }
+    }
+
+    async fn insert_user(&self, user: User) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        // The id is unique as well, like the primary key of the SQL stores
+        if users.values().any(|existing| existing.id == user.id) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        match users.entry(user.email.clone()) {
+            Entry::Occupied(_) => Err(UserStoreError::UserAlreadyExists),
+            Entry::Vacant(entry) => {
+                entry.insert(user);
+                Ok(())
+            }
+        }
+    }
+
+    async fn update_user(&self, id: &UserId, update: impl FnOnce(&mut User)) -> Result<User, UserStoreError> {
+        match self.users.write().await.values_mut().find(|user| &user.id == id) {
+            Some(user) => {
+                update(user);
+                user.updated_at = Utc::now();
+                Ok(user.clone())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
     #[tracing::instrument(name = "Adding user to HashmapUserStore", skip_all)]
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
-            return Err(UserStoreError::UserAlreadyExists);
-        }
-        self.users.insert(user.email.clone(), user);
-        Ok(())
+    async fn add_user(&self, mut user: User, password: Option<Password>) -> Result<(), UserStoreError> {
+        // Hashed before taking the lock
+        user.password_hash = match password {
+            Some(password) => Some(
+                self.password_hasher
+                    .hash_password(&password)
+                    .await
+                    .map_err(UserStoreError::UnexpectedError)?,
+            ),
+            None => None,
+        };
+
+        self.insert_user(user).await
     }
 
-    #[tracing::instrument(name = "Retrieving user from HashmapUserStore", skip_all)] 
+    #[tracing::instrument(name = "Retrieving user from HashmapUserStore", skip_all)]
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        match self.users.get(email) {
+        match self.users.read().await.get(email) {
             Some(user) => Ok(user.clone()),
             None => Err(UserStoreError::UserNotFound),
         }
     }
 
+    #[tracing::instrument(name = "Retrieving user by id from HashmapUserStore", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        match self.users.read().await.values().find(|user| &user.id == id) {
+            Some(user) => Ok(user.clone()),
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Listing users in HashmapUserStore", skip_all)]
+    async fn list_users(&self, after: Option<&UserId>, limit: usize) -> Result<Vec<User>, UserStoreError> {
+        let mut users: Vec<User> = self.users
+            .read()
+            .await
+            .values()
+            .filter(|user| after.is_none_or(|after| &user.id > after))
+            .cloned()
+            .collect();
+        users.sort_by_key(|user| user.id);
+        users.truncate(limit);
+        Ok(users)
+    }
+
     #[tracing::instrument(name = "Validating user credentials in HashmapUserStore", skip_all)]
     async fn validate_user(&self,
                            email: &Email,
-                           password: &Password) -> Result<(), UserStoreError> {
-        match self.users.get(email) {
+                           password: &Password) -> Result<User, UserStoreError> {
+        // Unknown emails fail like wrong passwords, so callers can't tell them apart
+        let mut user = match self.get_user(email).await {
+            Ok(user) => user,
+            Err(UserStoreError::UserNotFound) => return Err(UserStoreError::InvalidCredentials),
+            Err(e) => return Err(e),
+        };
+
+        if user.is_locked(Utc::now()) {
+            return Err(UserStoreError::UserLocked);
+        }
+
+        let password_hash = match &user.password_hash {
+            Some(password_hash) => password_hash,
+            None => return Err(UserStoreError::InvalidCredentials),
+        };
+
+        self.password_hasher
+            .verify_password(password_hash, password)
+            .await
+            .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+        if self.password_hasher.needs_rehash(password_hash) {
+            match self.password_hasher.hash_password(password).await {
+                Ok(password_hash) => {
+                    if let Some(stored) = self.users.write().await.get_mut(email) {
+                        stored.password_hash = Some(password_hash.clone());
+                    }
+                    user.password_hash = Some(password_hash);
+                }
+                // A failed upgrade must not fail the login, the old hash still works
+                Err(e) => tracing::warn!("failed to rehash password: {:?}", e),
+            }
+        }
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(name = "Importing user into HashmapUserStore", skip_all)]
+    async fn import_user(&self, mut user: User, password_hash: HashedPassword) -> Result<(), UserStoreError> {
+        user.password_hash = Some(password_hash);
+        self.insert_user(user).await
+    }
+
+    #[tracing::instrument(name = "Updating display name in HashmapUserStore", skip_all)]
+    async fn update_display_name(&self,
+                                 email: &Email,
+                                 display_name: Option<DisplayName>) -> Result<User, UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.display_name = display_name;
+                user.updated_at = Utc::now();
+                Ok(user.clone())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Updating email in HashmapUserStore", skip_all)]
+    async fn update_email(&self, id: &UserId, email: Email) -> Result<User, UserStoreError> {
+        let mut users = self.users.write().await;
+
+        let old_email = match users.values().find(|user| &user.id == id) {
+            Some(user) => user.email.clone(),
+            None => return Err(UserStoreError::UserNotFound),
+        };
+        // Changing only the display form keeps the same key
+        if old_email != email && users.contains_key(&email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        let mut user = users.remove(&old_email).ok_or(UserStoreError::UserNotFound)?;
+        user.email = email.clone();
+        user.updated_at = Utc::now();
+        users.insert(email, user.clone());
+        Ok(user)
+    }
+
+    #[tracing::instrument(name = "Recording login in HashmapUserStore", skip_all)]
+    async fn record_login(&self, email: &Email, at: DateTime<Utc>) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
             Some(user) => {
-                if user.password.eq(password) {
-                    Ok(())
-                } else {
-                    Err(UserStoreError::InvalidCredentials)
+                user.last_login_at = Some(at);
+                user.failed_login_attempts = 0;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Recording failed login in HashmapUserStore", skip_all)]
+    async fn record_failed_login(&self,
+                                 email: &Email,
+                                 max_attempts: u32,
+                                 locked_until: DateTime<Utc>) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.failed_login_attempts += 1;
+                if user.failed_login_attempts >= max_attempts {
+                    user.failed_login_attempts = 0;
+                    user.locked_until = Some(locked_until);
                 }
+                Ok(())
             }
             None => Err(UserStoreError::UserNotFound),
         }
     }
-}
 
+    #[tracing::instrument(name = "Unlocking user in HashmapUserStore", skip_all)]
+    async fn unlock_user(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.update_user(id, |user| {
+            user.failed_login_attempts = 0;
+            user.locked_until = None;
+        })
+        .await
+    }
+
+    #[tracing::instrument(name = "Updating 2FA requirement in HashmapUserStore", skip_all)]
+    async fn update_requires_2fa(&self, id: &UserId, requires_2fa: bool) -> Result<User, UserStoreError> {
+        self.update_user(id, |user| user.requires_2fa = requires_2fa).await
+    }
+
+    #[tracing::instrument(name = "Clearing password in HashmapUserStore", skip_all)]
+    async fn clear_password(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.update_user(id, |user| user.password_hash = None).await
+    }
+
+    #[tracing::instrument(name = "Setting password in HashmapUserStore", skip_all)]
+    async fn set_password(&self, id: &UserId, password: Password) -> Result<User, UserStoreError> {
+        let password_hash = self.password_hasher
+            .hash_password(&password)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        self.update_user(id, |user| {
+            user.password_hash = Some(password_hash);
+            user.failed_login_attempts = 0;
+            user.locked_until = None;
+        })
+        .await
+    }
+}
@@ -1,21 +1,45 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
 use secrecy::{Secret, ExposeSecret};
 
-use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
+use crate::domain::{data_stores::{BannedTokenStore, BannedTokenStoreError}, UserId};
 
 #[derive(Default)]
 pub struct HashsetBannedTokenStore {
-    banned_tokens: HashSet<String>
+    banned_tokens: RwLock<HashSet<String>>,
+    revoked_user_tokens: RwLock<HashMap<UserId, DateTime<Utc>>>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn add_banned_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        self.banned_tokens.insert(token.expose_secret().to_owned());
+    async fn add_banned_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        self.banned_tokens.write().await.insert(token.expose_secret().to_owned());
         Ok(())
     }
 
     async fn is_banned_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.banned_tokens.contains(token.expose_secret()))
+        Ok(self.banned_tokens.read().await.contains(token.expose_secret()))
+    }
+
+    async fn revoke_user_tokens(&self, user_id: &UserId, at: DateTime<Utc>) -> Result<(), BannedTokenStoreError> {
+        let mut revoked_user_tokens = self.revoked_user_tokens.write().await;
+        let revoked_at = revoked_user_tokens.entry(*user_id).or_insert(at);
+        *revoked_at = (*revoked_at).max(at);
+        Ok(())
+    }
+
+    async fn is_revoked_user_token(&self,
+                                   user_id: &UserId,
+                                   issued_at: DateTime<Utc>) -> Result<bool, BannedTokenStoreError> {
+        Ok(self.revoked_user_tokens
+            .read()
+            .await
+            .get(user_id)
+            .is_some_and(|revoked_at| issued_at <= *revoked_at))
+    }
+
+    async fn user_tokens_revoked_at(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>, BannedTokenStoreError> {
+        Ok(self.revoked_user_tokens.read().await.get(user_id).copied())
     }
 }
@@ -10,3 +10,43 @@ pub mod redis_banned_token_store;
 
 pub mod redis_two_fa_store;
 
+pub mod hashmap_api_key_store;
+
+pub mod postgres_api_key_store;
+
+pub mod hashmap_magic_link_store;
+
+pub mod redis_magic_link_store;
+
+pub mod hashmap_password_reset_store;
+
+pub mod redis_password_reset_store;
+
+pub mod hashmap_device_grant_store;
+
+pub mod redis_device_grant_store;
+
+pub mod hashmap_impersonation_store;
+
+pub mod postgres_impersonation_store;
+
+pub mod postgres_banned_token_store;
+
+pub mod postgres_two_fa_store;
+
+pub mod sqlite_user_store;
+
+pub mod sqlite_banned_token_store;
+
+pub mod sqlite_two_fa_store;
+
+
+pub mod hashmap_email_change_store;
+
+pub mod redis_email_change_store;
+
+pub mod cached_user_store;
+
+pub mod vec_audit_log;
+
+pub mod postgres_audit_log;
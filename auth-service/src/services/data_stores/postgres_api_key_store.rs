@@ -0,0 +1,124 @@
+use secrecy::{ExposeSecret, Secret};
+
+use sqlx::PgPool;
+
+use crate::domain::{ApiKeyRecord, ApiKeyStore, ApiKeyStoreError, UserId};
+
+pub struct PostgresApiKeyStore {
+    pool: PgPool,
+}
+
+impl PostgresApiKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for PostgresApiKeyStore {
+    #[tracing::instrument(name = "Adding API key to PostgreSQL", skip_all)]
+    async fn add_api_key(&self, api_key: ApiKeyRecord) -> Result<(), ApiKeyStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO api_keys (id, user_id, name, prefix, key_hash, scopes, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            api_key.id,
+            api_key.user_id.as_ref(),
+            api_key.name,
+            api_key.prefix,
+            api_key.key_hash.expose_secret(),
+            &api_key.scopes,
+            api_key.created_at,
+            api_key.expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+            Some(code) if code == UNIQUE_VIOLATION => ApiKeyStoreError::ApiKeyAlreadyExists,
+            _ => ApiKeyStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving API key from PostgreSQL", skip_all)]
+    async fn get_api_key(&self, prefix: &str) -> Result<ApiKeyRecord, ApiKeyStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT id, user_id, name, prefix, key_hash, scopes, created_at, expires_at
+            FROM api_keys
+            WHERE prefix = $1
+            "#,
+            prefix
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?
+        .map(|row| ApiKeyRecord {
+            id: row.id,
+            user_id: row.user_id.into(),
+            name: row.name,
+            prefix: row.prefix,
+            key_hash: Secret::new(row.key_hash),
+            scopes: row.scopes,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+        })
+        .ok_or(ApiKeyStoreError::ApiKeyNotFound)
+    }
+
+    #[tracing::instrument(name = "Retrieving API keys of user from PostgreSQL", skip_all)]
+    async fn get_api_keys(&self, user_id: &UserId) -> Result<Vec<ApiKeyRecord>, ApiKeyStoreError> {
+        let api_keys = sqlx::query!(
+            r#"
+            SELECT id, user_id, name, prefix, key_hash, scopes, created_at, expires_at
+            FROM api_keys
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+            user_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|row| ApiKeyRecord {
+            id: row.id,
+            user_id: row.user_id.into(),
+            name: row.name,
+            prefix: row.prefix,
+            key_hash: Secret::new(row.key_hash),
+            scopes: row.scopes,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+        })
+        .collect();
+
+        Ok(api_keys)
+    }
+
+    #[tracing::instrument(name = "Deleting API key from PostgreSQL", skip_all)]
+    async fn delete_api_key(&self, user_id: &UserId, id: &str) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM api_keys
+            WHERE user_id = $1 AND id = $2
+            "#,
+            user_id.as_ref(),
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ApiKeyStoreError::ApiKeyNotFound);
+        }
+        Ok(())
+    }
+
+}
+
+// SQLSTATE raised by PostgreSQL when a UNIQUE constraint is violated
+const UNIQUE_VIOLATION: &str = "23505";
@@ -0,0 +1,122 @@
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{AuditEvent, AuditEventFilter, AuditEventKind, AuditLog, AuditLogError, AuditOutcome, AuditRecord,
+                    Email};
+
+pub struct PostgresAuditLog {
+    pool: PgPool,
+}
+
+impl PostgresAuditLog {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLog for PostgresAuditLog {
+    #[tracing::instrument(name = "Recording audit event in PostgreSQL", skip_all)]
+    async fn record(&self, event: AuditEvent) -> Result<(), AuditLogError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_events (occurred_at, kind, outcome, user_id, email, email_canonical, ip, user_agent,
+                                      detail)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            event.occurred_at,
+            event.kind.as_ref(),
+            event.outcome.as_ref(),
+            event.user_id.as_ref().map(AsRef::<Uuid>::as_ref),
+            event.email.as_ref().map(|email| email.as_ref().expose_secret().as_str()),
+            event.email.as_ref().map(|email| email.canonical().expose_secret().as_str()),
+            event.ip.map(|ip| ip.to_string()),
+            event.user_agent,
+            event.detail
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Querying audit events in PostgreSQL", skip_all)]
+    async fn query(&self,
+                   filter: &AuditEventFilter,
+                   before: Option<i64>,
+                   limit: usize) -> Result<Vec<AuditRecord>, AuditLogError> {
+        let limit = i64::try_from(limit).map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+        sqlx::query_as!(
+            AuditEventRow,
+            r#"
+            SELECT id, occurred_at, kind, outcome, user_id, email, ip, user_agent, detail
+            FROM audit_events
+            WHERE ($1::BIGINT IS NULL OR id < $1)
+              AND ($2::UUID IS NULL OR user_id = $2)
+              AND ($3::TEXT IS NULL OR email_canonical = $3)
+              AND ($4::TEXT IS NULL OR kind = $4)
+              AND ($5::TEXT IS NULL OR outcome = $5)
+              AND ($6::TIMESTAMPTZ IS NULL OR occurred_at >= $6)
+              AND ($7::TIMESTAMPTZ IS NULL OR occurred_at < $7)
+            ORDER BY id DESC
+            LIMIT $8
+            "#,
+            before,
+            filter.user_id.as_ref().map(AsRef::<Uuid>::as_ref),
+            filter.email.as_ref().map(|email| email.canonical().expose_secret().as_str()),
+            filter.kind.as_ref().map(AsRef::<str>::as_ref),
+            filter.outcome.as_ref().map(AsRef::<str>::as_ref),
+            filter.since,
+            filter.until,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AuditLogError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(AuditRecord::try_from)
+        .collect()
+    }
+}
+
+struct AuditEventRow {
+    id: i64,
+    occurred_at: DateTime<Utc>,
+    kind: String,
+    outcome: String,
+    user_id: Option<Uuid>,
+    email: Option<String>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    detail: Option<String>,
+}
+
+impl TryFrom<AuditEventRow> for AuditRecord {
+    type Error = AuditLogError;
+
+    fn try_from(row: AuditEventRow) -> Result<Self, Self::Error> {
+        Ok(AuditRecord {
+            id: row.id,
+            event: AuditEvent {
+                occurred_at: row.occurred_at,
+                kind: AuditEventKind::parse(&row.kind).map_err(AuditLogError::UnexpectedError)?,
+                outcome: AuditOutcome::parse(&row.outcome).map_err(AuditLogError::UnexpectedError)?,
+                user_id: row.user_id.map(Into::into),
+                email: row.email
+                    .map(|email| Email::parse(Secret::new(email)))
+                    .transpose()
+                    .map_err(AuditLogError::UnexpectedError)?,
+                ip: row.ip
+                    .map(|ip| ip.parse())
+                    .transpose()
+                    .map_err(|e: std::net::AddrParseError| AuditLogError::UnexpectedError(e.into()))?,
+                user_agent: row.user_agent,
+                detail: row.detail,
+            },
+        })
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::{DateTime, Duration, Utc};
+use secrecy::{ExposeSecret, Secret};
+
+use sqlx::PgPool;
+
+use crate::{
+    domain::{data_stores::{BannedTokenStore, BannedTokenStoreError}, UserId},
+    utils::constants::TTL_SECONDS_I64,
+};
+
+pub struct PostgresBannedTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresBannedTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for PostgresBannedTokenStore {
+    #[tracing::instrument(name = "Adding banned token to PostgreSQL", skip_all)]
+    async fn add_banned_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        // A token doesn't need to stay banned once it has expired on its own
+        let expires_at = Utc::now() + Duration::seconds(TTL_SECONDS_I64);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO banned_tokens (token, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (token) DO NOTHING
+            "#,
+            token.expose_secret(),
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking banned token in PostgreSQL", skip_all)]
+    async fn is_banned_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM banned_tokens
+                WHERE token = $1 AND expires_at > NOW()
+            ) AS "is_banned!"
+            "#,
+            token.expose_secret()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(row.is_banned)
+    }
+
+    #[tracing::instrument(name = "Revoking user tokens in PostgreSQL", skip_all)]
+    async fn revoke_user_tokens(&self, user_id: &UserId, at: DateTime<Utc>) -> Result<(), BannedTokenStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO revoked_user_tokens (user_id, revoked_at)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET revoked_at = GREATEST(revoked_user_tokens.revoked_at, EXCLUDED.revoked_at)
+            "#,
+            user_id.as_ref(),
+            at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking revoked user tokens in PostgreSQL", skip_all)]
+    async fn is_revoked_user_token(&self,
+                                   user_id: &UserId,
+                                   issued_at: DateTime<Utc>) -> Result<bool, BannedTokenStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM revoked_user_tokens
+                WHERE user_id = $1 AND revoked_at >= $2
+            ) AS "is_revoked!"
+            "#,
+            user_id.as_ref(),
+            issued_at
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(row.is_revoked)
+    }
+
+    #[tracing::instrument(name = "Retrieving user token revocation from PostgreSQL", skip_all)]
+    async fn user_tokens_revoked_at(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>, BannedTokenStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT revoked_at FROM revoked_user_tokens
+            WHERE user_id = $1
+            "#,
+            user_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(row.map(|row| row.revoked_at))
+    }
+}
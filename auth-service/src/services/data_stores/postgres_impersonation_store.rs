@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+
+use sqlx::PgPool;
+
+use crate::domain::{Impersonation, ImpersonationStore, ImpersonationStoreError, UserId};
+
+pub struct PostgresImpersonationStore {
+    pool: PgPool,
+}
+
+impl PostgresImpersonationStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ImpersonationStore for PostgresImpersonationStore {
+    #[tracing::instrument(name = "Adding impersonation to PostgreSQL", skip_all)]
+    async fn add_impersonation(&self, impersonation: Impersonation) -> Result<(), ImpersonationStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO impersonations (id, admin_id, user_id, reason, started_at, expires_at, ended_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            impersonation.id,
+            impersonation.admin_id.as_ref(),
+            impersonation.user_id.as_ref(),
+            impersonation.reason,
+            impersonation.started_at,
+            impersonation.expires_at,
+            impersonation.ended_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ImpersonationStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving impersonation from PostgreSQL", skip_all)]
+    async fn get_impersonation(&self, id: &str) -> Result<Impersonation, ImpersonationStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT id, admin_id, user_id, reason, started_at, expires_at, ended_at
+            FROM impersonations
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ImpersonationStoreError::UnexpectedError(e.into()))?
+        .map(|row| Impersonation {
+            id: row.id,
+            admin_id: row.admin_id.into(),
+            user_id: row.user_id.into(),
+            reason: row.reason,
+            started_at: row.started_at,
+            expires_at: row.expires_at,
+            ended_at: row.ended_at,
+        })
+        .ok_or(ImpersonationStoreError::ImpersonationNotFound)
+    }
+
+    #[tracing::instrument(name = "Retrieving impersonations of a user from PostgreSQL", skip_all)]
+    async fn get_impersonations(&self, user_id: &UserId) -> Result<Vec<Impersonation>, ImpersonationStoreError> {
+        let impersonations = sqlx::query!(
+            r#"
+            SELECT id, admin_id, user_id, reason, started_at, expires_at, ended_at
+            FROM impersonations
+            WHERE admin_id = $1 OR user_id = $1
+            ORDER BY started_at
+            "#,
+            user_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ImpersonationStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|row| Impersonation {
+            id: row.id,
+            admin_id: row.admin_id.into(),
+            user_id: row.user_id.into(),
+            reason: row.reason,
+            started_at: row.started_at,
+            expires_at: row.expires_at,
+            ended_at: row.ended_at,
+        })
+        .collect();
+
+        Ok(impersonations)
+    }
+
+    #[tracing::instrument(name = "Ending impersonation in PostgreSQL", skip_all)]
+    async fn end_impersonation(&self, id: &str, ended_at: DateTime<Utc>) -> Result<(), ImpersonationStoreError> {
+        // Keep the first end time, the record is an audit trail
+        let result = sqlx::query!(
+            r#"
+            UPDATE impersonations
+            SET ended_at = COALESCE(ended_at, $2)
+            WHERE id = $1
+            "#,
+            id,
+            ended_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ImpersonationStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ImpersonationStoreError::ImpersonationNotFound);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use std::num::NonZeroUsize;
+
+use chrono::{Duration, Utc};
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, Secret};
+
+use sqlx::PgPool;
+
+use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeHash, TwoFACodeStore, TwoFACodeStoreError};
+use crate::utils::constants::TTL_SECONDS_I64;
+
+pub struct PostgresTwoFACodeStore {
+    pool: PgPool,
+    max_pending: NonZeroUsize,
+    code_secret: Secret<String>,
+}
+
+impl PostgresTwoFACodeStore {
+    pub fn new(pool: PgPool, max_pending: NonZeroUsize, code_secret: Secret<String>) -> Self {
+        Self { pool, max_pending, code_secret }
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for PostgresTwoFACodeStore {
+    #[tracing::instrument(name = "Adding 2FA code to PostgreSQL", skip_all)]
+    async fn add_two_fa_code(&self, email: &Email, login_attempt_id: LoginAttemptId, code: TwoFACode) ->
+        Result<(), TwoFACodeStoreError> {
+        let created_at = Utc::now();
+        let expires_at = created_at + Duration::seconds(TTL_SECONDS_I64);
+        let code_hash = TwoFACodeHash::new(&login_attempt_id, &code, &self.code_secret);
+        let max_pending = i64::try_from(self.max_pending.get()).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let mut transaction = self.pool.begin().await.map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO two_fa_codes (login_attempt_id, email, code_hash, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            login_attempt_id.as_ref().expose_secret(),
+            email.canonical().expose_secret(),
+            code_hash.as_ref().expose_secret(),
+            created_at,
+            expires_at
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // Drops the oldest codes of the email over the cap, along with its expired ones
+        sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE email = $1
+              AND login_attempt_id NOT IN (
+                  SELECT login_attempt_id
+                  FROM two_fa_codes
+                  WHERE email = $1 AND expires_at > NOW()
+                  ORDER BY login_attempt_id = $2 DESC, created_at DESC
+                  LIMIT $3
+              )
+            "#,
+            email.canonical().expose_secret(),
+            login_attempt_id.as_ref().expose_secret(),
+            max_pending
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        transaction.commit().await.map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Verifying 2FA code in PostgreSQL", skip_all)]
+    async fn verify_two_fa_code(&self, email: &Email, login_attempt_id: &LoginAttemptId, code: &TwoFACode) ->
+        Result<bool, TwoFACodeStoreError> {
+        let row = sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE login_attempt_id = $1 AND email = $2 AND expires_at > NOW()
+            RETURNING code_hash
+            "#,
+            login_attempt_id.as_ref().expose_secret(),
+            email.canonical().expose_secret()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let code_hash = TwoFACodeHash::parse(Secret::new(row.code_hash))
+            .wrap_err("failed to parse code_hash")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(code_hash.matches(login_attempt_id, code, &self.code_secret))
+    }
+
+    #[tracing::instrument(name = "Deleting 2FA code from PostgreSQL", skip_all)]
+    async fn delete_two_fa_code(&self, email: &Email, login_attempt_id: &LoginAttemptId) ->
+        Result<(), TwoFACodeStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE login_attempt_id = $1 AND email = $2
+            "#,
+            login_attempt_id.as_ref().expose_secret(),
+            email.canonical().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Deleting 2FA codes of an email from PostgreSQL", skip_all)]
+    async fn delete_two_fa_codes(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE email = $1
+            "#,
+            email.canonical().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
@@ -1,133 +1,445 @@
-use argon2::{
-    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
-    PasswordVerifier, Version,
-};
-
+use chrono::{DateTime, Utc};
 use secrecy::{ExposeSecret, Secret};
-use color_eyre::eyre::{Context, Result};
 
 use sqlx::PgPool;
+use uuid::Uuid;
 
-use crate::domain::{
-    data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+use crate::{
+    app_state::PasswordHasherType,
+    domain::{
+        data_stores::{UserStore, UserStoreError},
+        DisplayName, Email, HashedPassword, Password, Role, User, UserId,
+    },
 };
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    password_hasher: PasswordHasherType,
 }
 
 impl PostgresUserStore {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, password_hasher: PasswordHasherType) -> Self {
+        Self { pool, password_hasher }
     }
-}
 
-#[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(expected_password_hash: Secret<String>, // Updated!
-    password_candidate: Secret<String>) -> Result<()> {
-    let current_span: tracing::Span = tracing::Span::current();
-    let result = tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let expected_password_hash: PasswordHash<'_> =
-                PasswordHash::new(expected_password_hash.expose_secret())?;
-
-            Argon2::default()
-                .verify_password(
-                    password_candidate.expose_secret().as_bytes(), // Updated!
-                    &expected_password_hash,
-                )
-                .wrap_err("failed to verify password hash")
-        })
-    })
-    .await;
+    async fn insert_user(&self, user: &User) -> Result<(), UserStoreError> {
+        let failed_login_attempts = i32::try_from(user.failed_login_attempts)
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
-    result?
-}
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, email_canonical, password_hash, requires_2fa, role,
+                               display_name, created_at, updated_at, last_login_at,
+                               failed_login_attempts, locked_until)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            "#,
+            user.id.as_ref(),
+            &user.email.as_ref().expose_secret(),
+            &user.email.canonical().expose_secret(),
+            user.password_hash.as_ref().map(|hash| hash.as_ref().expose_secret()),
+            user.requires_2fa,
+            user.role.as_ref(),
+            user.display_name.as_ref().map(|name| name.as_ref()),
+            user.created_at,
+            user.updated_at,
+            user.last_login_at,
+            failed_login_attempts,
+            user.locked_until
+        )
+        .execute(&self.pool)
+        .await
+        // The unique indexes make the insert itself the duplicate check, so concurrent signups can't both win
+        .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+            Some(code) if code == UNIQUE_VIOLATION => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
 
-#[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> { // Updated!
-    let current_span: tracing::Span = tracing::Span::current();
-
-    let result = tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
-            let password_hash = Argon2::new(
-                Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(15000, 2, 1, None)?,
-            )
-            .hash_password(password.expose_secret().as_bytes(), &salt)? // Updated!
-            .to_string();
-
-            Ok(Secret::new(password_hash)) // Updated!
-        })
-    })
-    .await;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Rehashing password in PostgreSQL", skip_all)]
+    async fn rehash_password(&self, email: &Email, password: &Password) -> Result<HashedPassword, UserStoreError> {
+        let password_hash = self.password_hasher
+            .hash_password(password)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE email_canonical = $2
+            "#,
+            password_hash.as_ref().expose_secret(),
+            email.canonical().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
-    result?
+        Ok(password_hash)
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
-#[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
-async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-    let password_hash = compute_password_hash(user.password.as_ref().to_owned())
+    #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
+    async fn add_user(&self, mut user: User, password: Option<Password>) -> Result<(), UserStoreError> {
+        user.password_hash = match password {
+            Some(password) => Some(
+                self.password_hasher
+                    .hash_password(&password)
+                    .await
+                    .map_err(UserStoreError::UnexpectedError)?,
+            ),
+            None => None,
+        };
+
+        self.insert_user(&user).await
+    }
+
+    #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, requires_2fa, role, display_name, created_at, updated_at, last_login_at,
+                   failed_login_attempts, locked_until
+            FROM users
+            WHERE email_canonical = $1
+            "#,
+            email.canonical().expose_secret()
+        )
+        .fetch_optional(&self.pool)
         .await
-        .map_err(UserStoreError::UnexpectedError)?;
-
-    sqlx::query!(
-        r#"
-        INSERT INTO users (email, password_hash, requires_2fa)
-        VALUES ($1, $2, $3)
-        "#,
-        &user.email.as_ref().expose_secret(),
-        &password_hash.expose_secret(), // Updated!
-        user.requires_2fa
-    )
-    .execute(&self.pool)
-    .await
-    .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
-
-    Ok(())
-}
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
 
-#[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
-async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-    sqlx::query!(
-        r#"
-        SELECT email, password_hash, requires_2fa
-        FROM users
-        WHERE email = $1
-        "#,
-        email.as_ref().expose_secret()
-    )
-    .fetch_optional(&self.pool)
-    .await
-    .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
-    .map(|row| {
-        Ok(User {
-            email: Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)?,
-            password: Password::parse(Secret::new(row.password_hash)) // Updated!
-                .map_err(UserStoreError::UnexpectedError)?, // Updated!
-            requires_2fa: row.requires_2fa,
-        })
-    })
-    .ok_or(UserStoreError::UserNotFound)?
-}
-#[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
+    #[tracing::instrument(name = "Retrieving user by id from PostgreSQL", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, requires_2fa, role, display_name, created_at, updated_at, last_login_at,
+                   failed_login_attempts, locked_until
+            FROM users
+            WHERE id = $1
+            "#,
+            id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Listing users in PostgreSQL", skip_all)]
+    async fn list_users(&self, after: Option<&UserId>, limit: usize) -> Result<Vec<User>, UserStoreError> {
+        let limit = i64::try_from(limit).map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, requires_2fa, role, display_name, created_at, updated_at, last_login_at,
+                   failed_login_attempts, locked_until
+            FROM users
+            WHERE $1::uuid IS NULL OR id > $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+            after.map(|id| *id.as_ref()),
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(User::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn validate_user(
         &self,
         email: &Email,
-        password: &Password) -> Result<(), UserStoreError> {
-        let user = self.get_user(email).await?;
+        password: &Password) -> Result<User, UserStoreError> {
+        // Unknown emails fail like wrong passwords, so callers can't tell them apart
+        let mut user = match self.get_user(email).await {
+            Ok(user) => user,
+            Err(UserStoreError::UserNotFound) => return Err(UserStoreError::InvalidCredentials),
+            Err(e) => return Err(e),
+        };
+
+        if user.is_locked(Utc::now()) {
+            return Err(UserStoreError::UserLocked);
+        }
+
+        let password_hash = match &user.password_hash {
+            Some(password_hash) => password_hash,
+            None => return Err(UserStoreError::InvalidCredentials),
+        };
+
+        self.password_hasher
+            .verify_password(password_hash, password)
+            .await
+        .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+        // A failed upgrade must not fail the login, the old hash still works
+        if self.password_hasher.needs_rehash(password_hash) {
+            match self.rehash_password(email, password).await {
+                Ok(password_hash) => user.password_hash = Some(password_hash),
+                Err(e) => tracing::warn!("failed to rehash password: {:?}", e),
+            }
+        }
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(name = "Importing user into PostgreSQL", skip_all)]
+    async fn import_user(&self, mut user: User, password_hash: HashedPassword) -> Result<(), UserStoreError> {
+        user.password_hash = Some(password_hash);
+        self.insert_user(&user).await
+    }
+
+    #[tracing::instrument(name = "Updating display name in PostgreSQL", skip_all)]
+    async fn update_display_name(&self,
+                                 email: &Email,
+                                 display_name: Option<DisplayName>) -> Result<User, UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET display_name = $1, updated_at = NOW()
+            WHERE email_canonical = $2
+            "#,
+            display_name.as_ref().map(|name| name.as_ref()),
+            email.canonical().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user(email).await
+    }
+
+    #[tracing::instrument(name = "Updating email in PostgreSQL", skip_all)]
+    async fn update_email(&self, id: &UserId, email: Email) -> Result<User, UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET email = $1, email_canonical = $2, updated_at = NOW()
+            WHERE id = $3
+            "#,
+            email.as_ref().expose_secret(),
+            email.canonical().expose_secret(),
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+            Some(code) if code == UNIQUE_VIOLATION => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
 
-        verify_password_hash(
-            user.password.as_ref().to_owned(),
-            password.as_ref().to_owned(),
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    #[tracing::instrument(name = "Recording login in PostgreSQL", skip_all)]
+    async fn record_login(&self, email: &Email, at: DateTime<Utc>) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET last_login_at = $1, failed_login_attempts = 0
+            WHERE email_canonical = $2
+            "#,
+            at,
+            email.canonical().expose_secret()
         )
+        .execute(&self.pool)
         .await
-        .map_err(|_| UserStoreError::InvalidCredentials)
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording failed login in PostgreSQL", skip_all)]
+    async fn record_failed_login(&self,
+                                 email: &Email,
+                                 max_attempts: u32,
+                                 locked_until: DateTime<Utc>) -> Result<(), UserStoreError> {
+        let max_attempts = i32::try_from(max_attempts).map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        // Counting and locking in one statement keeps concurrent failures from being lost
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET failed_login_attempts = CASE WHEN failed_login_attempts + 1 >= $1 THEN 0
+                                             ELSE failed_login_attempts + 1 END,
+                locked_until = CASE WHEN failed_login_attempts + 1 >= $1 THEN $2
+                                    ELSE locked_until END
+            WHERE email_canonical = $3
+            "#,
+            max_attempts,
+            locked_until,
+            email.canonical().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Unlocking user in PostgreSQL", skip_all)]
+    async fn unlock_user(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET failed_login_attempts = 0, locked_until = NULL, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    #[tracing::instrument(name = "Updating 2FA requirement in PostgreSQL", skip_all)]
+    async fn update_requires_2fa(&self, id: &UserId, requires_2fa: bool) -> Result<User, UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET requires_2fa = $1, updated_at = NOW()
+            WHERE id = $2
+            "#,
+            requires_2fa,
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    #[tracing::instrument(name = "Clearing password in PostgreSQL", skip_all)]
+    async fn clear_password(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = NULL, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    #[tracing::instrument(name = "Setting password in PostgreSQL", skip_all)]
+    async fn set_password(&self, id: &UserId, password: Password) -> Result<User, UserStoreError> {
+        let password_hash = self.password_hasher
+            .hash_password(&password)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1, failed_login_attempts = 0, locked_until = NULL, updated_at = NOW()
+            WHERE id = $2
+            "#,
+            password_hash.as_ref().expose_secret(),
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+}
+
+struct UserRow {
+    id: Uuid,
+    email: String,
+    password_hash: Option<String>,
+    requires_2fa: bool,
+    role: String,
+    display_name: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    last_login_at: Option<DateTime<Utc>>,
+    failed_login_attempts: i32,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: row.id.into(),
+            email: Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)?,
+            password_hash: row.password_hash
+                .map(|hash| HashedPassword::parse(Secret::new(hash)))
+                .transpose()
+                .map_err(UserStoreError::UnexpectedError)?,
+            requires_2fa: row.requires_2fa,
+            role: Role::parse(&row.role).map_err(UserStoreError::UnexpectedError)?,
+            display_name: row.display_name
+                .map(|name| DisplayName::parse(&name))
+                .transpose()
+                .map_err(UserStoreError::UnexpectedError)?,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            last_login_at: row.last_login_at,
+            failed_login_attempts: u32::try_from(row.failed_login_attempts)
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?,
+            locked_until: row.locked_until,
+        })
     }
 }
 
+// SQLSTATE raised by PostgreSQL when a UNIQUE constraint is violated
+const UNIQUE_VIOLATION: &str = "23505";
@@ -1,21 +1,19 @@
-use std::sync::Arc;
-
-use redis::{Commands, Connection};
-use tokio::sync::RwLock;
-use color_eyre::eyre::Context;
+use chrono::{DateTime, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use color_eyre::eyre::{eyre, Context};
 use secrecy::{Secret, ExposeSecret};
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
-    utils::constants::TTL_SECONDS_I64,
+    domain::{data_stores::{BannedTokenStore, BannedTokenStoreError}, UserId},
+    utils::constants::{REVOKED_USER_TOKENS_TTL_SECONDS_U64, TTL_SECONDS_I64},
 };
 
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self { conn }
     }
 }
@@ -24,7 +22,7 @@ impl RedisBannedTokenStore {
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "add_banned_token", skip_all)]
-    async fn add_banned_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+    async fn add_banned_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
         let token_key = get_key(token.expose_secret());
         let ttl: u64 = TTL_SECONDS_I64
         .try_into()
@@ -33,9 +31,9 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
     let _: () = self
         .conn
-        .write()
-        .await
+        .clone()
         .set_ex(&token_key, 0, ttl)
+        .await
         .wrap_err("failed to set banned token in Redis") // New!
         .map_err(BannedTokenStoreError::UnexpectedError)?;
         Ok(())
@@ -46,14 +44,66 @@ impl BannedTokenStore for RedisBannedTokenStore {
         let token_key = get_key(token.expose_secret());
         let is_banned: bool = self
             .conn
-            .write()
-            .await
+            .clone()
             .exists(&token_key)
+            .await
             .wrap_err("failed to check if token exists in Redis") // New!
             .map_err(BannedTokenStoreError::UnexpectedError)?; // Updated!
 
         Ok(is_banned)
     }
+
+    #[tracing::instrument(name = "revoke_user_tokens", skip_all)]
+    async fn revoke_user_tokens(&self, user_id: &UserId, at: DateTime<Utc>) -> Result<(), BannedTokenStoreError> {
+        let key = get_revoked_user_tokens_key(user_id);
+        let mut conn = self.conn.clone();
+
+        // Never moves the revocation back in time
+        let revoked_at: Option<i64> = conn
+            .get(&key)
+            .await
+            .wrap_err("failed to get revoked user tokens from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+        let revoked_at = revoked_at.map_or(at.timestamp(), |revoked_at| revoked_at.max(at.timestamp()));
+
+        let _: () = conn
+            .set_ex(&key, revoked_at, REVOKED_USER_TOKENS_TTL_SECONDS_U64)
+            .await
+            .wrap_err("failed to set revoked user tokens in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "is_revoked_user_token", skip_all)]
+    async fn is_revoked_user_token(&self,
+                                   user_id: &UserId,
+                                   issued_at: DateTime<Utc>) -> Result<bool, BannedTokenStoreError> {
+        let revoked_at: Option<i64> = self
+            .conn
+            .clone()
+            .get(get_revoked_user_tokens_key(user_id))
+            .await
+            .wrap_err("failed to get revoked user tokens from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(revoked_at.is_some_and(|revoked_at| issued_at.timestamp() <= revoked_at))
+    }
+
+    #[tracing::instrument(name = "user_tokens_revoked_at", skip_all)]
+    async fn user_tokens_revoked_at(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>, BannedTokenStoreError> {
+        let revoked_at: Option<i64> = self
+            .conn
+            .clone()
+            .get(get_revoked_user_tokens_key(user_id))
+            .await
+            .wrap_err("failed to get revoked user tokens from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        revoked_at
+            .map(|revoked_at| DateTime::from_timestamp(revoked_at, 0)
+                .ok_or_else(|| BannedTokenStoreError::UnexpectedError(eyre!("Invalid revocation time"))))
+            .transpose()
+    }
 }
 
 // We are using a key prefix to prevent collisions and organize data!
@@ -61,4 +111,10 @@ const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
 
 fn get_key(token: &str) -> String {
     format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
-}
\ No newline at end of file
+}
+
+const REVOKED_USER_TOKENS_KEY_PREFIX: &str = "revoked_user_tokens:";
+
+fn get_revoked_user_tokens_key(user_id: &UserId) -> String {
+    format!("{}{}", REVOKED_USER_TOKENS_KEY_PREFIX, user_id)
+}
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use color_eyre::eyre::{Context, Result};
+
+use crate::domain::{DeviceCode, DeviceGrant, DeviceGrantStatus, DeviceGrantStore,
+                    DeviceGrantStoreError, UserCode, UserId};
+
+pub struct RedisDeviceGrantStore {
+    conn: ConnectionManager,
+    decide_script: Script,
+    poll_script: Script,
+}
+
+impl RedisDeviceGrantStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self {
+            conn,
+            decide_script: Script::new(DECIDE_SCRIPT),
+            poll_script: Script::new(POLL_SCRIPT),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum StoredDeviceGrantStatus {
+    Pending,
+    Approved(String),
+    Denied,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredDeviceGrant {
+    device_code: String,
+    user_code: String,
+    status: StoredDeviceGrantStatus,
+    expires_at: DateTime<Utc>,
+    last_polled_at: Option<DateTime<Utc>>,
+}
+
+impl From<&DeviceGrantStatus> for StoredDeviceGrantStatus {
+    fn from(status: &DeviceGrantStatus) -> Self {
+        match status {
+            DeviceGrantStatus::Pending => Self::Pending,
+            DeviceGrantStatus::Approved(user_id) => Self::Approved(user_id.to_string()),
+            DeviceGrantStatus::Denied => Self::Denied,
+        }
+    }
+}
+
+impl From<&DeviceGrant> for StoredDeviceGrant {
+    fn from(device_grant: &DeviceGrant) -> Self {
+        Self {
+            device_code: device_grant.device_code.as_ref().expose_secret().to_owned(),
+            user_code: device_grant.user_code.as_ref().to_owned(),
+            status: StoredDeviceGrantStatus::from(&device_grant.status),
+            expires_at: device_grant.expires_at,
+            last_polled_at: device_grant.last_polled_at,
+        }
+    }
+}
+
+impl TryFrom<StoredDeviceGrant> for DeviceGrant {
+    type Error = color_eyre::eyre::Report;
+
+    fn try_from(stored: StoredDeviceGrant) -> Result<Self> {
+        Ok(Self {
+            device_code: DeviceCode::parse(Secret::new(stored.device_code))?,
+            user_code: UserCode::parse(&stored.user_code)?,
+            status: match stored.status {
+                StoredDeviceGrantStatus::Pending => DeviceGrantStatus::Pending,
+                StoredDeviceGrantStatus::Approved(user_id) => DeviceGrantStatus::Approved(UserId::parse(&user_id)?),
+                StoredDeviceGrantStatus::Denied => DeviceGrantStatus::Denied,
+            },
+            expires_at: stored.expires_at,
+            last_polled_at: stored.last_polled_at,
+        })
+    }
+}
+
+const DEVICE_GRANT_PREFIX: &str = "device_grant:";
+const DEVICE_USER_CODE_PREFIX: &str = "device_user_code:";
+
+// Sets the status of the grant of a user code, if it's still pending. Returns whether it was.
+// KEYS: the user code. ARGV: the prefix of grant keys, the serialized status.
+const DECIDE_SCRIPT: &str = r#"
+local device_code = redis.call('GET', KEYS[1])
+if not device_code then
+    return 0
+end
+local key = ARGV[1] .. device_code
+local value = redis.call('GET', key)
+if not value then
+    return 0
+end
+local grant = cjson.decode(value)
+if grant.status ~= 'Pending' then
+    return 0
+end
+grant.status = cjson.decode(ARGV[2])
+redis.call('SET', key, cjson.encode(grant), 'KEEPTTL')
+return 1
+"#;
+
+// Returns the grant as it was, records the poll of a pending grant and drops a decided one with its user code.
+// KEYS: the grant. ARGV: the prefix of user code keys, the serialized time of the poll.
+const POLL_SCRIPT: &str = r#"
+local value = redis.call('GET', KEYS[1])
+if not value then
+    return false
+end
+local grant = cjson.decode(value)
+if grant.status == 'Pending' then
+    grant.last_polled_at = cjson.decode(ARGV[2])
+    redis.call('SET', KEYS[1], cjson.encode(grant), 'KEEPTTL')
+else
+    redis.call('DEL', KEYS[1], ARGV[1] .. grant.user_code)
+end
+return value
+"#;
+
+fn get_key(device_code: &DeviceCode) -> String {
+    format!("{}{}", DEVICE_GRANT_PREFIX, device_code.as_ref().expose_secret())
+}
+
+fn get_user_code_key(user_code: &UserCode) -> String {
+    format!("{}{}", DEVICE_USER_CODE_PREFIX, user_code.as_ref())
+}
+
+// Entries expire together with the grant itself
+fn get_ttl(device_grant: &DeviceGrant) -> Result<u64, DeviceGrantStoreError> {
+    let ttl = (device_grant.expires_at - Utc::now()).num_seconds();
+    if ttl <= 0 {
+        return Err(DeviceGrantStoreError::DeviceGrantNotFound);
+    }
+    Ok(ttl as u64)
+}
+
+fn serialize<T: Serialize>(value: &T) -> Result<String, DeviceGrantStoreError> {
+    serde_json::to_string(value)
+        .wrap_err("failed to serialize device grant")
+        .map_err(DeviceGrantStoreError::UnexpectedError)
+}
+
+#[async_trait::async_trait]
+impl DeviceGrantStore for RedisDeviceGrantStore {
+    #[tracing::instrument(name = "add_device_grant", skip_all)]
+    async fn add_device_grant(&self, device_grant: DeviceGrant) -> Result<(), DeviceGrantStoreError> {
+        let ttl = get_ttl(&device_grant)?;
+        let serialized_data = serialize(&StoredDeviceGrant::from(&device_grant))?;
+        let mut conn = self.conn.clone();
+
+        // The user code is claimed first, a grant never replaces another one holding the same code
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(get_user_code_key(&device_grant.user_code))
+            .arg(device_grant.device_code.as_ref().expose_secret())
+            .arg("EX")
+            .arg(ttl)
+            .arg("NX")
+            .query_async(&mut conn)
+            .await
+            .wrap_err("failed to set device user code in redis")
+            .map_err(DeviceGrantStoreError::UnexpectedError)?;
+        if claimed.is_none() {
+            return Err(DeviceGrantStoreError::UserCodeAlreadyExists);
+        }
+
+        let _: () = conn
+            .set_ex(get_key(&device_grant.device_code), serialized_data, ttl)
+            .await
+            .wrap_err("failed to set device grant in redis")
+            .map_err(DeviceGrantStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "decide_device_grant", skip_all)]
+    async fn decide_device_grant(&self,
+                                 user_code: &UserCode,
+                                 status: DeviceGrantStatus) -> Result<(), DeviceGrantStoreError> {
+        let decided: bool = self.decide_script
+            .key(get_user_code_key(user_code))
+            .arg(DEVICE_GRANT_PREFIX)
+            .arg(serialize(&StoredDeviceGrantStatus::from(&status))?)
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .wrap_err("failed to decide device grant in redis")
+            .map_err(DeviceGrantStoreError::UnexpectedError)?;
+
+        match decided {
+            true => Ok(()),
+            false => Err(DeviceGrantStoreError::DeviceGrantNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "poll_device_grant", skip_all)]
+    async fn poll_device_grant(&self,
+                               device_code: &DeviceCode,
+                               now: DateTime<Utc>) -> Result<DeviceGrant, DeviceGrantStoreError> {
+        let value: Option<String> = self.poll_script
+            .key(get_key(device_code))
+            .arg(DEVICE_USER_CODE_PREFIX)
+            .arg(serialize(&now)?)
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .wrap_err("failed to poll device grant in redis")
+            .map_err(DeviceGrantStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(DeviceGrantStoreError::DeviceGrantNotFound)?;
+        let stored: StoredDeviceGrant = serde_json::from_str(&value)
+                .wrap_err("failed to deserialize device grant")
+                .map_err(DeviceGrantStoreError::UnexpectedError)?;
+
+        DeviceGrant::try_from(stored)
+            .wrap_err("failed to parse device grant")
+            .map_err(DeviceGrantStoreError::UnexpectedError)
+    }
+}
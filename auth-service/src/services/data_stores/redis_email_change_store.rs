@@ -0,0 +1,145 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use color_eyre::eyre::Context;
+
+use crate::domain::{Email, EmailChange, EmailChangeStore, EmailChangeStoreError, TwoFACode, TwoFACodeHash, UserId};
+use crate::utils::constants::{EMAIL_CHANGE_TTL_SECONDS_I64, EMAIL_CHANGE_TTL_SECONDS_U64};
+
+pub struct RedisEmailChangeStore {
+    conn: ConnectionManager,
+    code_secret: Secret<String>,
+}
+
+impl RedisEmailChangeStore {
+    pub fn new(conn: ConnectionManager, code_secret: Secret<String>) -> Self {
+        Self { conn, code_secret }
+    }
+
+    // The pending change and the hash of its code
+    async fn get_pending(&self, user_id: &UserId) -> Result<(EmailChange, TwoFACodeHash), EmailChangeStoreError> {
+        let key = get_key(user_id);
+
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(&key)
+            .await
+            .wrap_err("failed to get email change from redis")
+            .map_err(EmailChangeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(EmailChangeStoreError::EmailChangeNotFound)?;
+        let data: EmailChangeTuple = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize email change tuple")
+            .map_err(EmailChangeStoreError::UnexpectedError)?;
+
+        let new_email = Email::parse(Secret::new(data.0))
+            .wrap_err("failed to parse new email")
+            .map_err(EmailChangeStoreError::UnexpectedError)?;
+        let code_hash = TwoFACodeHash::parse(Secret::new(data.1))
+            .wrap_err("failed to parse email change code hash")
+            .map_err(EmailChangeStoreError::UnexpectedError)?;
+        Ok((EmailChange::new(*user_id, new_email), code_hash))
+    }
+}
+
+// The new email as the user entered it, and the hash of the confirmation code
+#[derive(Serialize, Deserialize)]
+struct EmailChangeTuple(pub String, pub String);
+
+const EMAIL_CHANGE_PREFIX: &str = "email_change:";
+// Count of wrong codes of a change, it expires with the change
+const EMAIL_CHANGE_ATTEMPTS_PREFIX: &str = "email_change_attempts:";
+
+fn get_key(user_id: &UserId) -> String {
+    format!("{}{}", EMAIL_CHANGE_PREFIX, user_id)
+}
+
+fn get_attempts_key(user_id: &UserId) -> String {
+    format!("{}{}", EMAIL_CHANGE_ATTEMPTS_PREFIX, user_id)
+}
+
+#[async_trait::async_trait]
+impl EmailChangeStore for RedisEmailChangeStore {
+    #[tracing::instrument(name = "add_email_change", skip_all)]
+    async fn add_email_change(&self, email_change: EmailChange, code: &TwoFACode) -> Result<(), EmailChangeStoreError> {
+        let key = get_key(&email_change.user_id);
+
+        let code_hash = TwoFACodeHash::for_email_change(&email_change.user_id, code, &self.code_secret);
+        let data = EmailChangeTuple(
+            email_change.new_email.as_ref().expose_secret().to_owned(),
+            code_hash.as_ref().expose_secret().to_owned(),
+        );
+        let serialized_data = serde_json::to_string(&data)
+            .wrap_err("failed to serialize email change tuple")
+            .map_err(EmailChangeStoreError::UnexpectedError)?;
+
+        // A new change starts with a new code, so the wrong codes of the previous one are forgotten
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(&key, serialized_data, EMAIL_CHANGE_TTL_SECONDS_U64).ignore()
+            .del(get_attempts_key(&email_change.user_id)).ignore()
+            .query_async(&mut self.conn.clone())
+            .await
+            .wrap_err("failed to set email change in redis")
+            .map_err(EmailChangeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "get_email_change", skip_all)]
+    async fn get_email_change(&self, user_id: &UserId) -> Result<EmailChange, EmailChangeStoreError> {
+        Ok(self.get_pending(user_id).await?.0)
+    }
+
+    #[tracing::instrument(name = "verify_email_change_code", skip_all)]
+    async fn verify_email_change_code(&self, user_id: &UserId, code: &TwoFACode) -> Result<bool, EmailChangeStoreError> {
+        let (_, code_hash) = self.get_pending(user_id).await?;
+        Ok(code_hash.matches_email_change(user_id, code, &self.code_secret))
+    }
+
+    #[tracing::instrument(name = "record_failed_attempt", skip_all)]
+    async fn record_failed_attempt(&self, user_id: &UserId, max_attempts: u32) -> Result<bool, EmailChangeStoreError> {
+        let key = get_key(user_id);
+        let attempts_key = get_attempts_key(user_id);
+
+        // The count lives no longer than the change it belongs to, a new change resets it
+        let (failed_attempts, exists): (u32, bool) = redis::pipe()
+            .atomic()
+            .incr(&attempts_key, 1)
+            .expire(&attempts_key, EMAIL_CHANGE_TTL_SECONDS_I64).ignore()
+            .exists(&key)
+            .query_async(&mut self.conn.clone())
+            .await
+            .wrap_err("failed to count failed email change attempt in redis")
+            .map_err(EmailChangeStoreError::UnexpectedError)?;
+        if !exists {
+            return Err(EmailChangeStoreError::EmailChangeNotFound);
+        }
+
+        if failed_attempts >= max_attempts {
+            self.delete_email_change(user_id).await?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    #[tracing::instrument(name = "delete_email_change", skip_all)]
+    async fn delete_email_change(&self, user_id: &UserId) -> Result<(), EmailChangeStoreError> {
+        let key = get_key(user_id);
+
+        let (deleted, _): (u64, u64) = redis::pipe()
+            .atomic()
+            .del(&key)
+            .del(get_attempts_key(user_id))
+            .query_async(&mut self.conn.clone())
+            .await
+            .wrap_err("failed to delete email change from redis")
+            .map_err(EmailChangeStoreError::UnexpectedError)?;
+
+        match deleted {
+            0 => Err(EmailChangeStoreError::EmailChangeNotFound),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use secrecy::ExposeSecret;
+use color_eyre::eyre::Context;
+
+use crate::domain::{MagicLinkId, MagicLinkStore, MagicLinkStoreError, UserId};
+use crate::utils::constants::MAGIC_LINK_TTL_SECONDS_U64;
+
+pub struct RedisMagicLinkStore {
+    conn: ConnectionManager,
+}
+
+impl RedisMagicLinkStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+const MAGIC_LINK_PREFIX: &str = "magic_link:";
+
+fn get_key(magic_link_id: &MagicLinkId) -> String {
+    format!("{}{}", MAGIC_LINK_PREFIX, magic_link_id.as_ref().expose_secret())
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for RedisMagicLinkStore {
+    #[tracing::instrument(name = "add_magic_link", skip_all)]
+    async fn add_magic_link(&self, user_id: &UserId, magic_link_id: MagicLinkId) ->
+        Result<(), MagicLinkStoreError> {
+        let key = get_key(&magic_link_id);
+
+        let _: () = self
+                .conn
+                .clone()
+                .set_ex(&key, user_id.to_string(), MAGIC_LINK_TTL_SECONDS_U64)
+                .await
+                .wrap_err("failed to set magic link in redis")
+                .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "take_magic_link", skip_all)]
+    async fn take_magic_link(&self, magic_link_id: &MagicLinkId) ->
+        Result<UserId, MagicLinkStoreError> {
+        let key = get_key(magic_link_id);
+
+        // GETDEL reads and removes the link atomically, so it can be used only once
+        let value: Option<String> = self
+                .conn
+                .clone()
+                .get_del(&key)
+                .await
+                .wrap_err("failed to take magic link from redis")
+                .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        match value {
+            Some(user_id) => UserId::parse(&user_id)
+                .wrap_err("failed to parse user id of magic link")
+                .map_err(MagicLinkStoreError::UnexpectedError),
+            None => Err(MagicLinkStoreError::MagicLinkNotFound),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use secrecy::ExposeSecret;
+use color_eyre::eyre::Context;
+
+use crate::domain::{PasswordResetStore, PasswordResetStoreError, PasswordResetToken, UserId};
+use crate::utils::constants::PASSWORD_RESET_TTL_SECONDS_U64;
+
+pub struct RedisPasswordResetStore {
+    conn: ConnectionManager,
+}
+
+impl RedisPasswordResetStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+const PASSWORD_RESET_PREFIX: &str = "password_reset:";
+
+fn get_key(token: &PasswordResetToken) -> String {
+    format!("{}{}", PASSWORD_RESET_PREFIX, token.as_ref().expose_secret())
+}
+
+#[async_trait::async_trait]
+impl PasswordResetStore for RedisPasswordResetStore {
+    #[tracing::instrument(name = "add_password_reset", skip_all)]
+    async fn add_password_reset(&self, user_id: &UserId, token: PasswordResetToken) ->
+        Result<(), PasswordResetStoreError> {
+        let key = get_key(&token);
+
+        let _: () = self
+                .conn
+                .clone()
+                .set_ex(&key, user_id.to_string(), PASSWORD_RESET_TTL_SECONDS_U64)
+                .await
+                .wrap_err("failed to set password reset in redis")
+                .map_err(PasswordResetStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "take_password_reset", skip_all)]
+    async fn take_password_reset(&self, token: &PasswordResetToken) ->
+        Result<UserId, PasswordResetStoreError> {
+        let key = get_key(token);
+
+        // GETDEL reads and removes the reset atomically, so its token can be used only once
+        let value: Option<String> = self
+                .conn
+                .clone()
+                .get_del(&key)
+                .await
+                .wrap_err("failed to take password reset from redis")
+                .map_err(PasswordResetStoreError::UnexpectedError)?;
+
+        match value {
+            Some(user_id) => UserId::parse(&user_id)
+                .wrap_err("failed to parse user id of password reset")
+                .map_err(PasswordResetStoreError::UnexpectedError),
+            None => Err(PasswordResetStoreError::PasswordResetNotFound),
+        }
+    }
+}
@@ -1,98 +1,209 @@
-use std::sync::Arc;
+use std::num::NonZeroUsize;
 
-use redis::{Commands, Connection};
+use chrono::Utc;
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
 use color_eyre::eyre::Context;
 use secrecy::Secret;
 
-use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeHash, TwoFACodeStore, TwoFACodeStoreError};
 use crate::utils::constants::TTL_SECONDS_U64;
 
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
+    max_pending: NonZeroUsize,
+    code_secret: Secret<String>,
+    add_script: Script,
+    consume_script: Script,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(conn: ConnectionManager, max_pending: NonZeroUsize, code_secret: Secret<String>) -> Self {
+        Self {
+            conn,
+            max_pending,
+            code_secret,
+            add_script: Script::new(ADD_SCRIPT),
+            consume_script: Script::new(CONSUME_SCRIPT),
+        }
     }
-}
 
+    // The code of a login attempt, if it's still pending and was sent to `email`
+    async fn get_code_hash(&self,
+                           email: &Email,
+                           login_attempt_id: &LoginAttemptId) -> Result<Option<TwoFACodeHash>, TwoFACodeStoreError> {
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(get_key(login_attempt_id))
+            .await
+            .wrap_err("failed to get 2FA code from Redis")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let data: TwoFACodeTuple = match value {
+            Some(value) => serde_json::from_str(&value)
+                .wrap_err("failed to deserialize 2FA code tuple")
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+            None => return Ok(None),
+        };
+        if &data.0 != email.canonical().expose_secret() {
+            return Ok(None);
+        }
+
+        TwoFACodeHash::parse(Secret::new(data.1))
+            .wrap_err("failed to parse 2FA code hash")
+            .map(Some)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+}
 
+// The canonical email the code was sent to, and the code hash
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFACodeTuple(pub String, pub String);
 
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+// Sorted set of the pending login attempts of an email, scored by when they were made
+const TWO_FA_ATTEMPTS_PREFIX: &str = "two_fa_attempts:";
+
+// Adds the code and drops the attempts of the email over the cap in one step, so that concurrent logins
+// can't both see room under the cap.
+// KEYS: the code, the attempts of the email. ARGV: the tuple, the TTL in seconds, the login attempt id,
+// the time in milliseconds, the cap, the prefix of code keys.
+const ADD_SCRIPT: &str = r#"
+local ttl = tonumber(ARGV[2])
+local now = tonumber(ARGV[4])
+redis.call('SET', KEYS[1], ARGV[1], 'EX', ttl)
+redis.call('ZADD', KEYS[2], now, ARGV[3])
+redis.call('ZREMRANGEBYSCORE', KEYS[2], '-inf', now - ttl * 1000)
+redis.call('EXPIRE', KEYS[2], ttl)
+local evicted = redis.call('ZRANGE', KEYS[2], 0, -tonumber(ARGV[5]) - 1)
+for _, id in ipairs(evicted) do
+    redis.call('DEL', ARGV[6] .. id)
+    redis.call('ZREM', KEYS[2], id)
+end
+return #evicted
+"#;
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref().expose_secret())
+// Takes the code hash of a login attempt and drops the attempt, if it's pending for the email.
+// KEYS: the code, the attempts of the email. ARGV: the canonical email, the login attempt id.
+const CONSUME_SCRIPT: &str = r#"
+local value = redis.call('GET', KEYS[1])
+if not value then
+    return false
+end
+local data = cjson.decode(value)
+if data[1] ~= ARGV[1] then
+    return false
+end
+redis.call('DEL', KEYS[1])
+redis.call('ZREM', KEYS[2], ARGV[2])
+return data[2]
+"#;
+
+fn get_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, login_attempt_id.as_ref().expose_secret())
+}
+
+fn get_attempts_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_ATTEMPTS_PREFIX, email.canonical().expose_secret())
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for RedisTwoFACodeStore {
     #[tracing::instrument(name = "add_two_fa_code", skip_all)]
-    async fn add_two_fa_code(&mut self, email: &Email, login_attempt_id: LoginAttemptId, code: TwoFACode) -> 
+    async fn add_two_fa_code(&self, email: &Email, login_attempt_id: LoginAttemptId, code: TwoFACode) ->
         Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
+        let key = get_key(&login_attempt_id);
+        let attempts_key = get_attempts_key(email);
+        let now = Utc::now().timestamp_millis();
 
-        let data = TwoFATuple(
-            login_attempt_id.as_ref().expose_secret().to_owned(),
-            code.as_ref().expose_secret().to_owned(),
+        // Only the keyed hash is stored, reading Redis isn't enough to complete a login
+        let code_hash = TwoFACodeHash::new(&login_attempt_id, &code, &self.code_secret);
+        let data = TwoFACodeTuple(
+            email.canonical().expose_secret().to_owned(),
+            code_hash.as_ref().expose_secret().to_owned(),
         );
         let serialized_data = serde_json::to_string(&data)
-                .wrap_err("failed to serialize 2FA tuple")
-                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-    
-        let _: () = self
-                .conn
-                .write()
-                .await
-                .set_ex(&key, serialized_data, TTL_SECONDS_U64)
-                .wrap_err("failed to set 2FA code in redis")
-                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-    
-            Ok(())
+            .wrap_err("failed to serialize 2FA code tuple")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: usize = self.add_script
+            .key(&key)
+            .key(&attempts_key)
+            .arg(serialized_data)
+            .arg(TTL_SECONDS_U64)
+            .arg(login_attempt_id.as_ref().expose_secret())
+            .arg(now)
+            .arg(self.max_pending.get())
+            .arg(TWO_FA_CODE_PREFIX)
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .wrap_err("failed to set 2FA code in redis")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
     }
 
     #[tracing::instrument(name = "delete_two_fa_code", skip_all)]
-    async fn delete_two_fa_code(&mut self, email: &Email) -> 
+    async fn delete_two_fa_code(&self, email: &Email, login_attempt_id: &LoginAttemptId) ->
         Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
-        let _: () = self
-                        .conn
-                        .write()
-                        .await
-                        .del(&key)
-                        .wrap_err("failed to delete 2FA code from Redis")
-                        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        if self.get_code_hash(email, login_attempt_id).await?.is_none() {
+            return Ok(());
+        }
+
+        let _: () = redis::pipe()
+            .atomic()
+            .del(get_key(login_attempt_id)).ignore()
+            .zrem(get_attempts_key(email), login_attempt_id.as_ref().expose_secret()).ignore()
+            .query_async(&mut self.conn.clone())
+            .await
+            .wrap_err("failed to delete 2FA code from Redis")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
         Ok(())
     }
 
-    #[tracing::instrument(name = "get_two_fa_code", skip_all)]
-    async fn get_two_fa_code(&self, email: &Email) -> 
-        Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-            let key = get_key(email);
-
-            match self.conn.write().await.get::<_, String>(&key) {
-                Ok(value) => {
-                    let data: TwoFATuple = serde_json::from_str(&value)
-                        .wrap_err("failed to deserialize 2FA tuple")
-                        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-    
-                    let login_attempt_id = LoginAttemptId::parse(Secret::new(data.0))
-                        .wrap_err("failed to parse login_attempt_id")
-                        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-    
-                    let email_code = TwoFACode::parse(Secret::new(data.1))
-                        .wrap_err("failed to parse email_code")
-                        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-    
-                    Ok((login_attempt_id, email_code))
-                }
-                Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
-            }
+    #[tracing::instrument(name = "delete_two_fa_codes", skip_all)]
+    async fn delete_two_fa_codes(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let attempts_key = get_attempts_key(email);
+        let mut conn = self.conn.clone();
+
+        let attempts: Vec<String> = conn
+            .zrange(&attempts_key, 0, -1)
+            .await
+            .wrap_err("failed to get 2FA login attempts from Redis")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let mut keys: Vec<String> = attempts.iter().map(|id| format!("{}{}", TWO_FA_CODE_PREFIX, id)).collect();
+        keys.push(attempts_key);
+        let _: () = conn
+            .del(keys)
+            .await
+            .wrap_err("failed to delete 2FA codes from Redis")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    #[tracing::instrument(name = "verify_two_fa_code", skip_all)]
+    async fn verify_two_fa_code(&self, email: &Email, login_attempt_id: &LoginAttemptId, code: &TwoFACode) ->
+        Result<bool, TwoFACodeStoreError> {
+        let code_hash: Option<String> = self.consume_script
+            .key(get_key(login_attempt_id))
+            .key(get_attempts_key(email))
+            .arg(email.canonical().expose_secret())
+            .arg(login_attempt_id.as_ref().expose_secret())
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .wrap_err("failed to take 2FA code from Redis")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let code_hash = code_hash.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        let code_hash = TwoFACodeHash::parse(Secret::new(code_hash))
+            .wrap_err("failed to parse 2FA code hash")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(code_hash.matches(login_attempt_id, code, &self.code_secret))
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::{ExposeSecret, Secret};
+
+use sqlx::{Row, SqlitePool};
+
+use crate::{
+    domain::{data_stores::{BannedTokenStore, BannedTokenStoreError}, UserId},
+    utils::constants::TTL_SECONDS_I64,
+};
+
+pub struct SqliteBannedTokenStore {
+    pool: SqlitePool,
+}
+
+impl SqliteBannedTokenStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for SqliteBannedTokenStore {
+    #[tracing::instrument(name = "Adding banned token to SQLite", skip_all)]
+    async fn add_banned_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        // Expiry is kept as a unix timestamp since SQLite has no timestamp type
+        let expires_at = Utc::now().timestamp() + TTL_SECONDS_I64;
+
+        sqlx::query(
+            r#"
+            INSERT INTO banned_tokens (token, expires_at)
+            VALUES (?, ?)
+            ON CONFLICT (token) DO NOTHING
+            "#,
+        )
+        .bind(token.expose_secret())
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking banned token in SQLite", skip_all)]
+    async fn is_banned_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM banned_tokens
+                WHERE token = ? AND expires_at > ?
+            ) AS is_banned
+            "#,
+        )
+        .bind(token.expose_secret())
+        .bind(Utc::now().timestamp())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        row.try_get("is_banned")
+            .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Revoking user tokens in SQLite", skip_all)]
+    async fn revoke_user_tokens(&self, user_id: &UserId, at: DateTime<Utc>) -> Result<(), BannedTokenStoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO revoked_user_tokens (user_id, revoked_at)
+            VALUES (?, ?)
+            ON CONFLICT (user_id) DO UPDATE
+            SET revoked_at = MAX(revoked_at, excluded.revoked_at)
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(at.timestamp())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking revoked user tokens in SQLite", skip_all)]
+    async fn is_revoked_user_token(&self,
+                                   user_id: &UserId,
+                                   issued_at: DateTime<Utc>) -> Result<bool, BannedTokenStoreError> {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM revoked_user_tokens
+                WHERE user_id = ? AND revoked_at >= ?
+            ) AS is_revoked
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(issued_at.timestamp())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        row.try_get("is_revoked")
+            .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Retrieving user token revocation from SQLite", skip_all)]
+    async fn user_tokens_revoked_at(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>, BannedTokenStoreError> {
+        let revoked_at: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT revoked_at FROM revoked_user_tokens
+            WHERE user_id = ?
+            "#,
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        revoked_at
+            .map(|revoked_at| DateTime::from_timestamp(revoked_at, 0)
+                .ok_or_else(|| BannedTokenStoreError::UnexpectedError(eyre!("Invalid revocation time"))))
+            .transpose()
+    }
+}
@@ -0,0 +1,138 @@
+use std::num::NonZeroUsize;
+
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, Secret};
+
+use sqlx::{Row, SqlitePool};
+
+use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeHash, TwoFACodeStore, TwoFACodeStoreError};
+use crate::utils::constants::TTL_SECONDS_I64;
+
+pub struct SqliteTwoFACodeStore {
+    pool: SqlitePool,
+    max_pending: NonZeroUsize,
+    code_secret: Secret<String>,
+}
+
+impl SqliteTwoFACodeStore {
+    pub fn new(pool: SqlitePool, max_pending: NonZeroUsize, code_secret: Secret<String>) -> Self {
+        Self { pool, max_pending, code_secret }
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for SqliteTwoFACodeStore {
+    #[tracing::instrument(name = "Adding 2FA code to SQLite", skip_all)]
+    async fn add_two_fa_code(&self, email: &Email, login_attempt_id: LoginAttemptId, code: TwoFACode) ->
+        Result<(), TwoFACodeStoreError> {
+        let created_at = Utc::now().timestamp();
+        let expires_at = created_at + TTL_SECONDS_I64;
+        let code_hash = TwoFACodeHash::new(&login_attempt_id, &code, &self.code_secret);
+        let max_pending = i64::try_from(self.max_pending.get()).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let mut transaction = self.pool.begin().await.map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO two_fa_codes (login_attempt_id, email, code_hash, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(login_attempt_id.as_ref().expose_secret())
+        .bind(email.canonical().expose_secret())
+        .bind(code_hash.as_ref().expose_secret())
+        .bind(created_at)
+        .bind(expires_at)
+        .execute(&mut transaction)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // Drops the oldest codes of the email over the cap, along with its expired ones.
+        // Codes created within the same second are ordered by rowid.
+        sqlx::query(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE email = ?1
+              AND login_attempt_id NOT IN (
+                  SELECT login_attempt_id
+                  FROM two_fa_codes
+                  WHERE email = ?1 AND expires_at > ?2
+                  ORDER BY created_at DESC, rowid DESC
+                  LIMIT ?3
+              )
+            "#,
+        )
+        .bind(email.canonical().expose_secret())
+        .bind(created_at)
+        .bind(max_pending)
+        .execute(&mut transaction)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        transaction.commit().await.map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Verifying 2FA code in SQLite", skip_all)]
+    async fn verify_two_fa_code(&self, email: &Email, login_attempt_id: &LoginAttemptId, code: &TwoFACode) ->
+        Result<bool, TwoFACodeStoreError> {
+        let row = sqlx::query(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE login_attempt_id = ? AND email = ? AND expires_at > ?
+            RETURNING code_hash
+            "#,
+        )
+        .bind(login_attempt_id.as_ref().expose_secret())
+        .bind(email.canonical().expose_secret())
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let code_hash: String = row.try_get("code_hash").map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let code_hash = TwoFACodeHash::parse(Secret::new(code_hash))
+            .wrap_err("failed to parse code_hash")
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(code_hash.matches(login_attempt_id, code, &self.code_secret))
+    }
+
+    #[tracing::instrument(name = "Deleting 2FA code from SQLite", skip_all)]
+    async fn delete_two_fa_code(&self, email: &Email, login_attempt_id: &LoginAttemptId) ->
+        Result<(), TwoFACodeStoreError> {
+        sqlx::query(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE login_attempt_id = ? AND email = ?
+            "#,
+        )
+        .bind(login_attempt_id.as_ref().expose_secret())
+        .bind(email.canonical().expose_secret())
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Deleting 2FA codes of an email from SQLite", skip_all)]
+    async fn delete_two_fa_codes(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        sqlx::query(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE email = ?
+            "#,
+        )
+        .bind(email.canonical().expose_secret())
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
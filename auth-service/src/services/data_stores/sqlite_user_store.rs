@@ -0,0 +1,411 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::{ExposeSecret, Secret};
+
+use sqlx::{FromRow, SqlitePool};
+
+use crate::{
+    app_state::PasswordHasherType,
+    domain::{
+        data_stores::{UserStore, UserStoreError},
+        DisplayName, Email, HashedPassword, Password, Role, User, UserId,
+    },
+};
+
+pub struct SqliteUserStore {
+    pool: SqlitePool,
+    password_hasher: PasswordHasherType,
+}
+
+impl SqliteUserStore {
+    pub fn new(pool: SqlitePool, password_hasher: PasswordHasherType) -> Self {
+        Self { pool, password_hasher }
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<(), UserStoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, email, email_canonical, password_hash, requires_2fa, role,
+                               display_name, created_at, updated_at, last_login_at,
+                               failed_login_attempts, locked_until)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(user.id.to_string())
+        .bind(user.email.as_ref().expose_secret())
+        .bind(user.email.canonical().expose_secret())
+        .bind(user.password_hash.as_ref().map(|hash| hash.as_ref().expose_secret()))
+        .bind(user.requires_2fa)
+        .bind(user.role.as_ref())
+        .bind(user.display_name.as_ref().map(|name| name.as_ref()))
+        // Timestamps are kept as unix seconds since SQLite has no timestamp type
+        .bind(user.created_at.timestamp())
+        .bind(user.updated_at.timestamp())
+        .bind(user.last_login_at.map(|at| at.timestamp()))
+        .bind(user.failed_login_attempts)
+        .bind(user.locked_until.map(|until| until.timestamp()))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+            Some(code) if code == CONSTRAINT_PRIMARYKEY || code == CONSTRAINT_UNIQUE =>
+                UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Rehashing password in SQLite", skip_all)]
+    async fn rehash_password(&self, email: &Email, password: &Password) -> Result<HashedPassword, UserStoreError> {
+        let password_hash = self.password_hasher
+            .hash_password(password)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        sqlx::query("UPDATE users SET password_hash = ? WHERE email_canonical = ?")
+            .bind(password_hash.as_ref().expose_secret())
+            .bind(email.canonical().expose_secret())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(password_hash)
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for SqliteUserStore {
+    #[tracing::instrument(name = "Adding user to SQLite", skip_all)]
+    async fn add_user(&self, mut user: User, password: Option<Password>) -> Result<(), UserStoreError> {
+        user.password_hash = match password {
+            Some(password) => Some(
+                self.password_hasher
+                    .hash_password(&password)
+                    .await
+                    .map_err(UserStoreError::UnexpectedError)?,
+            ),
+            None => None,
+        };
+
+        self.insert_user(&user).await
+    }
+
+    #[tracing::instrument(name = "Retrieving user from SQLite", skip_all)]
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            SELECT id, email, password_hash, requires_2fa, role, display_name, created_at, updated_at, last_login_at,
+                   failed_login_attempts, locked_until
+            FROM users
+            WHERE email_canonical = ?
+            "#,
+        )
+        .bind(email.canonical().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from SQLite", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            SELECT id, email, password_hash, requires_2fa, role, display_name, created_at, updated_at, last_login_at,
+                   failed_login_attempts, locked_until
+            FROM users
+            WHERE id = ?
+            "#,
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Listing users in SQLite", skip_all)]
+    async fn list_users(&self, after: Option<&UserId>, limit: usize) -> Result<Vec<User>, UserStoreError> {
+        let limit = i64::try_from(limit).map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        // Hyphenated lowercase UUIDs sort as text in the same order as their bytes
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            SELECT id, email, password_hash, requires_2fa, role, display_name, created_at, updated_at, last_login_at,
+                   failed_login_attempts, locked_until
+            FROM users
+            WHERE ? IS NULL OR id > ?
+            ORDER BY id
+            LIMIT ?
+            "#,
+        )
+        .bind(after.map(|id| id.to_string()))
+        .bind(after.map(|id| id.to_string()))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(User::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Validating user in SQLite", skip_all)]
+    async fn validate_user(&self, email: &Email, password: &Password) -> Result<User, UserStoreError> {
+        // Unknown emails fail like wrong passwords, so callers can't tell them apart
+        let mut user = match self.get_user(email).await {
+            Ok(user) => user,
+            Err(UserStoreError::UserNotFound) => return Err(UserStoreError::InvalidCredentials),
+            Err(e) => return Err(e),
+        };
+
+        if user.is_locked(Utc::now()) {
+            return Err(UserStoreError::UserLocked);
+        }
+
+        let password_hash = match &user.password_hash {
+            Some(password_hash) => password_hash,
+            None => return Err(UserStoreError::InvalidCredentials),
+        };
+
+        self.password_hasher
+            .verify_password(password_hash, password)
+            .await
+            .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+        // A failed upgrade must not fail the login, the old hash still works
+        if self.password_hasher.needs_rehash(password_hash) {
+            match self.rehash_password(email, password).await {
+                Ok(password_hash) => user.password_hash = Some(password_hash),
+                Err(e) => tracing::warn!("failed to rehash password: {:?}", e),
+            }
+        }
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(name = "Importing user into SQLite", skip_all)]
+    async fn import_user(&self, mut user: User, password_hash: HashedPassword) -> Result<(), UserStoreError> {
+        user.password_hash = Some(password_hash);
+        self.insert_user(&user).await
+    }
+
+    #[tracing::instrument(name = "Updating display name in SQLite", skip_all)]
+    async fn update_display_name(&self,
+                                 email: &Email,
+                                 display_name: Option<DisplayName>) -> Result<User, UserStoreError> {
+        let result = sqlx::query("UPDATE users SET display_name = ?, updated_at = ? WHERE email_canonical = ?")
+            .bind(display_name.as_ref().map(|name| name.as_ref()))
+            .bind(Utc::now().timestamp())
+            .bind(email.canonical().expose_secret())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user(email).await
+    }
+
+    #[tracing::instrument(name = "Updating email in SQLite", skip_all)]
+    async fn update_email(&self, id: &UserId, email: Email) -> Result<User, UserStoreError> {
+        let result = sqlx::query("UPDATE users SET email = ?, email_canonical = ?, updated_at = ? WHERE id = ?")
+            .bind(email.as_ref().expose_secret())
+            .bind(email.canonical().expose_secret())
+            .bind(Utc::now().timestamp())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+                Some(code) if code == CONSTRAINT_PRIMARYKEY || code == CONSTRAINT_UNIQUE =>
+                    UserStoreError::UserAlreadyExists,
+                _ => UserStoreError::UnexpectedError(e.into()),
+            })?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    #[tracing::instrument(name = "Recording login in SQLite", skip_all)]
+    async fn record_login(&self, email: &Email, at: DateTime<Utc>) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET last_login_at = ?, failed_login_attempts = 0 WHERE email_canonical = ?")
+            .bind(at.timestamp())
+            .bind(email.canonical().expose_secret())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording failed login in SQLite", skip_all)]
+    async fn record_failed_login(&self,
+                                 email: &Email,
+                                 max_attempts: u32,
+                                 locked_until: DateTime<Utc>) -> Result<(), UserStoreError> {
+        // Counting and locking in one statement keeps concurrent failures from being lost
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET failed_login_attempts = CASE WHEN failed_login_attempts + 1 >= ?1 THEN 0
+                                             ELSE failed_login_attempts + 1 END,
+                locked_until = CASE WHEN failed_login_attempts + 1 >= ?1 THEN ?2
+                                    ELSE locked_until END
+            WHERE email_canonical = ?3
+            "#,
+        )
+        .bind(max_attempts)
+        .bind(locked_until.timestamp())
+        .bind(email.canonical().expose_secret())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Unlocking user in SQLite", skip_all)]
+    async fn unlock_user(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET failed_login_attempts = 0, locked_until = NULL, updated_at = ? WHERE id = ?"
+        )
+        .bind(Utc::now().timestamp())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    #[tracing::instrument(name = "Updating 2FA requirement in SQLite", skip_all)]
+    async fn update_requires_2fa(&self, id: &UserId, requires_2fa: bool) -> Result<User, UserStoreError> {
+        let result = sqlx::query("UPDATE users SET requires_2fa = ?, updated_at = ? WHERE id = ?")
+            .bind(requires_2fa)
+            .bind(Utc::now().timestamp())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    #[tracing::instrument(name = "Clearing password in SQLite", skip_all)]
+    async fn clear_password(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let result = sqlx::query("UPDATE users SET password_hash = NULL, updated_at = ? WHERE id = ?")
+            .bind(Utc::now().timestamp())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+
+    #[tracing::instrument(name = "Setting password in SQLite", skip_all)]
+    async fn set_password(&self, id: &UserId, password: Password) -> Result<User, UserStoreError> {
+        let password_hash = self.password_hasher
+            .hash_password(&password)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = ?, failed_login_attempts = 0, locked_until = NULL, updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(password_hash.as_ref().expose_secret())
+        .bind(Utc::now().timestamp())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.get_user_by_id(id).await
+    }
+}
+
+// Timestamps are unix seconds and the id is a hyphenated UUID
+#[derive(FromRow)]
+struct UserRow {
+    id: String,
+    email: String,
+    password_hash: Option<String>,
+    requires_2fa: bool,
+    role: String,
+    display_name: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+    last_login_at: Option<i64>,
+    failed_login_attempts: u32,
+    locked_until: Option<i64>,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: UserId::parse(&row.id).map_err(UserStoreError::UnexpectedError)?,
+            email: Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)?,
+            password_hash: row.password_hash
+                .map(|hash| HashedPassword::parse(Secret::new(hash)))
+                .transpose()
+                .map_err(UserStoreError::UnexpectedError)?,
+            requires_2fa: row.requires_2fa,
+            role: Role::parse(&row.role).map_err(UserStoreError::UnexpectedError)?,
+            display_name: row.display_name
+                .map(|name| DisplayName::parse(&name))
+                .transpose()
+                .map_err(UserStoreError::UnexpectedError)?,
+            created_at: timestamp(row.created_at)?,
+            updated_at: timestamp(row.updated_at)?,
+            last_login_at: row.last_login_at.map(timestamp).transpose()?,
+            failed_login_attempts: row.failed_login_attempts,
+            locked_until: row.locked_until.map(timestamp).transpose()?,
+        })
+    }
+}
+
+fn timestamp(seconds: i64) -> Result<DateTime<Utc>, UserStoreError> {
+    DateTime::from_timestamp(seconds, 0)
+        .ok_or_else(|| UserStoreError::UnexpectedError(eyre!("invalid timestamp {}", seconds)))
+}
+
+// Extended result codes raised by SQLite when a PRIMARY KEY or UNIQUE constraint is violated
+const CONSTRAINT_PRIMARYKEY: &str = "1555";
+const CONSTRAINT_UNIQUE: &str = "2067";
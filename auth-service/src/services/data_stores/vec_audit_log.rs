@@ -0,0 +1,34 @@
+use tokio::sync::RwLock;
+
+use crate::domain::{AuditEvent, AuditEventFilter, AuditLog, AuditLogError, AuditRecord};
+
+// Events are kept in the order they were recorded, the index is the id
+#[derive(Default)]
+pub struct VecAuditLog {
+    events: RwLock<Vec<AuditEvent>>,
+}
+
+#[async_trait::async_trait]
+impl AuditLog for VecAuditLog {
+    async fn record(&self, event: AuditEvent) -> Result<(), AuditLogError> {
+        self.events.write().await.push(event);
+        Ok(())
+    }
+
+    async fn query(&self,
+                   filter: &AuditEventFilter,
+                   before: Option<i64>,
+                   limit: usize) -> Result<Vec<AuditRecord>, AuditLogError> {
+        let events = self.events.read().await;
+        let end = before.map_or(events.len(), |before| before.clamp(0, events.len() as i64) as usize);
+
+        Ok(events[..end]
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, event)| filter.matches(event))
+            .take(limit)
+            .map(|(id, event)| AuditRecord { id: id as i64, event: event.clone() })
+            .collect())
+    }
+}
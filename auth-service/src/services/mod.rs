@@ -1,5 +1,15 @@
+pub mod argon2_password_hasher;
+
 pub mod mock_email_client;
 
 pub mod postmark_email_client;
 
 pub mod data_stores;
+
+pub mod rate_limiter;
+
+pub mod personal_data_exporters;
+
+pub mod postgres_sweeper;
+
+pub mod sqlite_sweeper;
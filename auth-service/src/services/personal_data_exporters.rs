@@ -0,0 +1,316 @@
+use std::cmp::Reverse;
+
+use chrono::{DateTime, Utc};
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    app_state::{ApiKeyStoreType, AppState, AuditLogType, BannedTokenStoreType, EmailChangeStoreType,
+                ImpersonationStoreType},
+    domain::{AuditEventFilter, AuditEventKind, AuditOutcome, AuditRecord, EmailChangeStoreError,
+             PersonalDataExportError, PersonalDataExporter, User},
+};
+
+const AUDIT_EVENTS_EXPORT_PAGE_SIZE: usize = 500;
+
+// Every subsystem holding data about users, in the order their sections appear in an export
+pub fn personal_data_exporters(state: &AppState) -> Vec<Box<dyn PersonalDataExporter + Send + Sync>> {
+    vec![
+        Box::new(UserRecordExporter),
+        Box::new(TwoFactorExporter),
+        Box::new(SessionsExporter {
+            banned_token_store: state.banned_token_store.clone(),
+            impersonation_store: state.impersonation_store.clone(),
+        }),
+        Box::new(EmailChangeExporter { email_change_store: state.email_change_store.clone() }),
+        Box::new(ApiKeysExporter { api_key_store: state.api_key_store.clone() }),
+        Box::new(AuditEventsExporter { audit_log: state.audit_log.clone() }),
+    ]
+}
+
+fn to_value<T: Serialize>(section: T) -> Result<Value, PersonalDataExportError> {
+    serde_json::to_value(section).map_err(|e| PersonalDataExportError::UnexpectedError(e.into()))
+}
+
+// The user record, without the password hash
+pub struct UserRecordExporter;
+
+#[derive(Serialize)]
+struct UserRecordSection {
+    id: String,
+    email: String,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    role: String,
+    #[serde(rename = "hasPassword")]
+    has_password: bool,
+    #[serde(rename = "createdAt")]
+    created_at: DateTime<Utc>,
+    #[serde(rename = "updatedAt")]
+    updated_at: DateTime<Utc>,
+    #[serde(rename = "failedLoginAttempts")]
+    failed_login_attempts: u32,
+    #[serde(rename = "lockedUntil")]
+    locked_until: Option<DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+impl PersonalDataExporter for UserRecordExporter {
+    fn section(&self) -> &'static str {
+        "user"
+    }
+
+    async fn export(&self, user: &User) -> Result<Value, PersonalDataExportError> {
+        to_value(UserRecordSection {
+            id: user.id.to_string(),
+            email: user.email.as_ref().expose_secret().to_owned(),
+            display_name: user.display_name.as_ref().map(|name| name.as_ref().to_owned()),
+            role: user.role.as_ref().to_owned(),
+            has_password: user.password_hash.is_some(),
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            failed_login_attempts: user.failed_login_attempts,
+            locked_until: user.locked_until,
+        })
+    }
+}
+
+// 2FA settings, pending codes are secrets and left out
+pub struct TwoFactorExporter;
+
+#[derive(Serialize)]
+struct TwoFactorSection {
+    #[serde(rename = "requires2FA")]
+    requires_2fa: bool,
+    method: &'static str,
+}
+
+#[async_trait::async_trait]
+impl PersonalDataExporter for TwoFactorExporter {
+    fn section(&self) -> &'static str {
+        "twoFactor"
+    }
+
+    async fn export(&self, user: &User) -> Result<Value, PersonalDataExportError> {
+        to_value(TwoFactorSection { requires_2fa: user.requires_2fa, method: "email" })
+    }
+}
+
+// Logins, revocations of the user's tokens and impersonations on either side.
+// The other party of an impersonation is left out, it's someone else's personal data.
+pub struct SessionsExporter {
+    banned_token_store: BannedTokenStoreType,
+    impersonation_store: ImpersonationStoreType,
+}
+
+#[derive(Serialize)]
+struct SessionsSection {
+    #[serde(rename = "lastLoginAt")]
+    last_login_at: Option<DateTime<Utc>>,
+    #[serde(rename = "tokensRevokedAt")]
+    tokens_revoked_at: Option<DateTime<Utc>>,
+    impersonations: Vec<ImpersonationSection>,
+}
+
+#[derive(Serialize)]
+struct ImpersonationSection {
+    id: String,
+    #[serde(rename = "asAdmin")]
+    as_admin: bool,
+    reason: Option<String>,
+    #[serde(rename = "startedAt")]
+    started_at: DateTime<Utc>,
+    #[serde(rename = "expiresAt")]
+    expires_at: DateTime<Utc>,
+    #[serde(rename = "endedAt")]
+    ended_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+impl PersonalDataExporter for SessionsExporter {
+    fn section(&self) -> &'static str {
+        "sessions"
+    }
+
+    async fn export(&self, user: &User) -> Result<Value, PersonalDataExportError> {
+        let tokens_revoked_at = self.banned_token_store
+            .user_tokens_revoked_at(&user.id)
+            .await
+            .map_err(|e| PersonalDataExportError::UnexpectedError(e.into()))?;
+
+        let impersonations = self.impersonation_store
+            .get_impersonations(&user.id)
+            .await
+            .map_err(|e| PersonalDataExportError::UnexpectedError(e.into()))?
+            .into_iter()
+            .map(|impersonation| ImpersonationSection {
+                as_admin: impersonation.admin_id == user.id,
+                id: impersonation.id,
+                reason: impersonation.reason,
+                started_at: impersonation.started_at,
+                expires_at: impersonation.expires_at,
+                ended_at: impersonation.ended_at,
+            })
+            .collect();
+
+        to_value(SessionsSection { last_login_at: user.last_login_at, tokens_revoked_at, impersonations })
+    }
+}
+
+// The pending change of email, without its confirmation code
+pub struct EmailChangeExporter {
+    email_change_store: EmailChangeStoreType,
+}
+
+#[derive(Serialize)]
+struct EmailChangeSection {
+    #[serde(rename = "newEmail")]
+    new_email: String,
+}
+
+#[async_trait::async_trait]
+impl PersonalDataExporter for EmailChangeExporter {
+    fn section(&self) -> &'static str {
+        "pendingEmailChange"
+    }
+
+    async fn export(&self, user: &User) -> Result<Value, PersonalDataExportError> {
+        match self.email_change_store.get_email_change(&user.id).await {
+            Ok(email_change) => to_value(EmailChangeSection {
+                new_email: email_change.new_email.as_ref().expose_secret().to_owned(),
+            }),
+            Err(EmailChangeStoreError::EmailChangeNotFound) => Ok(Value::Null),
+            Err(e) => Err(PersonalDataExportError::UnexpectedError(e.into())),
+        }
+    }
+}
+
+// API key metadata, the keys themselves are only ever shown once
+pub struct ApiKeysExporter {
+    api_key_store: ApiKeyStoreType,
+}
+
+#[derive(Serialize)]
+struct ApiKeySection {
+    id: String,
+    name: String,
+    prefix: String,
+    scopes: Vec<String>,
+    #[serde(rename = "createdAt")]
+    created_at: DateTime<Utc>,
+    #[serde(rename = "expiresAt")]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+impl PersonalDataExporter for ApiKeysExporter {
+    fn section(&self) -> &'static str {
+        "apiKeys"
+    }
+
+    async fn export(&self, user: &User) -> Result<Value, PersonalDataExportError> {
+        let api_keys: Vec<ApiKeySection> = self.api_key_store
+            .get_api_keys(&user.id)
+            .await
+            .map_err(|e| PersonalDataExportError::UnexpectedError(e.into()))?
+            .into_iter()
+            .map(|record| ApiKeySection {
+                id: record.id,
+                name: record.name,
+                prefix: record.prefix,
+                scopes: record.scopes,
+                created_at: record.created_at,
+                expires_at: record.expires_at,
+            })
+            .collect();
+
+        to_value(api_keys)
+    }
+}
+
+// Every audit event recorded against the user, newest first. Some events, like a wrong 2FA code,
+// are recorded with the email alone, so events are matched on the user's id or on their current email.
+// Events under the email are only the user's from when they took it, at signup or by their last
+// change of email. Before that, or with another user's id, they belong to whoever held it then.
+pub struct AuditEventsExporter {
+    audit_log: AuditLogType,
+}
+
+impl AuditEventsExporter {
+    async fn query_all(&self, filter: &AuditEventFilter) -> Result<Vec<AuditRecord>, PersonalDataExportError> {
+        let mut records = Vec::new();
+        let mut before = None;
+
+        loop {
+            let page = self.audit_log
+                .query(filter, before, AUDIT_EVENTS_EXPORT_PAGE_SIZE)
+                .await
+                .map_err(|e| PersonalDataExportError::UnexpectedError(e.into()))?;
+            let full_page = page.len() == AUDIT_EVENTS_EXPORT_PAGE_SIZE;
+            before = page.last().map(|record| record.id);
+            records.extend(page);
+
+            if !full_page {
+                return Ok(records);
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuditEventSection {
+    #[serde(rename = "occurredAt")]
+    occurred_at: DateTime<Utc>,
+    kind: String,
+    outcome: String,
+    ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    user_agent: Option<String>,
+    detail: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl PersonalDataExporter for AuditEventsExporter {
+    fn section(&self) -> &'static str {
+        "auditEvents"
+    }
+
+    async fn export(&self, user: &User) -> Result<Value, PersonalDataExportError> {
+        let by_id = self.query_all(&AuditEventFilter { user_id: Some(user.id), ..Default::default() }).await?;
+        let held_since = by_id
+            .iter()
+            .filter(|record| record.event.kind == AuditEventKind::EmailChanged
+                && record.event.outcome == AuditOutcome::Success)
+            .map(|record| record.event.occurred_at)
+            .fold(user.created_at, DateTime::max);
+        let by_email = self
+            .query_all(&AuditEventFilter {
+                email: Some(user.email.clone()),
+                since: Some(held_since),
+                ..Default::default()
+            })
+            .await?
+            .into_iter()
+            .filter(|record| record.event.user_id.is_none());
+
+        // Events under the email with a user id are either in `by_id` or someone else's, none is exported twice.
+        // Record ids grow with time, so sorting by id puts the newest first.
+        let mut records: Vec<AuditRecord> = by_id.into_iter().chain(by_email).collect();
+        records.sort_by_key(|record| Reverse(record.id));
+
+        let events: Vec<AuditEventSection> = records
+            .into_iter()
+            .map(|record| AuditEventSection {
+                occurred_at: record.event.occurred_at,
+                kind: record.event.kind.as_ref().to_owned(),
+                outcome: record.event.outcome.as_ref().to_owned(),
+                ip: record.event.ip.map(|ip| ip.to_string()),
+                user_agent: record.event.user_agent,
+                detail: record.event.detail,
+            })
+            .collect();
+
+        to_value(events)
+    }
+}
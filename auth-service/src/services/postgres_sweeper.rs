@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use color_eyre::eyre::Result;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+// Periodically deletes the expired rows of the banned token and 2FA code tables.
+// Expired rows are already ignored by the stores, this only keeps the tables small.
+pub fn spawn_expired_rows_sweeper(pool: PgPool, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match sweep_expired_rows(&pool).await {
+                Ok(deleted) if deleted > 0 => tracing::info!(deleted, "swept expired rows"),
+                Ok(_) => {}
+                Err(e) => tracing::error!("failed to sweep expired rows: {:?}", e),
+            }
+        }
+    })
+}
+
+#[tracing::instrument(name = "Sweeping expired rows", skip_all)]
+pub async fn sweep_expired_rows(pool: &PgPool) -> Result<u64> {
+    let banned_tokens = sqlx::query!(
+        r#"
+        DELETE FROM banned_tokens
+        WHERE expires_at <= NOW()
+        "#
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    let two_fa_codes = sqlx::query!(
+        r#"
+        DELETE FROM two_fa_codes
+        WHERE expires_at <= NOW()
+        "#
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(banned_tokens + two_fa_codes)
+}
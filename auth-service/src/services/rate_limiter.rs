@@ -0,0 +1,43 @@
+use std::{collections::VecDeque, hash::Hash, num::NonZeroUsize, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use lru::LruCache;
+
+// Counts events per key over a sliding window, in memory and per instance. Failed logins are
+// counted per client IP, sent login codes per email as well as per client IP.
+pub struct RateLimiter<K: Hash + Eq> {
+    events: Mutex<LruCache<K, VecDeque<DateTime<Utc>>>>,
+    max_events: usize,
+    window: Duration,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(capacity: NonZeroUsize, max_events: usize, window: Duration) -> Self {
+        Self { events: Mutex::new(LruCache::new(capacity)), max_events, window }
+    }
+
+    // Whether `key` has used up its events within the window
+    pub fn is_limited(&self, key: &K, now: DateTime<Utc>) -> bool {
+        let mut events = self.events.lock().unwrap();
+        match events.get_mut(key) {
+            Some(times) => {
+                purge(times, now - self.window);
+                times.len() >= self.max_events
+            }
+            None => false,
+        }
+    }
+
+    pub fn record(&self, key: K, now: DateTime<Utc>) {
+        let mut events = self.events.lock().unwrap();
+        let times = events.get_or_insert_mut(key, VecDeque::new);
+        purge(times, now - self.window);
+        times.push_back(now);
+    }
+}
+
+fn purge(times: &mut VecDeque<DateTime<Utc>>, before: DateTime<Utc>) {
+    while times.front().is_some_and(|time| *time <= before) {
+        times.pop_front();
+    }
+}
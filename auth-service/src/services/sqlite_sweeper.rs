@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::eyre::Result;
+use sqlx::SqlitePool;
+use tokio::task::JoinHandle;
+
+// SQLite counterpart of the Postgres sweeper, expiry is stored as a unix timestamp.
+pub fn spawn_expired_rows_sweeper(pool: SqlitePool, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match sweep_expired_rows(&pool).await {
+                Ok(deleted) if deleted > 0 => tracing::info!(deleted, "swept expired rows"),
+                Ok(_) => {}
+                Err(e) => tracing::error!("failed to sweep expired rows: {:?}", e),
+            }
+        }
+    })
+}
+
+#[tracing::instrument(name = "Sweeping expired SQLite rows", skip_all)]
+pub async fn sweep_expired_rows(pool: &SqlitePool) -> Result<u64> {
+    let now = Utc::now().timestamp();
+
+    let banned_tokens = sqlx::query("DELETE FROM banned_tokens WHERE expires_at <= ?")
+        .bind(now)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    let two_fa_codes = sqlx::query("DELETE FROM two_fa_codes WHERE expires_at <= ?")
+        .bind(now)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(banned_tokens + two_fa_codes)
+}
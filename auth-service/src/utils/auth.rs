@@ -1,23 +1,49 @@
 use axum_extra::extract::cookie::{Cookie, SameSite};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email};
+use crate::{app_state::{ApiKeyStoreType, BannedTokenStoreType},
+            domain::{ApiKey, ApiKeyRecord, Impersonation, MagicLinkId, UserId}};
 
-use super::constants::{JWT_COOKIE_NAME, TTL_SECONDS_I64, JWT_SECRET};
+use super::constants::{JWT_COOKIE_NAME, TTL_SECONDS_I64, MAGIC_LINK_TTL_SECONDS_I64, JWT_SECRET};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    // The user id, emails are kept out of tokens since they can change and are PII
     pub sub: String,
     pub exp: usize,
+    // Tokens issued before this claim existed read as issued at the epoch
+    #[serde(default)]
+    pub iat: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    // Set on impersonation tokens, names the admin acting as `sub` (RFC 8693, section 4.1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<ActorClaim>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActorClaim {
+    pub sub: String,
+}
+
+// The audience keeps magic link tokens from being accepted as auth tokens
+const MAGIC_LINK_AUDIENCE: &str = "magic-link";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkClaims {
+    pub sub: String,
+    pub jti: String,
+    pub aud: String,
+    pub exp: usize,
 }
 
 #[tracing::instrument(name = "generate_auth_cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(user_id: &UserId) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(user_id)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -32,12 +58,27 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// A cookie that outlives a revocation of the user's tokens made at `revoked_at`, for the user
+// whose tokens were revoked by their own request. `iat` counts whole seconds, a token issued
+// in the second of the revocation would be revoked with the others, hence the next second.
+#[tracing::instrument(name = "generate_auth_cookie_after_revocation", skip_all)]
+pub fn generate_auth_cookie_after_revocation(user_id: &UserId, revoked_at: DateTime<Utc>) -> Result<Cookie<'static>> {
+    let issued_at = DateTime::from_timestamp(revoked_at.timestamp() + 1, 0)
+        .ok_or(eyre!("failed to compute the issue time after {}", revoked_at))?;
+    let token = generate_auth_token_issued_at(user_id, issued_at)?;
+    Ok(create_auth_cookie(token))
+}
+
 #[tracing::instrument(name = "generate_auth_token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<String> {
+pub fn generate_auth_token(user_id: &UserId) -> Result<String> {
+    generate_auth_token_issued_at(user_id, Utc::now())
+}
+
+fn generate_auth_token_issued_at(user_id: &UserId, now: DateTime<Utc>) -> Result<String> {
     let delta = chrono::Duration::try_seconds(TTL_SECONDS_I64)
         .wrap_err("failed to create 10 minute time delta")?;
 
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(eyre!("failed to add 10 minutes to current time"))?
         .timestamp();
@@ -47,9 +88,33 @@ fn generate_auth_token(email: &Email) -> Result<String> {
         exp
     ))?;
 
-    let sub = email.as_ref().expose_secret().to_owned();
+    let iat = issued_at(now)?;
 
-    let claims = Claims { sub, exp };
+    let claims = Claims { sub: user_id.to_string(), exp, iat, jti: None, act: None };
+
+    create_token(&claims)
+}
+
+#[tracing::instrument(name = "generate_impersonation_token", skip_all)]
+pub fn generate_impersonation_token(impersonation: &Impersonation,
+                                    user_id: &UserId,
+                                    admin_id: &UserId) -> Result<String> {
+    let exp = impersonation.expires_at.timestamp();
+
+    let exp: usize = exp.try_into().wrap_err(format!(
+        "failed to cast exp time to usize. exp time: {}",
+        exp
+    ))?;
+
+    let claims = Claims {
+        sub: user_id.to_string(),
+        exp,
+        iat: issued_at(impersonation.started_at)?,
+        jti: Some(impersonation.id.clone()),
+        act: Some(ActorClaim {
+            sub: admin_id.to_string(),
+        }),
+    };
 
     create_token(&claims)
 }
@@ -57,7 +122,7 @@ fn generate_auth_token(email: &Email) -> Result<String> {
 #[tracing::instrument(name = "validate_token", skip_all)]
 pub async fn validate_token(token: &str,
     banned_token_store: BannedTokenStoreType) -> Result<Claims> {
-    match banned_token_store.read().await.is_banned_token(&Secret::new(token.to_owned())).await {
+    match banned_token_store.is_banned_token(&Secret::new(token.to_owned())).await {
         Ok(value) => {
             if value {
                 return Err(eyre!("token is banned"));
@@ -66,13 +131,96 @@ pub async fn validate_token(token: &str,
         Err(e) => return Err(e.into()),
     }
 
-    decode::<Claims>(
+    let claims = decode::<Claims>(
         token,
         &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
         &Validation::default(),
     )
     .map(|data| data.claims)
-    .wrap_err("failed to decode token")
+    .wrap_err("failed to decode token")?;
+
+    let user_id = UserId::parse(&claims.sub)?;
+    let issued_at = i64::try_from(claims.iat)
+        .ok()
+        .and_then(|iat| DateTime::from_timestamp(iat, 0))
+        .wrap_err("invalid iat claim")?;
+    if banned_token_store.is_revoked_user_token(&user_id, issued_at).await? {
+        return Err(eyre!("token is revoked"));
+    }
+
+    Ok(claims)
+}
+
+fn issued_at(at: DateTime<Utc>) -> Result<usize> {
+    at.timestamp().try_into().wrap_err(format!(
+        "failed to cast iat time to usize. iat time: {}",
+        at.timestamp()
+    ))
+}
+
+#[tracing::instrument(name = "generate_magic_link_token", skip_all)]
+pub fn generate_magic_link_token(user_id: &UserId, magic_link_id: &MagicLinkId) -> Result<String> {
+    let delta = chrono::Duration::try_seconds(MAGIC_LINK_TTL_SECONDS_I64)
+        .wrap_err("failed to create magic link time delta")?;
+
+    let exp = Utc::now()
+        .checked_add_signed(delta)
+        .ok_or(eyre!("failed to add magic link time delta to current time"))?
+        .timestamp();
+
+    let exp: usize = exp.try_into().wrap_err(format!(
+        "failed to cast exp time to usize. exp time: {}",
+        exp
+    ))?;
+
+    let claims = MagicLinkClaims {
+        sub: user_id.to_string(),
+        jti: magic_link_id.as_ref().expose_secret().to_owned(),
+        aud: MAGIC_LINK_AUDIENCE.to_owned(),
+        exp,
+    };
+
+    encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+    )
+    .wrap_err("failed to create magic link token")
+}
+
+#[tracing::instrument(name = "validate_magic_link_token", skip_all)]
+pub fn validate_magic_link_token(token: &str) -> Result<MagicLinkClaims> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[MAGIC_LINK_AUDIENCE]);
+
+    decode::<MagicLinkClaims>(
+        token,
+        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+        &validation,
+    )
+    .map(|data| data.claims)
+    .wrap_err("failed to decode magic link token")
+}
+
+#[tracing::instrument(name = "validate_api_key", skip_all)]
+pub async fn validate_api_key(token: &str,
+    api_key_store: ApiKeyStoreType) -> Result<ApiKeyRecord> {
+    let api_key = ApiKey::parse(Secret::new(token.to_owned()))?;
+
+    let record = api_key_store
+        .get_api_key(&api_key.prefix())
+        .await
+        .wrap_err("failed to retrieve API key")?;
+
+    if !record.matches(&api_key) {
+        return Err(eyre!("API key does not match"));
+    }
+
+    if record.is_expired() {
+        return Err(eyre!("API key is expired"));
+    }
+
+    Ok(record)
 }
 
 #[tracing::instrument(name = "create_token", skip_all)]
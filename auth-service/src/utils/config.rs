@@ -0,0 +1,223 @@
+use std::env as std_env;
+
+use color_eyre::eyre::{eyre, Result};
+use dotenvy::dotenv;
+
+use crate::domain::EmailPolicy;
+
+use super::constants::{env, DEFAULT_PASSWORD_HASH_ITERATIONS, DEFAULT_PASSWORD_HASH_MEMORY_KIB,
+                       DEFAULT_PASSWORD_HASH_PARALLELISM, DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS,
+                       DEFAULT_USER_CACHE_CAPACITY, DEFAULT_USER_CACHE_TTL_SECONDS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreBackend {
+    Memory,
+    Postgres,
+    Redis,
+    Sqlite,
+}
+
+impl StoreBackend {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "memory" => Ok(Self::Memory),
+            "postgres" => Ok(Self::Postgres),
+            "redis" => Ok(Self::Redis),
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(eyre!("{} is not a valid store backend, expected memory, postgres, redis or sqlite.", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailClientBackend {
+    Mock,
+    Postmark,
+}
+
+impl EmailClientBackend {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "mock" => Ok(Self::Mock),
+            "postmark" => Ok(Self::Postmark),
+            _ => Err(eyre!("{} is not a valid email client, expected mock or postmark.", s)),
+        }
+    }
+}
+
+// Argon2id cost parameters used for new password hashes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PasswordHashConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashConfig {
+    fn default() -> Self {
+        Self {
+            memory_kib: DEFAULT_PASSWORD_HASH_MEMORY_KIB,
+            iterations: DEFAULT_PASSWORD_HASH_ITERATIONS,
+            parallelism: DEFAULT_PASSWORD_HASH_PARALLELISM,
+        }
+    }
+}
+
+// Users cached in front of a Postgres or SQLite user store, a capacity of 0 disables the cache
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UserCacheConfig {
+    pub capacity: u32,
+    pub ttl_seconds: u32,
+}
+
+impl Default for UserCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_USER_CACHE_CAPACITY,
+            ttl_seconds: DEFAULT_USER_CACHE_TTL_SECONDS,
+        }
+    }
+}
+
+// Selects the backend of every store and the email client
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppConfig {
+    pub user_store: StoreBackend,
+    pub banned_token_store: StoreBackend,
+    pub two_fa_code_store: StoreBackend,
+    pub api_key_store: StoreBackend,
+    pub magic_link_store: StoreBackend,
+    pub password_reset_store: StoreBackend,
+    pub device_grant_store: StoreBackend,
+    pub impersonation_store: StoreBackend,
+    pub email_change_store: StoreBackend,
+    pub audit_log: StoreBackend,
+    pub email_client: EmailClientBackend,
+    pub password_hash: PasswordHashConfig,
+    pub email_policy: EmailPolicy,
+    pub user_cache: UserCacheConfig,
+    // Login attempts of a user that can wait for their 2FA code at once, the oldest are dropped beyond it
+    pub two_fa_max_pending_attempts: u32,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            user_store: StoreBackend::Memory,
+            banned_token_store: StoreBackend::Redis,
+            two_fa_code_store: StoreBackend::Redis,
+            api_key_store: StoreBackend::Memory,
+            magic_link_store: StoreBackend::Redis,
+            password_reset_store: StoreBackend::Redis,
+            device_grant_store: StoreBackend::Redis,
+            impersonation_store: StoreBackend::Memory,
+            email_change_store: StoreBackend::Redis,
+            audit_log: StoreBackend::Memory,
+            email_client: EmailClientBackend::Postmark,
+            password_hash: PasswordHashConfig::default(),
+            email_policy: EmailPolicy::default(),
+            user_cache: UserCacheConfig::default(),
+            two_fa_max_pending_attempts: DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS,
+        }
+    }
+}
+
+impl AppConfig {
+    // The defaults once the user store is on SQLite, for a single process without Postgres or Redis:
+    // the stores SQLite backs use it as well, the others are kept in memory
+    pub fn sqlite() -> Self {
+        Self {
+            user_store: StoreBackend::Sqlite,
+            banned_token_store: StoreBackend::Sqlite,
+            two_fa_code_store: StoreBackend::Sqlite,
+            magic_link_store: StoreBackend::Memory,
+            password_reset_store: StoreBackend::Memory,
+            device_grant_store: StoreBackend::Memory,
+            email_change_store: StoreBackend::Memory,
+            ..Self::default()
+        }
+    }
+
+    // Reads the configuration from the environment, unset variables keep their default
+    pub fn from_env() -> Result<Self> {
+        dotenv().ok();
+        let user_store = store_backend(env::USER_STORE_ENV_VAR, Self::default().user_store)?;
+        let default = match user_store {
+            StoreBackend::Sqlite => Self::sqlite(),
+            _ => Self::default(),
+        };
+        Ok(Self {
+            user_store,
+            banned_token_store: store_backend(env::BANNED_TOKEN_STORE_ENV_VAR, default.banned_token_store)?,
+            two_fa_code_store: store_backend(env::TWO_FA_CODE_STORE_ENV_VAR, default.two_fa_code_store)?,
+            api_key_store: store_backend(env::API_KEY_STORE_ENV_VAR, default.api_key_store)?,
+            magic_link_store: store_backend(env::MAGIC_LINK_STORE_ENV_VAR, default.magic_link_store)?,
+            password_reset_store: store_backend(env::PASSWORD_RESET_STORE_ENV_VAR, default.password_reset_store)?,
+            device_grant_store: store_backend(env::DEVICE_GRANT_STORE_ENV_VAR, default.device_grant_store)?,
+            impersonation_store: store_backend(env::IMPERSONATION_STORE_ENV_VAR, default.impersonation_store)?,
+            email_change_store: store_backend(env::EMAIL_CHANGE_STORE_ENV_VAR, default.email_change_store)?,
+            audit_log: store_backend(env::AUDIT_LOG_ENV_VAR, default.audit_log)?,
+            email_client: match std_env::var(env::EMAIL_CLIENT_ENV_VAR) {
+                Ok(value) => EmailClientBackend::parse(&value)?,
+                Err(_) => default.email_client,
+            },
+            password_hash: PasswordHashConfig {
+                memory_kib: number(env::PASSWORD_HASH_MEMORY_KIB_ENV_VAR, default.password_hash.memory_kib)?,
+                iterations: number(env::PASSWORD_HASH_ITERATIONS_ENV_VAR, default.password_hash.iterations)?,
+                parallelism: number(env::PASSWORD_HASH_PARALLELISM_ENV_VAR, default.password_hash.parallelism)?,
+            },
+            email_policy: EmailPolicy {
+                case_insensitive_local_part: boolean(env::EMAIL_CASE_INSENSITIVE_LOCAL_PART_ENV_VAR,
+                                                     default.email_policy.case_insensitive_local_part)?,
+                ignore_plus_tag: boolean(env::EMAIL_IGNORE_PLUS_TAG_ENV_VAR, default.email_policy.ignore_plus_tag)?,
+            },
+            user_cache: UserCacheConfig {
+                capacity: number(env::USER_CACHE_CAPACITY_ENV_VAR, default.user_cache.capacity)?,
+                ttl_seconds: number(env::USER_CACHE_TTL_SECONDS_ENV_VAR, default.user_cache.ttl_seconds)?,
+            },
+            two_fa_max_pending_attempts: number(env::TWO_FA_MAX_PENDING_ATTEMPTS_ENV_VAR,
+                                                default.two_fa_max_pending_attempts)?,
+        })
+    }
+
+    pub fn uses(&self, backend: StoreBackend) -> bool {
+        [
+            self.user_store,
+            self.banned_token_store,
+            self.two_fa_code_store,
+            self.api_key_store,
+            self.magic_link_store,
+            self.password_reset_store,
+            self.device_grant_store,
+            self.impersonation_store,
+            self.email_change_store,
+            self.audit_log,
+        ]
+        .contains(&backend)
+    }
+}
+
+fn store_backend(env_var: &str, default: StoreBackend) -> Result<StoreBackend> {
+    match std_env::var(env_var) {
+        Ok(value) => StoreBackend::parse(&value),
+        Err(_) => Ok(default),
+    }
+}
+
+fn number(env_var: &str, default: u32) -> Result<u32> {
+    match std_env::var(env_var) {
+        Ok(value) => value.trim().parse().map_err(|_| eyre!("{} must be a positive number, got {}.", env_var, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn boolean(env_var: &str, default: bool) -> Result<bool> {
+    match std_env::var(env_var) {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(eyre!("{} must be true or false, got {}.", env_var, value)),
+        },
+        Err(_) => Ok(default),
+    }
+}
@@ -6,8 +6,39 @@ use secrecy::Secret;
 pub const JWT_COOKIE_NAME: &str = "jwt";
 pub const TTL_SECONDS_I64: i64 = 600; 
 pub const TTL_SECONDS_U64: u64 = 600;
+pub const MAGIC_LINK_TTL_SECONDS_I64: i64 = 900;
+pub const MAGIC_LINK_TTL_SECONDS_U64: u64 = 900;
+// Reset tokens are passed on to the user by an admin, so they are given a day
+pub const PASSWORD_RESET_TTL_SECONDS_I64: i64 = 86400;
+pub const PASSWORD_RESET_TTL_SECONDS_U64: u64 = 86400;
+pub const DEVICE_CODE_TTL_SECONDS_I64: i64 = 600;
+pub const DEVICE_CODE_POLL_INTERVAL_SECONDS_I64: i64 = 5;
+pub const IMPERSONATION_TTL_SECONDS_I64: i64 = 900;
+pub const EMAIL_CHANGE_TTL_SECONDS_I64: i64 = 900;
+pub const EMAIL_CHANGE_TTL_SECONDS_U64: u64 = 900;
+pub const MAX_EMAIL_CHANGE_ATTEMPTS: u32 = 5;
+pub const SWEEP_INTERVAL_SECONDS_U64: u64 = 60;
+pub const MAX_FAILED_LOGIN_ATTEMPTS: u32 = 5;
+pub const LOCKOUT_SECONDS_I64: i64 = 900;
+// One less than locks an account, over the same window, so a single IP can't lock anyone out
+pub const MAX_FAILED_LOGINS_PER_IP: usize = MAX_FAILED_LOGIN_ATTEMPTS as usize - 1;
+// Login codes sent per email and per client IP over the window, so that no one floods a mailbox
+pub const MAX_LOGIN_CODES_PER_EMAIL: usize = 5;
+pub const MAX_LOGIN_CODES_PER_IP: usize = 20;
+pub const LOGIN_CODE_WINDOW_SECONDS_I64: i64 = 900;
+pub const RATE_LIMITER_CAPACITY: usize = 10_000;
+// Outlives every token the service issues, auth tokens as well as impersonation tokens
+pub const REVOKED_USER_TOKENS_TTL_SECONDS_U64: u64 = 900;
 
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1"; 
+pub const DEFAULT_BASE_URL: &str = "http://localhost:3000";
+pub const DEFAULT_SQLITE_DATABASE_URL: &str = "sqlite://auth.db";
+pub const DEFAULT_PASSWORD_HASH_MEMORY_KIB: u32 = 15000;
+pub const DEFAULT_PASSWORD_HASH_ITERATIONS: u32 = 2;
+pub const DEFAULT_PASSWORD_HASH_PARALLELISM: u32 = 1;
+pub const DEFAULT_USER_CACHE_CAPACITY: u32 = 10000;
+pub const DEFAULT_USER_CACHE_TTL_SECONDS: u32 = 30;
+pub const DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS: u32 = 5;
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
@@ -25,6 +56,10 @@ lazy_static! {
     pub static ref DATABASE_URL: Secret<String> = set_database_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host(); 
     pub static ref POSTMARK_AUTH_TOKEN: Secret<String> = set_postmark_auth_token();
+    pub static ref BASE_URL: String = set_base_url();
+    pub static ref SQLITE_DATABASE_URL: Secret<String> = set_sqlite_database_url();
+    pub static ref PASSWORD_PEPPER: Option<Secret<String>> = set_password_pepper();
+    pub static ref TWO_FA_CODE_SECRET: Secret<String> = set_two_fa_code_secret();
 }
 
 fn set_token() -> Secret<String> {
@@ -53,11 +88,64 @@ fn set_postmark_auth_token() -> Secret<String> {
     )
 }
 
+fn set_base_url() -> String {
+    dotenv().ok();
+    std_env::var(env::BASE_URL_ENV_VAR).unwrap_or(DEFAULT_BASE_URL.to_owned())
+}
+
+// The pepper is optional. Hashes made without it keep verifying after it's set, but changing it
+// invalidates every hash made with it.
+fn set_password_pepper() -> Option<Secret<String>> {
+    dotenv().ok();
+    std_env::var(env::PASSWORD_PEPPER_ENV_VAR)
+        .ok()
+        .filter(|pepper| !pepper.is_empty())
+        .map(Secret::new)
+}
+
+// Keys the HMAC of stored 2FA codes. Changing it invalidates the pending codes and nothing else.
+fn set_two_fa_code_secret() -> Secret<String> {
+    dotenv().ok();
+    let secret = std_env::var(env::TWO_FA_CODE_SECRET_ENV_VAR).expect("TWO_FA_CODE_SECRET must be set.");
+    if secret.is_empty() {
+        panic!("TWO_FA_CODE_SECRET must not be empty.");
+    }
+    Secret::new(secret)
+}
+
+fn set_sqlite_database_url() -> Secret<String> {
+    dotenv().ok();
+    Secret::new(std_env::var(env::SQLITE_DATABASE_URL_ENV_VAR).unwrap_or(DEFAULT_SQLITE_DATABASE_URL.to_owned()))
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
+    pub const SQLITE_DATABASE_URL_ENV_VAR: &str = "SQLITE_DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
     pub const POSTMARK_AUTH_TOKEN_ENV_VAR: &str = "POSTMARK_AUTH_TOKEN";
+    pub const BASE_URL_ENV_VAR: &str = "AUTH_SERVICE_BASE_URL";
+    pub const USER_STORE_ENV_VAR: &str = "USER_STORE";
+    pub const BANNED_TOKEN_STORE_ENV_VAR: &str = "BANNED_TOKEN_STORE";
+    pub const TWO_FA_CODE_STORE_ENV_VAR: &str = "TWO_FA_CODE_STORE";
+    pub const API_KEY_STORE_ENV_VAR: &str = "API_KEY_STORE";
+    pub const MAGIC_LINK_STORE_ENV_VAR: &str = "MAGIC_LINK_STORE";
+    pub const PASSWORD_RESET_STORE_ENV_VAR: &str = "PASSWORD_RESET_STORE";
+    pub const EMAIL_CHANGE_STORE_ENV_VAR: &str = "EMAIL_CHANGE_STORE";
+    pub const DEVICE_GRANT_STORE_ENV_VAR: &str = "DEVICE_GRANT_STORE";
+    pub const IMPERSONATION_STORE_ENV_VAR: &str = "IMPERSONATION_STORE";
+    pub const AUDIT_LOG_ENV_VAR: &str = "AUDIT_LOG";
+    pub const EMAIL_CLIENT_ENV_VAR: &str = "EMAIL_CLIENT";
+    pub const PASSWORD_PEPPER_ENV_VAR: &str = "PASSWORD_PEPPER";
+    pub const TWO_FA_CODE_SECRET_ENV_VAR: &str = "TWO_FA_CODE_SECRET";
+    pub const PASSWORD_HASH_MEMORY_KIB_ENV_VAR: &str = "PASSWORD_HASH_MEMORY_KIB";
+    pub const PASSWORD_HASH_ITERATIONS_ENV_VAR: &str = "PASSWORD_HASH_ITERATIONS";
+    pub const PASSWORD_HASH_PARALLELISM_ENV_VAR: &str = "PASSWORD_HASH_PARALLELISM";
+    pub const EMAIL_CASE_INSENSITIVE_LOCAL_PART_ENV_VAR: &str = "EMAIL_CASE_INSENSITIVE_LOCAL_PART";
+    pub const EMAIL_IGNORE_PLUS_TAG_ENV_VAR: &str = "EMAIL_IGNORE_PLUS_TAG";
+    pub const USER_CACHE_CAPACITY_ENV_VAR: &str = "USER_CACHE_CAPACITY";
+    pub const USER_CACHE_TTL_SECONDS_ENV_VAR: &str = "USER_CACHE_TTL_SECONDS";
+    pub const TWO_FA_MAX_PENDING_ATTEMPTS_ENV_VAR: &str = "TWO_FA_MAX_PENDING_ATTEMPTS";
 }
 
 
@@ -1,5 +1,7 @@
 pub mod constants;
 
+pub mod config;
+
 pub mod auth;
 
 pub mod tracing;
\ No newline at end of file
@@ -0,0 +1,149 @@
+use crate::helpers::TestApp;
+use auth_service::domain::Email;
+use auth_service::utils::constants::MAX_FAILED_LOGIN_ATTEMPTS;
+use chrono::{Duration, Utc};
+use secrecy::Secret;
+
+async fn create_api_key(app: &TestApp, body: serde_json::Value) -> serde_json::Value {
+    let response = app.post("/api-keys", &body).await;
+    assert_eq!(response.status().as_u16(), 201);
+    response.json().await.expect("Failed to read API key")
+}
+
+#[tokio::test]
+async fn should_return_201_and_the_key_only_once() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let created = create_api_key(&app, serde_json::json!({ "name": "ci", "scopes": ["deploy"] })).await;
+    let key = created["key"].as_str().expect("No key");
+    assert!(key.starts_with(&format!("ak_{}_", created["prefix"].as_str().unwrap())));
+    assert_eq!(created["scopes"], serde_json::json!(["deploy"]));
+
+    let response = app.get("/api-keys").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let listed: Vec<serde_json::Value> = response.json().await.expect("Failed to read API keys");
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["id"], created["id"]);
+    assert!(listed[0].get("key").is_none());
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_input() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let test_cases = [
+        serde_json::json!({ "name": " " }),
+        serde_json::json!({ "name": "ci", "scopes": ["two words"] }),
+        serde_json::json!({ "name": "ci", "expiresAt": "2000-01-01T00:00:00Z" }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post("/api-keys", test_case).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", test_case);
+    }
+}
+
+#[tokio::test]
+async fn should_return_400_without_a_jwt() {
+    let app = TestApp::new().await;
+
+    let response = app.post("/api-keys", &serde_json::json!({ "name": "ci" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get("/api-keys").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_accept_an_api_key_in_place_of_a_jwt() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+    let created = create_api_key(&app, serde_json::json!({ "name": "ci", "scopes": ["deploy"] })).await;
+    let key = created["key"].as_str().unwrap();
+
+    let response = app.post("/verify-token", &serde_json::json!({ "token": key, "scope": "deploy" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_when_an_api_key_comes_without_a_scope() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+    let created = create_api_key(&app, serde_json::json!({ "name": "ci", "scopes": ["deploy"] })).await;
+
+    let response = app.post("/verify-token", &serde_json::json!({ "token": created["key"] })).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_once_the_owners_tokens_are_revoked() {
+    let app = TestApp::new().await;
+    let email = app.signup_and_login().await;
+    let created = create_api_key(&app, serde_json::json!({ "name": "ci", "scopes": ["deploy"] })).await;
+    let owner = app.app_state.user_store.get_user(&Email::parse(Secret::new(email)).unwrap()).await.unwrap();
+
+    app.app_state.banned_token_store.revoke_user_tokens(&owner.id, Utc::now()).await.unwrap();
+
+    let response = app.post("/verify-token", &serde_json::json!({ "token": created["key"], "scope": "deploy" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_423_while_the_owner_is_locked() {
+    let app = TestApp::new().await;
+    let email = app.signup_and_login().await;
+    let created = create_api_key(&app, serde_json::json!({ "name": "ci", "scopes": ["deploy"] })).await;
+    let parsed = Email::parse(Secret::new(email)).unwrap();
+
+    for _ in 0..MAX_FAILED_LOGIN_ATTEMPTS {
+        app.app_state.user_store
+            .record_failed_login(&parsed, MAX_FAILED_LOGIN_ATTEMPTS, Utc::now() + Duration::minutes(15))
+            .await
+            .unwrap();
+    }
+
+    let response = app.post("/verify-token", &serde_json::json!({ "token": created["key"], "scope": "deploy" })).await;
+    assert_eq!(response.status().as_u16(), 423);
+}
+
+#[tokio::test]
+async fn should_return_403_when_the_api_key_lacks_the_scope() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+    let created = create_api_key(&app, serde_json::json!({ "name": "ci", "scopes": ["read"] })).await;
+
+    let response = app.post("/verify-token", &serde_json::json!({ "token": created["key"], "scope": "deploy" })).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_return_401_for_a_wrong_or_revoked_api_key() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+    let created = create_api_key(&app, serde_json::json!({ "name": "ci", "scopes": ["deploy"] })).await;
+    let key = created["key"].as_str().unwrap();
+
+    // Same prefix, different secret
+    let wrong_key = format!("{}{}", &key[..key.len() - 1], if key.ends_with('a') { 'b' } else { 'a' });
+    let response = app.post("/verify-token", &serde_json::json!({ "token": wrong_key, "scope": "deploy" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.delete(&format!("/api-keys/{}", created["id"].as_str().unwrap())).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post("/verify-token", &serde_json::json!({ "token": key, "scope": "deploy" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_404_when_revoking_another_users_api_key() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+    let created = create_api_key(&app, serde_json::json!({ "name": "ci" })).await;
+
+    app.signup_and_login().await;
+    let response = app.delete(&format!("/api-keys/{}", created["id"].as_str().unwrap())).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,112 @@
+use reqwest::Method;
+
+use crate::helpers::TestApp;
+
+// A successful then a failed password login of a new user, with the admin logged in afterwards
+async fn user_with_two_logins(app: &TestApp) -> String {
+    let email = app.signup_and_login().await;
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": "wrongpassword" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.login_admin().await;
+    email
+}
+
+async fn list(app: &TestApp, query: &str) -> serde_json::Value {
+    let response = app.get(&format!("/admin/audit-events?{}", query)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Failed to read audit events")
+}
+
+#[tokio::test]
+async fn should_list_the_events_of_a_user_newest_first() {
+    let app = TestApp::new().await;
+    let email = user_with_two_logins(&app).await;
+
+    let body = list(&app, &format!("email={}&kind=login", email)).await;
+    let events = body["events"].as_array().unwrap();
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["outcome"], "failure");
+    assert_eq!(events[0]["detail"], "incorrect_password");
+    assert_eq!(events[1]["outcome"], "success");
+    assert_eq!(events[1]["detail"], "password");
+    assert!(events.iter().all(|event| event["email"] == email.as_str() && event["userId"].is_string()));
+    assert!(events[0]["id"].as_i64() > events[1]["id"].as_i64());
+    assert!(body["nextBefore"].is_null());
+}
+
+#[tokio::test]
+async fn should_filter_by_user_id_and_outcome() {
+    let app = TestApp::new().await;
+    let email = user_with_two_logins(&app).await;
+    let user_id = list(&app, &format!("email={}", email)).await["events"][0]["userId"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let body = list(&app, &format!("userId={}&kind=login&outcome=success", user_id)).await;
+    let events = body["events"].as_array().unwrap();
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["detail"], "password");
+}
+
+#[tokio::test]
+async fn should_page_through_events() {
+    let app = TestApp::new().await;
+    let email = user_with_two_logins(&app).await;
+
+    let first = list(&app, &format!("email={}&kind=login&limit=1", email)).await;
+    assert_eq!(first["events"][0]["outcome"], "failure");
+    let before = first["nextBefore"].as_i64().expect("No next page");
+
+    let second = list(&app, &format!("email={}&kind=login&limit=1&before={}", email, before)).await;
+    assert_eq!(second["events"][0]["outcome"], "success");
+
+    let before = second["nextBefore"].as_i64().expect("No next page");
+    let last = list(&app, &format!("email={}&kind=login&limit=1&before={}", email, before)).await;
+    assert_eq!(last["events"], serde_json::json!([]));
+    assert!(last["nextBefore"].is_null());
+}
+
+#[tokio::test]
+async fn should_return_400_for_an_invalid_query() {
+    let app = TestApp::new().await;
+    app.login_admin().await;
+
+    for query in ["kind=unknown", "outcome=unknown", "userId=not-a-uuid", "email=invalid", "limit=0", "limit=501"] {
+        let response = app.get(&format!("/admin/audit-events?{}", query)).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for query: {}", query);
+    }
+}
+
+#[tokio::test]
+async fn should_return_403_for_a_user_who_is_not_an_admin() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let response = app.get("/admin/audit-events").await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_return_403_for_an_impersonation_token() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    app.login_admin().await;
+    let token = app.impersonate(&email).await;
+
+    let response = app.with_token(Method::GET, "/admin/audit-events", &token).send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_return_400_without_a_jwt() {
+    let app = TestApp::new().await;
+
+    let response = app.get("/admin/audit-events").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
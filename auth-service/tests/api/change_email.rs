@@ -0,0 +1,164 @@
+use auth_service::domain::{AuditEventFilter, AuditEventKind, AuditOutcome};
+use reqwest::Method;
+
+use crate::helpers::{TestApp, PASSWORD};
+
+// Starts a change of the logged in user's email and returns the code sent to the new address
+async fn request_change(app: &TestApp, new_email: &str) -> String {
+    let response = app.post("/change-email", &serde_json::json!({ "newEmail": new_email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.email_client.last_email_to(new_email).expect("No code sent").content
+}
+
+fn wrong_code(code: &str) -> &'static str {
+    if code == "123456" { "654321" } else { "123456" }
+}
+
+#[tokio::test]
+async fn should_change_the_email_once_confirmed() {
+    let app = TestApp::new().await;
+    let old_email = app.signup().await;
+    let response = app.post_login(&serde_json::json!({ "email": old_email, "password": PASSWORD })).await;
+    let old_token = TestApp::jwt_cookie(&response).expect("No JWT cookie");
+    let new_email = TestApp::get_random_email();
+
+    let code = request_change(&app, &new_email).await;
+    assert!(app.email_client.last_email_to(&old_email).is_some());
+
+    let response = app.post("/change-email/confirm", &serde_json::json!({ "code": code })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(TestApp::jwt_cookie(&response).is_some());
+
+    let me: serde_json::Value = app.get("/me").await.json().await.unwrap();
+    assert_eq!(me["email"], new_email);
+
+    // Sessions of the old address are over, other devices included
+    let response = app.with_token(Method::GET, "/me", &old_token).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.login(&new_email).await;
+    let response = app.post_login(&serde_json::json!({ "email": old_email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_keep_api_keys_and_impersonations_with_the_user() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    app.login_admin().await;
+    app.impersonate(&email).await;
+    app.login(&email).await;
+    let response = app.post("/api-keys", &serde_json::json!({ "name": "ci" })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let new_email = TestApp::get_random_email();
+
+    let code = request_change(&app, &new_email).await;
+    let response = app.post("/change-email/confirm", &serde_json::json!({ "code": code })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let api_keys: serde_json::Value = app.get("/api-keys").await.json().await.unwrap();
+    assert_eq!(api_keys[0]["name"], "ci");
+    let archive: serde_json::Value = app.get("/me/export").await.json().await.unwrap();
+    assert_eq!(archive["sessions"]["impersonations"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn should_record_the_change_in_the_audit_log() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+    let new_email = TestApp::get_random_email();
+    let code = request_change(&app, &new_email).await;
+
+    app.post("/change-email/confirm", &serde_json::json!({ "code": wrong_code(&code) })).await;
+    app.post("/change-email/confirm", &serde_json::json!({ "code": code })).await;
+
+    let filter = AuditEventFilter { kind: Some(AuditEventKind::EmailChanged), ..Default::default() };
+    let records = app.app_state.audit_log.query(&filter, None, 10).await.unwrap();
+    let outcomes: Vec<(AuditOutcome, Option<String>)> = records
+        .into_iter()
+        .map(|record| (record.event.outcome, record.event.detail))
+        .collect();
+    assert_eq!(outcomes, vec![
+        (AuditOutcome::Success, None),
+        (AuditOutcome::Failure, Some("incorrect_code".to_owned())),
+    ]);
+}
+
+#[tokio::test]
+async fn should_drop_the_change_after_too_many_wrong_codes() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+    let new_email = TestApp::get_random_email();
+    let code = request_change(&app, &new_email).await;
+
+    for _ in 0..5 {
+        let response = app.post("/change-email/confirm", &serde_json::json!({ "code": wrong_code(&code) })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post("/change-email/confirm", &serde_json::json!({ "code": code })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // A new request starts over with a new code
+    let code = request_change(&app, &new_email).await;
+    let response = app.post("/change-email/confirm", &serde_json::json!({ "code": code })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_409_when_the_new_email_is_taken() {
+    let app = TestApp::new().await;
+    let taken = app.signup().await;
+    app.signup_and_login().await;
+
+    let response = app.post("/change-email", &serde_json::json!({ "newEmail": taken })).await;
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_input() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let response = app.post("/change-email", &serde_json::json!({ "newEmail": "not-an-email" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post("/change-email/confirm", &serde_json::json!({ "code": "abc" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_without_a_pending_change() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let response = app.post("/change-email/confirm", &serde_json::json!({ "code": "123456" })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_400_without_a_jwt() {
+    let app = TestApp::new().await;
+
+    let response = app.post("/change-email", &serde_json::json!({ "newEmail": TestApp::get_random_email() })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_403_for_an_impersonation_token() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    app.login_admin().await;
+    let token = app.impersonate(&email).await;
+
+    let response = app.with_token(Method::POST, "/change-email", &token)
+        .json(&serde_json::json!({ "newEmail": TestApp::get_random_email() }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+}
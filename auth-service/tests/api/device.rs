@@ -0,0 +1,126 @@
+use auth_service::ErrorResponse;
+
+use crate::helpers::TestApp;
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+async fn device_code(app: &TestApp) -> serde_json::Value {
+    let response = app.post("/device/code", &serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Failed to read device code")
+}
+
+async fn poll(app: &TestApp, device_code: &serde_json::Value) -> reqwest::Response {
+    app.post_form("/device/token", &[
+        ("grant_type", DEVICE_CODE_GRANT_TYPE),
+        ("device_code", device_code["device_code"].as_str().unwrap()),
+    ]).await
+}
+
+async fn error_of(response: reqwest::Response) -> String {
+    assert_eq!(response.status().as_u16(), 400);
+    response.json::<ErrorResponse>().await.expect("Could not deserialize response body to ErrorResponse").error
+}
+
+#[tokio::test]
+async fn should_issue_a_token_once_the_user_approves() {
+    let app = TestApp::new().await;
+    let device = device_code(&app).await;
+
+    assert_eq!(error_of(poll(&app, &device).await).await, "authorization_pending");
+
+    app.signup_and_login().await;
+    let response = app.post("/device/approve", &serde_json::json!({ "userCode": device["user_code"], "approved": true })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = poll(&app, &device).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token: serde_json::Value = response.json().await.expect("Failed to read token");
+    assert_eq!(token["token_type"], "Bearer");
+
+    let response = app.post("/verify-token", &serde_json::json!({ "token": token["access_token"] })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The device code is single use
+    assert_eq!(error_of(poll(&app, &device).await).await, "invalid_grant");
+}
+
+#[tokio::test]
+async fn should_return_access_denied_once_the_user_denies() {
+    let app = TestApp::new().await;
+    let device = device_code(&app).await;
+
+    app.signup_and_login().await;
+    let response = app.post("/device/approve", &serde_json::json!({ "userCode": device["user_code"], "approved": false })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(error_of(poll(&app, &device).await).await, "access_denied");
+    assert_eq!(error_of(poll(&app, &device).await).await, "invalid_grant");
+}
+
+#[tokio::test]
+async fn should_return_400_when_the_grant_was_already_decided() {
+    let app = TestApp::new().await;
+    let device = device_code(&app).await;
+    app.signup_and_login().await;
+    let response = app.post("/device/approve", &serde_json::json!({ "userCode": device["user_code"], "approved": false })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post("/device/approve", &serde_json::json!({ "userCode": device["user_code"], "approved": true })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error_of(poll(&app, &device).await).await, "access_denied");
+}
+
+#[tokio::test]
+async fn should_return_slow_down_when_polling_too_fast() {
+    let app = TestApp::new().await;
+    let device = device_code(&app).await;
+
+    assert_eq!(error_of(poll(&app, &device).await).await, "authorization_pending");
+    assert_eq!(error_of(poll(&app, &device).await).await, "slow_down");
+}
+
+#[tokio::test]
+async fn should_reject_bad_token_requests() {
+    let app = TestApp::new().await;
+    let device = device_code(&app).await;
+
+    let response = app.post_form("/device/token", &[
+        ("grant_type", "password"),
+        ("device_code", device["device_code"].as_str().unwrap()),
+    ]).await;
+    assert_eq!(error_of(response).await, "unsupported_grant_type");
+
+    let response = app.post_form("/device/token", &[
+        ("grant_type", DEVICE_CODE_GRANT_TYPE),
+        ("device_code", "not-a-device-code"),
+    ]).await;
+    assert_eq!(error_of(response).await, "invalid_grant");
+
+    let response = app.post_form("/device/token", &[
+        ("grant_type", DEVICE_CODE_GRANT_TYPE),
+        ("device_code", &uuid::Uuid::new_v4().to_string()),
+    ]).await;
+    assert_eq!(error_of(response).await, "invalid_grant");
+}
+
+#[tokio::test]
+async fn should_return_400_when_approving_without_a_jwt() {
+    let app = TestApp::new().await;
+    let device = device_code(&app).await;
+
+    let response = app.post("/device/approve", &serde_json::json!({ "userCode": device["user_code"], "approved": true })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_400_for_an_unknown_user_code() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let response = app.post("/device/approve", &serde_json::json!({ "userCode": "BCDF-GHJK", "approved": true })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
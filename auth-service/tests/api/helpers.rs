@@ -1,39 +1,72 @@
 #![allow(unused_imports)]
 #![allow(dead_code)]
-use auth_service::Application;
+use std::{path::PathBuf, sync::{Arc, Mutex}};
+
+use auth_service::{get_sqlite_pool, Application};
 use uuid::Uuid;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use auth_service::app_state::AppState;
-use auth_service::services::data_stores::hashmap_user_store::HashmapUserStore;
+use auth_service::app_state::{AppState, UserStoreType};
+use auth_service::domain::{Email, EmailClient, EmailPolicy, Password, Role, User};
+use auth_service::factory::build_app_state;
+use auth_service::utils::config::{AppConfig, EmailClientBackend, PasswordHashConfig, StoreBackend, UserCacheConfig};
+use auth_service::utils::constants::{DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS, JWT_COOKIE_NAME};
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+
+pub const PASSWORD: &str = "password123";
+
 pub  struct TestApp {
     pub address: String,
+    // Keeps the cookies the service sets, like a browser would
     pub http_client: reqwest::Client,
+    pub app_state: AppState,
+    pub email_client: Arc<RecordingEmailClient>,
 }
-/*
+
 impl TestApp {
     pub async fn new() -> Self {
-        let user_store = Arc::new(RwLock::new(HashmapUserStore::default()));
+        let app_state = build_app_state(&memory_config())
+            .await
+            .expect("Failed to build app state");
 
-        let app_state = AppState::new(user_store);
-    
-        let app = Application::build(app_state, "127.0.0.1:0")
+        Self::spawn(app_state).await
+    }
+
+    pub async fn with_user_store(user_store: UserStoreType) -> Self {
+        let mut app_state = build_app_state(&memory_config())
+            .await
+            .expect("Failed to build app state");
+        app_state.user_store = user_store;
+
+        Self::spawn(app_state).await
+    }
+
+    async fn spawn(mut app_state: AppState) -> Self {
+        let email_client = Arc::new(RecordingEmailClient::default());
+        app_state.email_client = email_client.clone();
+
+        let app = Application::build(app_state.clone(), "127.0.0.1:0")
             .await
             .expect("Failed to build app");
 
         let address = format!("http://{}", app.address.clone());
 
         // Run the auth service in a separate async task
-        // to avoid blocking the main test thread. 
+        // to avoid blocking the main test thread.
         #[allow(clippy::let_underscore_future)]
         let _ = tokio::spawn(app.run());
 
-        let http_client = reqwest::Client::new(); // Create a Reqwest http client instance
+        let http_client = reqwest::Client::builder()
+            .cookie_store(true)
+            .build()
+            .expect("Failed to build http client");
 
         // Create new `TestApp` instance and return it
         TestApp {
             address,
             http_client,
+            app_state,
+            email_client,
         }
     }
 
@@ -43,65 +76,214 @@ impl TestApp {
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-/*
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
         where Body: serde::Serialize {
+        self.post("/signup", body).await
+    }
+
+    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+        where Body: serde::Serialize {
+        self.post("/login", body).await
+    }
+
+    pub async fn get(&self, path: &str) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
-            .json(body)
+            .get(format!("{}{}", &self.address, path))
             .send()
             .await
             .expect("Failed to execute request.")
     }
-    */
-    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
-    where
-        Body: serde::Serialize,
-    {
+
+    pub async fn post<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+        where Body: serde::Serialize + ?Sized {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}{}", &self.address, path))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-    pub async fn post_login(&self) -> reqwest::Response {
+    pub async fn post_form<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+        where Body: serde::Serialize + ?Sized {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}{}", &self.address, path))
+            .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-    pub async fn post_logout(&self) -> reqwest::Response {
+    pub async fn patch<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+        where Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .patch(format!("{}{}", &self.address, path))
+            .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-    pub async fn post_verify_2fa(&self) -> reqwest::Response {
+    pub async fn delete(&self, path: &str) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .delete(format!("{}{}", &self.address, path))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-    pub async fn post_verify_token(&self) -> reqwest::Response {
-        self.http_client
-            .post(&format!("{}/verify-token", &self.address))
-            .send()
+    // Sends the request with `token` as the JWT cookie instead of the cookies of `http_client`
+    pub fn with_token(&self, method: reqwest::Method, path: &str, token: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .request(method, format!("{}{}", &self.address, path))
+            .header(reqwest::header::COOKIE, format!("{}={}", JWT_COOKIE_NAME, token))
+    }
+
+    // Signs up a user without 2FA and returns their email
+    pub async fn signup(&self) -> String {
+        let email = Self::get_random_email();
+        let response = self.post_signup(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+            "requires2FA": false
+        })).await;
+        assert_eq!(response.status().as_u16(), 201);
+        email
+    }
+
+    // Signs up a user without 2FA and logs them in on `http_client`
+    pub async fn signup_and_login(&self) -> String {
+        let email = self.signup().await;
+        self.login(&email).await;
+        email
+    }
+
+    pub async fn login(&self, email: &str) {
+        let response = self.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Adds an admin straight to the user store, there is no route to become one, and logs them in
+    pub async fn login_admin(&self) -> String {
+        let email = Self::get_random_email();
+        let mut user = User::new(Email::parse(Secret::new(email.clone())).unwrap(), false);
+        user.role = Role::Admin;
+        self.app_state
+            .user_store
+            .add_user(user, Some(Password::parse(Secret::new(PASSWORD.to_owned())).unwrap()))
             .await
-            .expect("Failed to execute request.")
-    }   
+            .expect("Failed to add admin");
+        self.login(&email).await;
+        email
+    }
+
+    // Impersonates `email` as the admin logged in on `http_client` and returns the impersonation token
+    pub async fn impersonate(&self, email: &str) -> String {
+        let response = self.post("/admin/impersonate", &serde_json::json!({ "email": email, "reason": "support" })).await;
+        assert_eq!(response.status().as_u16(), 201);
+        let body: serde_json::Value = response.json().await.expect("Failed to read impersonation");
+        body["token"].as_str().expect("No impersonation token").to_owned()
+    }
+
+    // The path of the first link to the service in the last email sent to `recipient`
+    pub fn link_in_last_email_to(&self, recipient: &str) -> String {
+        let email = self.email_client.last_email_to(recipient).expect("No email sent");
+        let start = email.content.find("http").expect("No link in email");
+        let link = email.content[start..].split_whitespace().next().unwrap();
+        let path_start = link.find("//").map(|i| i + 2).unwrap();
+        link[path_start..].find('/').map(|i| link[path_start + i..].to_owned()).expect("No path in link")
+    }
+
+    pub fn jwt_cookie(response: &reqwest::Response) -> Option<String> {
+        response.cookies()
+            .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+            .map(|cookie| cookie.value().to_owned())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub content: String,
+}
+
+// Keeps every email instead of sending it, so tests can read codes and links
+#[derive(Default)]
+pub struct RecordingEmailClient {
+    sent: Mutex<Vec<SentEmail>>,
+}
+
+impl RecordingEmailClient {
+    pub fn last_email_to(&self, recipient: &str) -> Option<SentEmail> {
+        self.sent.lock().unwrap().iter().rev().find(|email| email.recipient == recipient).cloned()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for RecordingEmailClient {
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
+        self.sent.lock().unwrap().push(SentEmail {
+            recipient: recipient.as_ref().expose_secret().to_owned(),
+            subject: subject.to_owned(),
+            content: content.to_owned(),
+        });
+        Ok(())
+    }
+}
+
+// Every store in memory and no real emails, so tests need no external services
+fn memory_config() -> AppConfig {
+    AppConfig {
+        user_store: StoreBackend::Memory,
+        banned_token_store: StoreBackend::Memory,
+        two_fa_code_store: StoreBackend::Memory,
+        api_key_store: StoreBackend::Memory,
+        magic_link_store: StoreBackend::Memory,
+        password_reset_store: StoreBackend::Memory,
+        device_grant_store: StoreBackend::Memory,
+        impersonation_store: StoreBackend::Memory,
+        email_change_store: StoreBackend::Memory,
+        audit_log: StoreBackend::Memory,
+        email_client: EmailClientBackend::Mock,
+        password_hash: PasswordHashConfig::default(),
+        email_policy: EmailPolicy::default(),
+        user_cache: UserCacheConfig::default(),
+        two_fa_max_pending_attempts: DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS,
+    }
+}
+
+// A migrated SQLite database in a file of its own, the file is deleted when it's dropped
+pub struct TestSqliteDb {
+    pub pool: SqlitePool,
+    path: PathBuf,
+}
+
+impl TestSqliteDb {
+    pub async fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("auth-test-{}.db", Uuid::new_v4()));
+        let pool = get_sqlite_pool(&Secret::new(format!("sqlite://{}", path.display())))
+            .await
+            .expect("Failed to create SQLite connection pool");
+        sqlx::migrate!("./migrations_sqlite")
+            .run(&pool)
+            .await
+            .expect("Failed to run SQLite migrations");
+        Self { pool, path }
+    }
+}
+
+impl Drop for TestSqliteDb {
+    fn drop(&mut self) {
+        // The journal files are only there while connections are open
+        for suffix in ["", "-journal", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+        }
+    }
 }
-*/
\ No newline at end of file
@@ -0,0 +1,125 @@
+use auth_service::domain::{AuditEventFilter, AuditEventKind, Email, UserId};
+use reqwest::Method;
+use secrecy::Secret;
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn should_return_403_when_an_impersonation_token_creates_an_api_key() {
+    let app = TestApp::new().await;
+    let user = app.signup().await;
+    app.login_admin().await;
+    let token = app.impersonate(&user).await;
+
+    let response = app.with_token(Method::POST, "/api-keys", &token)
+        .json(&serde_json::json!({ "name": "ci" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_return_403_when_an_impersonation_token_approves_a_device() {
+    let app = TestApp::new().await;
+    let user = app.signup().await;
+    app.login_admin().await;
+    let token = app.impersonate(&user).await;
+
+    let device: serde_json::Value = app.post("/device/code", &serde_json::json!({}))
+        .await
+        .json()
+        .await
+        .expect("Failed to read device code");
+
+    let response = app.with_token(Method::POST, "/device/approve", &token)
+        .json(&serde_json::json!({ "userCode": device["user_code"], "approved": true }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_let_an_impersonation_token_read_the_user() {
+    let app = TestApp::new().await;
+    let user = app.signup().await;
+    app.login_admin().await;
+    let token = app.impersonate(&user).await;
+
+    let response = app.with_token(Method::GET, "/me", &token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.expect("Failed to read user");
+    assert_eq!(body["email"], user);
+}
+
+#[tokio::test]
+async fn should_return_403_when_an_impersonation_token_updates_the_user() {
+    let app = TestApp::new().await;
+    let user = app.signup().await;
+    app.login_admin().await;
+    let token = app.impersonate(&user).await;
+
+    let response = app.with_token(Method::PATCH, "/me", &token)
+        .json(&serde_json::json!({ "displayName": "Mallory" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_return_403_when_an_impersonation_token_revokes_an_api_key() {
+    let app = TestApp::new().await;
+    let user = app.signup_and_login().await;
+    let response = app.post("/api-keys", &serde_json::json!({ "name": "ci" })).await;
+    let created: serde_json::Value = response.json().await.expect("Failed to read API key");
+    app.login_admin().await;
+    let token = app.impersonate(&user).await;
+
+    let path = format!("/api-keys/{}", created["id"].as_str().unwrap());
+    let response = app.with_token(Method::DELETE, &path, &token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_audit_the_start_and_end_of_an_impersonation() {
+    let app = TestApp::new().await;
+    let user = app.signup().await;
+    let admin = app.login_admin().await;
+    let token = app.impersonate(&user).await;
+
+    let response = app.post("/admin/impersonate/end", &serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let admin_id = user_id(&app, &admin).await;
+    let filter = AuditEventFilter { user_id: Some(user_id(&app, &user).await), ..Default::default() };
+    let events: Vec<(AuditEventKind, Option<String>)> = app.app_state.audit_log
+        .query(&filter, None, 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|record| record.event.kind.as_ref().starts_with("impersonation_"))
+        .map(|record| (record.event.kind, record.event.detail))
+        .collect();
+    assert_eq!(events, vec![
+        (AuditEventKind::ImpersonationEnded, Some(admin_id.to_string())),
+        (AuditEventKind::ImpersonationStarted, Some(admin_id.to_string())),
+    ]);
+}
+
+async fn user_id(app: &TestApp, email: &str) -> UserId {
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    app.app_state.user_store.get_user(&email).await.unwrap().id
+}
@@ -0,0 +1,79 @@
+use reqwest::Method;
+
+use crate::helpers::{TestApp, PASSWORD};
+
+fn bcrypt_hash(password: &str) -> String {
+    bcrypt::hash(password, 4).unwrap()
+}
+
+#[tokio::test]
+async fn should_import_users_who_then_log_in_with_their_old_password() {
+    let app = TestApp::new().await;
+    let existing = app.signup().await;
+    app.login_admin().await;
+    let email = TestApp::get_random_email();
+
+    let response = app.post("/admin/users/import", &serde_json::json!({ "users": [
+        { "email": email, "passwordHash": bcrypt_hash(PASSWORD) },
+        { "email": existing, "passwordHash": bcrypt_hash("other-password") },
+    ]})).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["imported"], 1);
+    assert_eq!(body["alreadyExists"], serde_json::json!([existing]));
+
+    app.login(&email).await;
+    // The existing user keeps their password
+    app.login(&existing).await;
+}
+
+#[tokio::test]
+async fn should_import_nothing_when_an_entry_is_invalid() {
+    let app = TestApp::new().await;
+    app.login_admin().await;
+    let email = TestApp::get_random_email();
+
+    let response = app.post("/admin/users/import", &serde_json::json!({ "users": [
+        { "email": email, "passwordHash": bcrypt_hash(PASSWORD) },
+        { "email": TestApp::get_random_email(), "passwordHash": "plaintext" },
+    ]})).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_403_for_a_user_who_is_not_an_admin() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let response = app.post("/admin/users/import", &serde_json::json!({ "users": [] })).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_return_403_for_an_impersonation_token() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    app.login_admin().await;
+    let token = app.impersonate(&email).await;
+
+    let response = app.with_token(Method::POST, "/admin/users/import", &token)
+        .json(&serde_json::json!({ "users": [] }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_return_400_without_a_jwt() {
+    let app = TestApp::new().await;
+
+    let response = app.post("/admin/users/import", &serde_json::json!({ "users": [] })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,6 +1,13 @@
 #![allow(unused_imports)]
-use crate::helpers::TestApp;
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::helpers::{TestApp, PASSWORD};
 use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::domain::Email;
+use auth_service::services::rate_limiter::RateLimiter;
+use auth_service::utils::constants::{MAX_FAILED_LOGIN_ATTEMPTS, MAX_FAILED_LOGINS_PER_IP};
+use chrono::{Duration, Utc};
+use secrecy::Secret;
 /*
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
@@ -64,4 +71,86 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     assert!(!auth_cookie.value().is_empty());
 }
-*/
\ No newline at end of file
+*/
+
+async fn post_wrong_password(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({ "email": email, "password": "wrongpassword" })).await
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_failed_logins_from_an_ip() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+
+    for _ in 0..MAX_FAILED_LOGINS_PER_IP {
+        assert_eq!(post_wrong_password(&app, &email).await.status().as_u16(), 401);
+    }
+
+    // Refused before the password is checked, even a correct one
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(TestApp::jwt_cookie(&response).is_none());
+}
+
+#[tokio::test]
+async fn should_not_let_a_single_ip_lock_an_account() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+
+    for _ in 0..MAX_FAILED_LOGIN_ATTEMPTS {
+        post_wrong_password(&app, &email).await;
+    }
+
+    let user = app.app_state.user_store
+        .get_user(&Email::parse(Secret::new(email)).unwrap())
+        .await
+        .unwrap();
+    assert!(!user.is_locked(Utc::now()));
+}
+
+#[tokio::test]
+async fn should_count_failed_logins_to_unknown_accounts_against_the_ip() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+
+    for _ in 0..MAX_FAILED_LOGINS_PER_IP {
+        post_wrong_password(&app, &TestApp::get_random_email()).await;
+    }
+
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn should_return_423_if_the_account_is_locked() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    let parsed = Email::parse(Secret::new(email.clone())).unwrap();
+
+    // As if the failures came from several IPs
+    for _ in 0..MAX_FAILED_LOGIN_ATTEMPTS {
+        app.app_state.user_store
+            .record_failed_login(&parsed, MAX_FAILED_LOGIN_ATTEMPTS, Utc::now() + Duration::minutes(15))
+            .await
+            .unwrap();
+    }
+
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 423);
+}
+
+#[tokio::test]
+async fn should_lift_the_ip_limit_once_the_failures_leave_the_window() {
+    let limiter = RateLimiter::new(std::num::NonZeroUsize::new(10).unwrap(), 2, Duration::minutes(15));
+    let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let other_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    let start = Utc::now();
+
+    limiter.record(ip, start);
+    limiter.record(ip, start + Duration::minutes(1));
+    assert!(limiter.is_limited(&ip, start + Duration::minutes(2)));
+    assert!(!limiter.is_limited(&other_ip, start + Duration::minutes(2)));
+
+    // The first failure is out of the window
+    assert!(!limiter.is_limited(&ip, start + Duration::minutes(15)));
+}
@@ -0,0 +1,187 @@
+use crate::helpers::TestApp;
+use auth_service::domain::Email;
+use auth_service::utils::constants::{MAX_FAILED_LOGIN_ATTEMPTS, MAX_FAILED_LOGINS_PER_IP, MAX_LOGIN_CODES_PER_EMAIL,
+                                     MAX_LOGIN_CODES_PER_IP};
+use chrono::{Duration, Utc};
+use secrecy::Secret;
+
+async fn signup_without_password(app: &TestApp) -> String {
+    let email = TestApp::get_random_email();
+    let response = app.post_signup(&serde_json::json!({ "email": email, "requires2FA": false })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    email
+}
+
+async fn request_code(app: &TestApp, email: &str) -> String {
+    let response = app.post("/login/code", &serde_json::json!({ "email": email })).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let body: serde_json::Value = response.json().await.expect("Failed to read login attempt");
+    body["loginAttemptId"].as_str().expect("No login attempt id").to_owned()
+}
+
+#[tokio::test]
+async fn should_log_in_a_passwordless_account_with_the_emailed_code() {
+    let app = TestApp::new().await;
+    let email = signup_without_password(&app).await;
+
+    let login_attempt_id = request_code(&app, &email).await;
+    let code = app.email_client.last_email_to(&email).expect("No code sent").content;
+
+    let response = app.post("/verify-2fa", &serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(TestApp::jwt_cookie(&response).is_some());
+
+    let response = app.get("/me").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_for_a_wrong_code() {
+    let app = TestApp::new().await;
+    let email = signup_without_password(&app).await;
+    let login_attempt_id = request_code(&app, &email).await;
+    let code = app.email_client.last_email_to(&email).unwrap().content;
+    let wrong_code = if code == "123456" { "654321" } else { "123456" };
+
+    let response = app.post("/verify-2fa", &serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": wrong_code
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_206_without_an_email_for_an_unknown_account() {
+    let app = TestApp::new().await;
+    let email = TestApp::get_random_email();
+
+    let login_attempt_id = request_code(&app, &email).await;
+    assert!(app.email_client.last_email_to(&email).is_none());
+
+    // The login attempt id it gets can never be verified
+    let response = app.post("/verify-2fa", &serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": "123456"
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_400_for_an_invalid_email() {
+    let app = TestApp::new().await;
+
+    let response = app.post("/login/code", &serde_json::json!({ "email": "not-an-email" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_when_logging_in_a_passwordless_account_with_a_password() {
+    let app = TestApp::new().await;
+    let email = signup_without_password(&app).await;
+
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": "password123" })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+async fn post_wrong_code(app: &TestApp, email: &str, login_attempt_id: &str) -> reqwest::Response {
+    let code = app.email_client.last_email_to(email).map(|sent| sent.content);
+    let wrong_code = if code.as_deref() == Some("123456") { "654321" } else { "123456" };
+
+    app.post("/verify-2fa", &serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": wrong_code
+    })).await
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_wrong_codes_from_an_ip() {
+    let app = TestApp::new().await;
+    let email = signup_without_password(&app).await;
+
+    for _ in 0..MAX_FAILED_LOGINS_PER_IP {
+        let login_attempt_id = request_code(&app, &email).await;
+        assert_eq!(post_wrong_code(&app, &email, &login_attempt_id).await.status().as_u16(), 401);
+    }
+
+    // Refused before the code is checked, even a correct one
+    let login_attempt_id = request_code(&app, &email).await;
+    let code = app.email_client.last_email_to(&email).unwrap().content;
+    let response = app.post("/verify-2fa", &serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(TestApp::jwt_cookie(&response).is_none());
+
+    // Password logins share the limit
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": "password123" })).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn should_lock_the_account_after_too_many_wrong_codes() {
+    let app = TestApp::new().await;
+    let email = signup_without_password(&app).await;
+    let parsed = Email::parse(Secret::new(email.clone())).unwrap();
+
+    // As if the other failures came from other IPs
+    for _ in 0..MAX_FAILED_LOGIN_ATTEMPTS - 1 {
+        app.app_state.user_store
+            .record_failed_login(&parsed, MAX_FAILED_LOGIN_ATTEMPTS, Utc::now() + Duration::minutes(15))
+            .await
+            .unwrap();
+    }
+    let login_attempt_id = request_code(&app, &email).await;
+    assert_eq!(post_wrong_code(&app, &email, &login_attempt_id).await.status().as_u16(), 401);
+    assert!(app.app_state.user_store.get_user(&parsed).await.unwrap().is_locked(Utc::now()));
+
+    let login_attempt_id = request_code(&app, &email).await;
+    let code = app.email_client.last_email_to(&email).unwrap().content;
+    let response = app.post("/verify-2fa", &serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 423);
+    assert!(TestApp::jwt_cookie(&response).is_none());
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_codes_to_an_email() {
+    let app = TestApp::new().await;
+    let email = signup_without_password(&app).await;
+    let unknown_email = TestApp::get_random_email();
+
+    // Unknown emails are limited the same way
+    for email in [&email, &unknown_email] {
+        for _ in 0..MAX_LOGIN_CODES_PER_EMAIL {
+            request_code(&app, email).await;
+        }
+
+        let response = app.post("/login/code", &serde_json::json!({ "email": email })).await;
+        assert_eq!(response.status().as_u16(), 429);
+    }
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_codes_requested_from_an_ip() {
+    let app = TestApp::new().await;
+
+    for _ in 0..MAX_LOGIN_CODES_PER_IP {
+        request_code(&app, &TestApp::get_random_email()).await;
+    }
+
+    let response = app.post("/login/code", &serde_json::json!({ "email": TestApp::get_random_email() })).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
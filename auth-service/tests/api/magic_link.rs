@@ -0,0 +1,93 @@
+use crate::helpers::TestApp;
+
+// The token of the link, as the confirmation page posts it
+fn token_of(link: &str) -> String {
+    link.split_once("token=").expect("No token in link").1.to_owned()
+}
+
+#[tokio::test]
+async fn should_log_in_with_the_emailed_link() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+
+    let response = app.post("/login/magic-link", &serde_json::json!({ "email": email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let link = app.link_in_last_email_to(&email);
+    assert!(link.starts_with("/login/magic-link/callback?token="));
+
+    // Following the link only asks to confirm the login
+    let response = app.get(&link).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.expect("Failed to read confirmation page");
+    assert!(page.contains(r#"method="post""#));
+    assert!(page.contains(&token_of(&link)));
+    assert_eq!(app.get("/me").await.status().as_u16(), 400);
+
+    // The confirmation redirects to the home page with the JWT cookie set
+    let response = app.post_form("/login/magic-link/callback", &[("token", token_of(&link))]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get("/me").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_keep_the_link_usable_when_it_is_only_fetched() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    app.post("/login/magic-link", &serde_json::json!({ "email": email })).await;
+    let link = app.link_in_last_email_to(&email);
+
+    // As a mail scanner would
+    app.get(&link).await;
+    app.get(&link).await;
+
+    let response = app.post_form("/login/magic-link/callback", &[("token", token_of(&link))]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.get("/me").await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_when_the_link_is_used_twice() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    app.post("/login/magic-link", &serde_json::json!({ "email": email })).await;
+    let token = token_of(&app.link_in_last_email_to(&email));
+
+    app.post_form("/login/magic-link/callback", &[("token", &token)]).await;
+    let response = app.post_form("/login/magic-link/callback", &[("token", &token)]).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_for_an_invalid_token() {
+    let app = TestApp::new().await;
+
+    let response = app.get("/login/magic-link/callback?token=invalid").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_form("/login/magic-link/callback", &[("token", "invalid")]).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_200_without_an_email_for_an_unknown_account() {
+    let app = TestApp::new().await;
+    let email = TestApp::get_random_email();
+
+    let response = app.post("/login/magic-link", &serde_json::json!({ "email": email })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app.email_client.last_email_to(&email).is_none());
+}
+
+#[tokio::test]
+async fn should_return_400_for_an_invalid_email() {
+    let app = TestApp::new().await;
+
+    let response = app.post("/login/magic-link", &serde_json::json!({ "email": "not-an-email" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,9 +1,22 @@
+mod api_keys;
+mod audit_events;
+mod change_email;
+mod device;
 mod helpers;
 //mod routes;
+mod impersonation;
+mod import_users;
 mod login;
+mod login_code;
 mod logout;
+mod magic_link;
+mod me;
+mod me_export;
+mod password_hasher;
+mod password_reset;
 mod root;
 mod signup;
+mod store_conformance;
 mod verify_2fa;
 mod verify_token;
 
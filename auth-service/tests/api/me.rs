@@ -0,0 +1,78 @@
+use reqwest::Method;
+
+use crate::helpers::{TestApp, PASSWORD};
+
+#[tokio::test]
+async fn should_return_the_logged_in_user() {
+    let app = TestApp::new().await;
+    let email = app.signup_and_login().await;
+
+    let response = app.get("/me").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body: serde_json::Value = response.json().await.expect("Failed to read user");
+    assert_eq!(body["email"], email);
+    assert_eq!(body["displayName"], serde_json::Value::Null);
+    assert_eq!(body["requires2FA"], false);
+    assert_eq!(body["role"], "user");
+    assert!(body["lastLoginAt"].is_string());
+}
+
+#[tokio::test]
+async fn should_set_keep_and_clear_the_display_name() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let response = app.patch("/me", &serde_json::json!({ "displayName": "  Alice  " })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["displayName"], "Alice");
+
+    // A missing field is left unchanged
+    let body: serde_json::Value = app.patch("/me", &serde_json::json!({})).await.json().await.unwrap();
+    assert_eq!(body["displayName"], "Alice");
+
+    let body: serde_json::Value = app.patch("/me", &serde_json::json!({ "displayName": null })).await.json().await.unwrap();
+    assert_eq!(body["displayName"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn should_return_400_for_an_invalid_display_name() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+
+    let test_cases = [
+        serde_json::json!({ "displayName": "   " }),
+        serde_json::json!({ "displayName": "a".repeat(101) }),
+        serde_json::json!({ "displayName": "line\nbreak" }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.patch("/me", test_case).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", test_case);
+    }
+}
+
+#[tokio::test]
+async fn should_return_400_without_a_jwt() {
+    let app = TestApp::new().await;
+
+    assert_eq!(app.get("/me").await.status().as_u16(), 400);
+    assert_eq!(app.patch("/me", &serde_json::json!({ "displayName": "Alice" })).await.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_for_an_invalid_or_logged_out_jwt() {
+    let app = TestApp::new().await;
+
+    let response = app.with_token(Method::GET, "/me", "invalid").send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let email = app.signup().await;
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+    let token = TestApp::jwt_cookie(&response).expect("No JWT cookie");
+    app.post("/logout", &serde_json::json!({})).await;
+
+    let response = app.with_token(Method::GET, "/me", &token).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,150 @@
+use reqwest::Method;
+
+use crate::helpers::{TestApp, PASSWORD};
+
+async fn export(app: &TestApp) -> serde_json::Value {
+    let response = app.get("/me/export").await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Failed to read export")
+}
+
+#[tokio::test]
+async fn should_export_every_section() {
+    let app = TestApp::new().await;
+    let email = app.signup_and_login().await;
+    let new_email = TestApp::get_random_email();
+    let response = app.post("/api-keys", &serde_json::json!({ "name": "ci", "scopes": ["deploy"] })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response = app.post("/change-email", &serde_json::json!({ "newEmail": new_email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let archive = export(&app).await;
+
+    assert!(archive["exportedAt"].is_string());
+    assert_eq!(archive["user"]["email"], email.as_str());
+    assert_eq!(archive["user"]["hasPassword"], true);
+    assert!(archive["user"].get("passwordHash").is_none());
+    assert_eq!(archive["twoFactor"]["requires2FA"], false);
+    assert!(archive["sessions"]["lastLoginAt"].is_string());
+    assert_eq!(archive["pendingEmailChange"]["newEmail"], new_email.as_str());
+    assert!(archive["pendingEmailChange"].get("code").is_none());
+    assert_eq!(archive["apiKeys"][0]["name"], "ci");
+    assert!(archive["apiKeys"][0].get("key").is_none());
+    let events = archive["auditEvents"].as_array().unwrap();
+    assert_eq!(events.last().unwrap()["kind"], "signup");
+    assert!(events.iter().any(|event| event["kind"] == "login" && event["outcome"] == "success"));
+}
+
+#[tokio::test]
+async fn should_export_events_recorded_with_the_email_only() {
+    let app = TestApp::new().await;
+    let email = TestApp::get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": PASSWORD,
+        "requires2FA": true
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // A wrong code ends the login attempt, the second one gets a new code
+    for correct in [false, true] {
+        let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+        assert_eq!(response.status().as_u16(), 206);
+        let body: serde_json::Value = response.json().await.unwrap();
+        let code = app.email_client.last_email_to(&email).expect("No code sent").content;
+        let two_fa_code = match correct {
+            true => code.as_str(),
+            false if code == "123456" => "654321",
+            false => "123456",
+        };
+        app.post("/verify-2fa", &serde_json::json!({
+            "email": email,
+            "loginAttemptId": body["loginAttemptId"],
+            "2FACode": two_fa_code
+        })).await;
+    }
+
+    let archive = export(&app).await;
+
+    // Newest first, the failure recorded by email alone in its place among the user's events
+    let events: Vec<(&str, &str)> = archive["auditEvents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|event| event["kind"] == "2fa_verified")
+        .map(|event| (event["outcome"].as_str().unwrap(), event["detail"].as_str().unwrap_or_default()))
+        .collect();
+    assert_eq!(events, vec![("success", ""), ("failure", "incorrect_code")]);
+}
+
+#[tokio::test]
+async fn should_not_export_events_of_the_previous_holder_of_the_email() {
+    let app = TestApp::new().await;
+    let email = app.signup_and_login().await;
+    let new_email = TestApp::get_random_email();
+    let response = app.post("/change-email", &serde_json::json!({ "newEmail": new_email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let code = app.email_client.last_email_to(&new_email).expect("No code sent").content;
+    let response = app.post("/change-email/confirm", &serde_json::json!({ "code": code })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Recorded with the freed email alone, before anyone holds it again
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // A new account takes over the freed email
+    let response = app.post_signup(&serde_json::json!({ "email": email, "password": PASSWORD, "requires2FA": false })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.login(&email).await;
+
+    let archive = export(&app).await;
+
+    let events = archive["auditEvents"].as_array().unwrap();
+    assert_eq!(events.iter().filter(|event| event["kind"] == "signup").count(), 1);
+    assert!(events.iter().all(|event| event["kind"] != "email_changed"));
+    assert!(events.iter().all(|event| event["detail"] != "unknown_user"));
+}
+
+#[tokio::test]
+async fn should_export_events_recorded_with_the_new_email_only_from_the_change() {
+    let app = TestApp::new().await;
+    app.signup_and_login().await;
+    let new_email = TestApp::get_random_email();
+    let response = app.post("/change-email", &serde_json::json!({ "newEmail": new_email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let code = app.email_client.last_email_to(&new_email).expect("No code sent").content;
+
+    // Recorded with the new email alone while it doesn't belong to anyone yet
+    let response = app.post_login(&serde_json::json!({ "email": new_email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post("/change-email/confirm", &serde_json::json!({ "code": code })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let archive = export(&app).await;
+
+    let events = archive["auditEvents"].as_array().unwrap();
+    assert!(events.iter().any(|event| event["kind"] == "email_changed"));
+    assert!(events.iter().all(|event| event["detail"] != "unknown_user"));
+}
+
+#[tokio::test]
+async fn should_return_403_for_an_impersonation_token() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    app.login_admin().await;
+    let token = app.impersonate(&email).await;
+
+    let response = app.with_token(Method::GET, "/me/export", &token).send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_return_400_without_a_jwt() {
+    let app = TestApp::new().await;
+
+    let response = app.get("/me/export").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
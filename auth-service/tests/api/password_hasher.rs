@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use auth_service::{
+    domain::{Email, Password, PasswordHasher, User, UserStore},
+    services::{argon2_password_hasher::Argon2PasswordHasher, data_stores::hashmap_user_store::HashmapUserStore},
+    utils::config::PasswordHashConfig,
+};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::helpers::TestApp;
+
+fn hasher(pepper: Option<&str>) -> Argon2PasswordHasher {
+    Argon2PasswordHasher::new(&PasswordHashConfig::default(), pepper.map(|pepper| Secret::new(pepper.to_owned())))
+        .unwrap()
+}
+
+fn password(password: &str) -> Password {
+    Password::parse(Secret::new(password.to_owned())).unwrap()
+}
+
+#[tokio::test]
+async fn should_verify_and_rehash_unpeppered_hashes_once_a_pepper_is_set() {
+    let unpeppered = hasher(None).hash_password(&password("password123")).await.unwrap();
+    let peppered_hasher = hasher(Some("pepper-secret"));
+
+    assert!(peppered_hasher.verify_password(&unpeppered, &password("password123")).await.is_ok());
+    assert!(peppered_hasher.verify_password(&unpeppered, &password("password456")).await.is_err());
+    assert!(peppered_hasher.needs_rehash(&unpeppered));
+
+    let rehashed = peppered_hasher.hash_password(&password("password123")).await.unwrap();
+    assert!(peppered_hasher.verify_password(&rehashed, &password("password123")).await.is_ok());
+    assert!(!peppered_hasher.needs_rehash(&rehashed));
+}
+
+#[tokio::test]
+async fn should_not_verify_peppered_hashes_without_the_pepper() {
+    let peppered = hasher(Some("pepper-secret")).hash_password(&password("password123")).await.unwrap();
+
+    assert!(hasher(None).verify_password(&peppered, &password("password123")).await.is_err());
+    assert!(hasher(Some("other-secret")).verify_password(&peppered, &password("password123")).await.is_err());
+}
+
+#[tokio::test]
+async fn should_log_in_users_imported_with_unpeppered_hashes_and_pepper_their_hash() {
+    let store = HashmapUserStore::new(Arc::new(hasher(Some("pepper-secret"))));
+    let imported_hash = hasher(None).hash_password(&password("password123")).await.unwrap();
+    let email = Email::parse(Secret::new(TestApp::get_random_email())).unwrap();
+    store.import_user(User::new(email.clone(), false), imported_hash)
+        .await
+        .unwrap();
+
+    assert!(store.validate_user(&email, &password("password123")).await.is_ok());
+
+    let password_hash = store.get_user(&email).await.unwrap().password_hash.unwrap();
+    assert!(password_hash.as_ref().expose_secret().contains("keyid="));
+    assert!(store.validate_user(&email, &password("password123")).await.is_ok());
+}
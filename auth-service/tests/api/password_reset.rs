@@ -0,0 +1,70 @@
+use auth_service::domain::{Email, PasswordResetToken};
+use reqwest::Method;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::helpers::{TestApp, PASSWORD};
+
+// Clears the password of `email` and hands out a reset token, as `auth-admin force-password-reset` does
+async fn force_password_reset(app: &TestApp, email: &str) -> String {
+    let user = app.app_state.user_store.get_user(&Email::parse(Secret::new(email.to_owned())).unwrap()).await.unwrap();
+    app.app_state.user_store.clear_password(&user.id).await.unwrap();
+    let token = PasswordResetToken::default();
+    app.app_state.password_reset_store.add_password_reset(&user.id, token.clone()).await.unwrap();
+    token.as_ref().expose_secret().to_owned()
+}
+
+#[tokio::test]
+async fn should_set_a_new_password_with_the_token() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+    let old_token = TestApp::jwt_cookie(&response).expect("No JWT cookie");
+    let token = force_password_reset(&app, &email).await;
+
+    let response = app.post("/password-reset", &serde_json::json!({ "token": token, "password": "new-password" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": "new-password" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app.with_token(Method::GET, "/me", &old_token).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_accept_a_token_only_once() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    let token = force_password_reset(&app, &email).await;
+
+    let response = app.post("/password-reset", &serde_json::json!({ "token": token, "password": "new-password" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post("/password-reset", &serde_json::json!({ "token": token, "password": "other-password" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&serde_json::json!({ "email": email, "password": "new-password" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_keep_the_token_when_the_password_is_rejected() {
+    let app = TestApp::new().await;
+    let email = app.signup().await;
+    let token = force_password_reset(&app, &email).await;
+
+    let response = app.post("/password-reset", &serde_json::json!({ "token": token, "password": "short" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app.post("/password-reset", &serde_json::json!({ "token": token, "password": "new-password" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_for_an_unknown_or_malformed_token() {
+    let app = TestApp::new().await;
+
+    for token in [uuid::Uuid::new_v4().to_string(), "not-a-token".to_owned()] {
+        let response = app.post("/password-reset", &serde_json::json!({ "token": token, "password": "new-password" })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
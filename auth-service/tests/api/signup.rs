@@ -120,3 +120,77 @@ async fn should_return_409_if_email_already_exists() {
 
 }
 */
+
+mod concurrent_signup {
+    use std::sync::Arc;
+
+    use auth_service::{
+        app_state::{PasswordHasherType, UserStoreType},
+        get_postgres_pool,
+        services::{argon2_password_hasher::Argon2PasswordHasher,
+                   data_stores::{postgres_user_store::PostgresUserStore, sqlite_user_store::SqliteUserStore}},
+        utils::{config::PasswordHashConfig, constants::DATABASE_URL},
+    };
+    use tokio::task::JoinSet;
+
+    use crate::helpers::{TestApp, TestSqliteDb};
+
+    const SIGNUPS: usize = 16;
+
+    fn password_hasher() -> PasswordHasherType {
+        Arc::new(Argon2PasswordHasher::new(&PasswordHashConfig::default(), None).unwrap())
+    }
+
+    // Fires the same signup concurrently, exactly one must win and the rest get a 409
+    async fn assert_single_winner(app: TestApp) {
+        let app = Arc::new(app);
+        let body = serde_json::json!({
+            "email": TestApp::get_random_email(),
+            "password": "password123",
+            "requires2FA": false
+        });
+
+        let mut signups = JoinSet::new();
+        for _ in 0..SIGNUPS {
+            let app = app.clone();
+            let body = body.clone();
+            signups.spawn(async move { app.post_signup(&body).await.status().as_u16() });
+        }
+
+        let mut statuses = Vec::new();
+        while let Some(status) = signups.join_next().await {
+            statuses.push(status.expect("Signup task panicked"));
+        }
+
+        assert_eq!(statuses.iter().filter(|status| **status == 201).count(), 1, "{:?}", statuses);
+        assert_eq!(statuses.iter().filter(|status| **status == 409).count(), SIGNUPS - 1, "{:?}", statuses);
+    }
+
+    #[tokio::test]
+    async fn should_return_409_for_all_but_one_concurrent_signup() {
+        assert_single_winner(TestApp::new().await).await;
+    }
+
+    #[tokio::test]
+    async fn should_return_409_for_all_but_one_concurrent_signup_with_sqlite() {
+        let db = TestSqliteDb::new().await;
+
+        let user_store: UserStoreType = Arc::new(SqliteUserStore::new(db.pool.clone(), password_hasher()));
+        assert_single_winner(TestApp::with_user_store(user_store).await).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Postgres, run with DATABASE_URL set"]
+    async fn should_return_409_for_all_but_one_concurrent_signup_with_postgres() {
+        let pool = get_postgres_pool(&DATABASE_URL)
+            .await
+            .expect("Failed to create Postgres connection pool");
+        sqlx::migrate!()
+            .run(&pool)
+            .await
+            .expect("Failed to run migrations");
+
+        let user_store: UserStoreType = Arc::new(PostgresUserStore::new(pool, password_hasher()));
+        assert_single_winner(TestApp::with_user_store(user_store).await).await;
+    }
+}
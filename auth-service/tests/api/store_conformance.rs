@@ -0,0 +1,702 @@
+// Pins down the behaviour every backend of the user, banned token, 2FA code and email change stores and of the
+// audit log shares.
+// The suites take any implementation, the backends are wired up at the bottom.
+use std::{num::NonZeroUsize, sync::Arc};
+
+use auth_service::{
+    app_state::{AuditLogType, BannedTokenStoreType, EmailChangeStoreType, PasswordHasherType, TwoFACodeStoreType,
+                UserStoreType},
+    domain::{AuditEvent, AuditEventFilter, AuditEventKind, AuditOutcome, DisplayName, Email, EmailChange,
+             EmailChangeStoreError, HashedPassword, LoginAttemptId, Password, TwoFACode, TwoFACodeStoreError, User,
+             UserId, UserStoreError},
+    services::argon2_password_hasher::Argon2PasswordHasher,
+    utils::config::PasswordHashConfig,
+};
+use chrono::{DateTime, Duration, Utc};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::helpers::TestApp;
+
+pub async fn user_store_suite(store: UserStoreType) {
+    adds_and_gets_users(&store).await;
+    rejects_duplicate_users(&store).await;
+    reports_missing_users(&store).await;
+    validates_passwords(&store).await;
+    imports_hashed_passwords(&store).await;
+    lists_users_by_id(&store).await;
+    updates_users(&store).await;
+    locks_after_failed_logins(&store).await;
+}
+
+pub async fn banned_token_store_suite(store: BannedTokenStoreType) {
+    bans_tokens(&store).await;
+    revokes_user_tokens(&store).await;
+}
+
+// The 2FA code stores under test keep this many codes pending per email
+pub const MAX_PENDING_TWO_FA_CODES: usize = 2;
+
+pub async fn two_fa_code_store_suite(store: TwoFACodeStoreType) {
+    consumes_two_fa_codes(&store).await;
+    keeps_and_deletes_concurrent_two_fa_codes(&store).await;
+    caps_pending_two_fa_codes(&store).await;
+}
+
+pub fn max_pending_two_fa_codes() -> NonZeroUsize {
+    NonZeroUsize::new(MAX_PENDING_TWO_FA_CODES).unwrap()
+}
+
+pub fn two_fa_code_secret() -> Secret<String> {
+    Secret::new("two-fa-code-secret".to_owned())
+}
+
+pub async fn email_change_store_suite(store: EmailChangeStoreType) {
+    verifies_email_change_codes(&store).await;
+}
+
+pub async fn audit_log_suite(audit_log: AuditLogType) {
+    pages_through_audit_events(&audit_log).await;
+    filters_audit_events(&audit_log).await;
+}
+
+async fn adds_and_gets_users(store: &UserStoreType) {
+    let email = random_email();
+    let user = User::new(email.clone(), true);
+    store.add_user(user.clone(), Some(password("password123"))).await.unwrap();
+
+    let stored = store.get_user(&email).await.unwrap();
+    assert_eq!(stored.id, user.id);
+    assert_eq!(stored.email, user.email);
+    assert!(stored.requires_2fa);
+    assert_eq!(stored.role, user.role);
+    assert_eq!(stored.created_at.timestamp(), user.created_at.timestamp());
+    // Passwords are only ever kept hashed
+    assert_ne!(stored.password_hash.unwrap().as_ref().expose_secret(), "password123");
+
+    assert_eq!(store.get_user_by_id(&user.id).await.unwrap().email, email);
+    // Lookups go through the canonical form of the email
+    assert_eq!(store.get_user(&shouted(&email)).await.unwrap().id, user.id);
+}
+
+async fn rejects_duplicate_users(store: &UserStoreType) {
+    let user = User::new(random_email(), false);
+    store.add_user(user.clone(), Some(password("password123"))).await.unwrap();
+
+    let same_email = User::new(user.email.clone(), false);
+    assert_eq!(store.add_user(same_email, None).await, Err(UserStoreError::UserAlreadyExists));
+
+    let same_canonical_email = User::new(shouted(&user.email), false);
+    assert_eq!(store.add_user(same_canonical_email.clone(), None).await, Err(UserStoreError::UserAlreadyExists));
+    assert_eq!(store.import_user(same_canonical_email, hashed_password("password123").await).await,
+               Err(UserStoreError::UserAlreadyExists));
+
+    let mut same_id = User::new(random_email(), false);
+    same_id.id = user.id;
+    assert_eq!(store.add_user(same_id, None).await, Err(UserStoreError::UserAlreadyExists));
+}
+
+async fn reports_missing_users(store: &UserStoreType) {
+    let email = random_email();
+    let id = UserId::default();
+
+    assert_eq!(store.get_user(&email).await, Err(UserStoreError::UserNotFound));
+    assert_eq!(store.get_user_by_id(&id).await, Err(UserStoreError::UserNotFound));
+    assert_eq!(store.update_display_name(&email, None).await, Err(UserStoreError::UserNotFound));
+    assert_eq!(store.record_login(&email, Utc::now()).await, Err(UserStoreError::UserNotFound));
+    assert_eq!(store.record_failed_login(&email, 3, Utc::now()).await, Err(UserStoreError::UserNotFound));
+    assert_eq!(store.update_email(&id, random_email()).await, Err(UserStoreError::UserNotFound));
+    assert_eq!(store.unlock_user(&id).await, Err(UserStoreError::UserNotFound));
+    assert_eq!(store.update_requires_2fa(&id, true).await, Err(UserStoreError::UserNotFound));
+    assert_eq!(store.clear_password(&id).await, Err(UserStoreError::UserNotFound));
+}
+
+async fn validates_passwords(store: &UserStoreType) {
+    let user = User::new(random_email(), false);
+    store.add_user(user.clone(), Some(password("password123"))).await.unwrap();
+    let passwordless = User::new(random_email(), false);
+    store.add_user(passwordless.clone(), None).await.unwrap();
+
+    assert_eq!(store.validate_user(&user.email, &password("password123")).await.map(|user| user.id), Ok(user.id));
+    assert_eq!(store.validate_user(&shouted(&user.email), &password("password123")).await.map(|user| user.id),
+               Ok(user.id));
+    assert_eq!(store.validate_user(&user.email, &password("password456")).await,
+               Err(UserStoreError::InvalidCredentials));
+    // Unknown and passwordless users can't be told apart from a wrong password
+    assert_eq!(store.validate_user(&random_email(), &password("password123")).await,
+               Err(UserStoreError::InvalidCredentials));
+    assert_eq!(store.validate_user(&passwordless.email, &password("password123")).await,
+               Err(UserStoreError::InvalidCredentials));
+}
+
+async fn imports_hashed_passwords(store: &UserStoreType) {
+    let user = User::new(random_email(), false);
+    store.import_user(user.clone(), hashed_password("password123").await).await.unwrap();
+
+    assert_eq!(store.get_user(&user.email).await.unwrap().id, user.id);
+    assert_eq!(store.validate_user(&user.email, &password("password123")).await.map(|user| user.id), Ok(user.id));
+}
+
+async fn lists_users_by_id(store: &UserStoreType) {
+    let mut added = Vec::new();
+    for _ in 0..3 {
+        let user = User::new(random_email(), false);
+        store.add_user(user.clone(), None).await.unwrap();
+        added.push(user.id);
+    }
+
+    let mut listed = Vec::new();
+    let mut after = None;
+    loop {
+        let page = store.list_users(after.as_ref(), 2).await.unwrap();
+        assert!(page.len() <= 2);
+        listed.extend(page.iter().map(|user| user.id));
+        match page.last() {
+            Some(user) if page.len() == 2 => after = Some(user.id),
+            _ => break,
+        }
+    }
+
+    assert!(listed.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", listed);
+    assert!(added.iter().all(|id| listed.contains(id)));
+}
+
+async fn updates_users(store: &UserStoreType) {
+    let user = User::new(random_email(), true);
+    store.add_user(user.clone(), Some(password("password123"))).await.unwrap();
+    let other = User::new(random_email(), false);
+    store.add_user(other.clone(), None).await.unwrap();
+
+    let display_name = DisplayName::parse("Ada").unwrap();
+    let updated = store.update_display_name(&user.email, Some(display_name.clone())).await.unwrap();
+    assert_eq!(updated.display_name, Some(display_name.clone()));
+    assert_eq!(store.get_user(&user.email).await.unwrap().display_name, Some(display_name));
+    let updated = store.update_display_name(&user.email, None).await.unwrap();
+    assert_eq!(updated.display_name, None);
+
+    let at = Utc::now();
+    store.record_login(&user.email, at).await.unwrap();
+    let last_login_at = store.get_user(&user.email).await.unwrap().last_login_at;
+    assert_eq!(last_login_at.map(|at| at.timestamp()), Some(at.timestamp()));
+
+    assert_eq!(store.update_email(&user.id, other.email.clone()).await, Err(UserStoreError::UserAlreadyExists));
+    let new_email = random_email();
+    let updated = store.update_email(&user.id, new_email.clone()).await.unwrap();
+    assert_eq!(updated.email, new_email);
+    assert_eq!(store.get_user(&new_email).await.unwrap().id, user.id);
+    assert_eq!(store.get_user(&user.email).await, Err(UserStoreError::UserNotFound));
+
+    let updated = store.update_requires_2fa(&user.id, false).await.unwrap();
+    assert!(!updated.requires_2fa);
+    assert!(!store.get_user_by_id(&user.id).await.unwrap().requires_2fa);
+
+    let updated = store.clear_password(&user.id).await.unwrap();
+    assert!(updated.password_hash.is_none());
+    assert_eq!(store.validate_user(&new_email, &password("password123")).await,
+               Err(UserStoreError::InvalidCredentials));
+
+    store.record_failed_login(&new_email, 1, Utc::now() + Duration::minutes(15)).await.unwrap();
+    let updated = store.set_password(&user.id, password("password456")).await.unwrap();
+    assert!(!updated.is_locked(Utc::now()));
+    assert!(store.validate_user(&new_email, &password("password456")).await.is_ok());
+}
+
+async fn locks_after_failed_logins(store: &UserStoreType) {
+    let user = User::new(random_email(), false);
+    store.add_user(user.clone(), Some(password("password123"))).await.unwrap();
+    let locked_until = Utc::now() + Duration::minutes(15);
+
+    for _ in 0..2 {
+        store.record_failed_login(&user.email, 3, locked_until).await.unwrap();
+    }
+    let stored = store.get_user(&user.email).await.unwrap();
+    assert_eq!(stored.failed_login_attempts, 2);
+    assert_eq!(stored.locked_until, None);
+
+    // A successful login starts the count over
+    store.record_login(&user.email, Utc::now()).await.unwrap();
+    assert_eq!(store.get_user(&user.email).await.unwrap().failed_login_attempts, 0);
+
+    for _ in 0..3 {
+        store.record_failed_login(&user.email, 3, locked_until).await.unwrap();
+    }
+    let stored = store.get_user(&user.email).await.unwrap();
+    assert_eq!(stored.failed_login_attempts, 0);
+    assert_eq!(stored.locked_until.map(|until| until.timestamp()), Some(locked_until.timestamp()));
+    assert!(stored.is_locked(Utc::now()));
+    // Even the right password is refused while locked
+    assert_eq!(store.validate_user(&user.email, &password("password123")).await, Err(UserStoreError::UserLocked));
+
+    let unlocked = store.unlock_user(&user.id).await.unwrap();
+    assert_eq!(unlocked.locked_until, None);
+    assert_eq!(unlocked.failed_login_attempts, 0);
+    assert!(!store.get_user(&user.email).await.unwrap().is_locked(Utc::now()));
+    assert!(store.validate_user(&user.email, &password("password123")).await.is_ok());
+}
+
+async fn bans_tokens(store: &BannedTokenStoreType) {
+    let token = Secret::new(uuid::Uuid::new_v4().to_string());
+
+    assert!(!store.is_banned_token(&token).await.unwrap());
+    store.add_banned_token(token.clone()).await.unwrap();
+    assert!(store.is_banned_token(&token).await.unwrap());
+    // Banning twice is not an error
+    store.add_banned_token(token.clone()).await.unwrap();
+    assert!(store.is_banned_token(&token).await.unwrap());
+    assert!(!store.is_banned_token(&Secret::new(uuid::Uuid::new_v4().to_string())).await.unwrap());
+}
+
+async fn revokes_user_tokens(store: &BannedTokenStoreType) {
+    let user_id = UserId::default();
+    // Tokens carry their issue time in whole seconds
+    let now = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+
+    assert!(!store.is_revoked_user_token(&user_id, now).await.unwrap());
+    assert_eq!(store.user_tokens_revoked_at(&user_id).await.unwrap(), None);
+
+    store.revoke_user_tokens(&user_id, now).await.unwrap();
+    assert_eq!(store.user_tokens_revoked_at(&user_id).await.unwrap(), Some(now));
+    assert!(store.is_revoked_user_token(&user_id, now - Duration::minutes(1)).await.unwrap());
+    assert!(store.is_revoked_user_token(&user_id, now).await.unwrap());
+    assert!(!store.is_revoked_user_token(&user_id, now + Duration::seconds(2)).await.unwrap());
+    assert!(!store.is_revoked_user_token(&UserId::default(), now).await.unwrap());
+
+    // An older revocation never moves the cutoff back
+    store.revoke_user_tokens(&user_id, now - Duration::hours(1)).await.unwrap();
+    assert!(store.is_revoked_user_token(&user_id, now).await.unwrap());
+    assert_eq!(store.user_tokens_revoked_at(&user_id).await.unwrap(), Some(now));
+}
+
+async fn consumes_two_fa_codes(store: &TwoFACodeStoreType) {
+    let email = random_email();
+    let login_attempt_id = LoginAttemptId::default();
+    let code = two_fa_code("123456");
+
+    assert_eq!(store.verify_two_fa_code(&email, &login_attempt_id, &code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+    store.add_two_fa_code(&email, login_attempt_id.clone(), code.clone()).await.unwrap();
+    // A login attempt is bound to the email its code was sent to, and left alone by others
+    assert_eq!(store.verify_two_fa_code(&random_email(), &login_attempt_id, &code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    assert_eq!(store.verify_two_fa_code(&email, &LoginAttemptId::default(), &code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    assert_eq!(store.verify_two_fa_code(&shouted(&email), &login_attempt_id, &code).await, Ok(true));
+    // A code is accepted once
+    assert_eq!(store.verify_two_fa_code(&email, &login_attempt_id, &code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+    // A wrong code ends the attempt too
+    store.add_two_fa_code(&email, login_attempt_id.clone(), code.clone()).await.unwrap();
+    assert_eq!(store.verify_two_fa_code(&email, &login_attempt_id, &two_fa_code("654321")).await, Ok(false));
+    assert_eq!(store.verify_two_fa_code(&email, &login_attempt_id, &code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+}
+
+async fn keeps_and_deletes_concurrent_two_fa_codes(store: &TwoFACodeStoreType) {
+    let email = random_email();
+    let (first_id, first_code) = (LoginAttemptId::default(), two_fa_code("123456"));
+    let (second_id, second_code) = (LoginAttemptId::default(), two_fa_code("654321"));
+    let add_both = || async {
+        store.add_two_fa_code(&email, first_id.clone(), first_code.clone()).await.unwrap();
+        store.add_two_fa_code(&email, second_id.clone(), second_code.clone()).await.unwrap();
+    };
+
+    // Every login attempt of an email is pending with its own code
+    add_both().await;
+    assert_eq!(store.verify_two_fa_code(&email, &first_id, &second_code).await, Ok(false));
+    assert_eq!(store.verify_two_fa_code(&email, &second_id, &second_code).await, Ok(true));
+
+    // Another email can't delete the attempt
+    add_both().await;
+    store.delete_two_fa_code(&random_email(), &first_id).await.unwrap();
+    store.delete_two_fa_code(&email, &second_id).await.unwrap();
+    assert_eq!(store.verify_two_fa_code(&email, &second_id, &second_code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    assert_eq!(store.verify_two_fa_code(&email, &first_id, &first_code).await, Ok(true));
+    // Deleting again succeeds
+    store.delete_two_fa_code(&email, &first_id).await.unwrap();
+
+    add_both().await;
+    store.delete_two_fa_codes(&email).await.unwrap();
+    assert_eq!(store.verify_two_fa_code(&email, &first_id, &first_code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    assert_eq!(store.verify_two_fa_code(&email, &second_id, &second_code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    // Deleting again, or for an email without a code, succeeds
+    store.delete_two_fa_codes(&email).await.unwrap();
+    store.delete_two_fa_codes(&random_email()).await.unwrap();
+}
+
+async fn caps_pending_two_fa_codes(store: &TwoFACodeStoreType) {
+    let email = random_email();
+    let other_email = random_email();
+    let code = two_fa_code("123456");
+    let attempts: Vec<LoginAttemptId> = (0..=MAX_PENDING_TWO_FA_CODES).map(|_| LoginAttemptId::default()).collect();
+    let other_attempt = LoginAttemptId::default();
+
+    store.add_two_fa_code(&other_email, other_attempt.clone(), code.clone()).await.unwrap();
+    for login_attempt_id in &attempts {
+        store.add_two_fa_code(&email, login_attempt_id.clone(), code.clone()).await.unwrap();
+    }
+
+    // Going over the cap dropped the oldest attempt of the email only
+    assert_eq!(store.verify_two_fa_code(&email, &attempts[0], &code).await,
+               Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    for login_attempt_id in &attempts[1..] {
+        assert_eq!(store.verify_two_fa_code(&email, login_attempt_id, &code).await, Ok(true));
+    }
+    assert_eq!(store.verify_two_fa_code(&other_email, &other_attempt, &code).await, Ok(true));
+}
+
+async fn verifies_email_change_codes(store: &EmailChangeStoreType) {
+    let user_id = UserId::default();
+    let email_change = EmailChange::new(user_id, random_email());
+    let code = two_fa_code("123456");
+
+    assert_eq!(store.verify_email_change_code(&user_id, &code).await, Err(EmailChangeStoreError::EmailChangeNotFound));
+
+    store.add_email_change(email_change.clone(), &code).await.unwrap();
+    assert_eq!(store.get_email_change(&user_id).await.unwrap(), email_change);
+    assert_eq!(store.verify_email_change_code(&user_id, &code).await, Ok(true));
+    assert_eq!(store.verify_email_change_code(&user_id, &two_fa_code("654321")).await, Ok(false));
+    // The code is bound to the user the change was requested for
+    assert_eq!(store.verify_email_change_code(&UserId::default(), &code).await,
+               Err(EmailChangeStoreError::EmailChangeNotFound));
+
+    // Checking a code leaves the change pending, wrong codes only drop it once counted
+    assert_eq!(store.record_failed_attempt(&user_id, 2).await, Ok(false));
+    assert_eq!(store.record_failed_attempt(&user_id, 2).await, Ok(true));
+    assert_eq!(store.verify_email_change_code(&user_id, &code).await, Err(EmailChangeStoreError::EmailChangeNotFound));
+
+    // A new change comes with a new code
+    store.add_email_change(email_change.clone(), &code).await.unwrap();
+    store.add_email_change(email_change, &two_fa_code("654321")).await.unwrap();
+    assert_eq!(store.verify_email_change_code(&user_id, &code).await, Ok(false));
+    store.delete_email_change(&user_id).await.unwrap();
+    assert_eq!(store.delete_email_change(&user_id).await, Err(EmailChangeStoreError::EmailChangeNotFound));
+}
+
+async fn pages_through_audit_events(audit_log: &AuditLogType) {
+    let user_id = UserId::default();
+    let filter = AuditEventFilter { user_id: Some(user_id), ..Default::default() };
+    for detail in ["first", "second", "third"] {
+        let event = AuditEvent::new(AuditEventKind::Login, AuditOutcome::Success)
+            .with_user_id(user_id)
+            .with_detail(detail);
+        audit_log.record(event).await.unwrap();
+    }
+
+    // Newest first
+    let page = audit_log.query(&filter, None, 2).await.unwrap();
+    let details: Vec<_> = page.iter().map(|record| record.event.detail.as_deref().unwrap()).collect();
+    assert_eq!(details, ["third", "second"]);
+    assert!(page[0].id > page[1].id);
+
+    let page = audit_log.query(&filter, Some(page[1].id), 2).await.unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].event.detail.as_deref(), Some("first"));
+    assert!(audit_log.query(&filter, Some(page[0].id), 2).await.unwrap().is_empty());
+}
+
+async fn filters_audit_events(audit_log: &AuditLogType) {
+    let user = User::new(random_email(), false);
+    let occurred_at = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+    let failure = AuditEvent {
+        occurred_at,
+        ip: Some("203.0.113.7".parse().unwrap()),
+        user_agent: Some("curl/8.0".to_owned()),
+        ..AuditEvent::new(AuditEventKind::Login, AuditOutcome::Failure)
+            .with_email(&user.email)
+            .with_detail("unknown_user")
+    };
+    let success = AuditEvent { occurred_at, ..AuditEvent::new(AuditEventKind::Signup, AuditOutcome::Success) }
+        .with_user(&user);
+    audit_log.record(failure.clone()).await.unwrap();
+    audit_log.record(success.clone()).await.unwrap();
+
+    let query = |filter: AuditEventFilter| async move {
+        audit_log.query(&filter, None, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.event)
+            .collect::<Vec<_>>()
+    };
+    let by_email = AuditEventFilter { email: Some(shouted(&user.email)), ..Default::default() };
+
+    assert_eq!(query(by_email.clone()).await, [success.clone(), failure.clone()]);
+    assert_eq!(query(AuditEventFilter { user_id: Some(user.id), ..Default::default() }).await,
+               std::slice::from_ref(&success));
+    assert_eq!(query(AuditEventFilter { kind: Some(AuditEventKind::Login), ..by_email.clone() }).await,
+               std::slice::from_ref(&failure));
+    assert_eq!(query(AuditEventFilter { outcome: Some(AuditOutcome::Success), ..by_email.clone() }).await,
+               std::slice::from_ref(&success));
+    assert_eq!(query(AuditEventFilter { since: Some(occurred_at), ..by_email.clone() }).await.len(), 2);
+    assert!(query(AuditEventFilter { until: Some(occurred_at), ..by_email.clone() }).await.is_empty());
+    assert!(query(AuditEventFilter { since: Some(occurred_at + Duration::seconds(1)), ..by_email }).await.is_empty());
+}
+
+fn random_email() -> Email {
+    Email::parse(Secret::new(TestApp::get_random_email())).unwrap()
+}
+
+// The same address in upper case, which has the same canonical form
+fn shouted(email: &Email) -> Email {
+    Email::parse(Secret::new(email.as_ref().expose_secret().to_uppercase())).unwrap()
+}
+
+fn password(password: &str) -> Password {
+    Password::parse(Secret::new(password.to_owned())).unwrap()
+}
+
+fn two_fa_code(code: &str) -> TwoFACode {
+    TwoFACode::parse(Secret::new(code.to_owned())).unwrap()
+}
+
+fn password_hasher() -> PasswordHasherType {
+    Arc::new(Argon2PasswordHasher::new(&PasswordHashConfig::default(), None).unwrap())
+}
+
+async fn hashed_password(password_text: &str) -> HashedPassword {
+    password_hasher().hash_password(&password(password_text)).await.unwrap()
+}
+
+mod memory {
+    use std::sync::Arc;
+
+    use auth_service::services::data_stores::{
+        hashmap_email_change_store::HashmapEmailChangeStore, hashmap_two_fa_store::HashmapTwoFACodeStore,
+        hashmap_user_store::HashmapUserStore, hashset_banned_token_store::HashsetBannedTokenStore,
+        vec_audit_log::VecAuditLog,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn user_store() {
+        user_store_suite(Arc::new(HashmapUserStore::new(password_hasher()))).await;
+    }
+
+    #[tokio::test]
+    async fn banned_token_store() {
+        banned_token_store_suite(Arc::new(HashsetBannedTokenStore::default())).await;
+    }
+
+    #[tokio::test]
+    async fn two_fa_code_store() {
+        let store = HashmapTwoFACodeStore::new(max_pending_two_fa_codes(), two_fa_code_secret());
+        two_fa_code_store_suite(Arc::new(store)).await;
+    }
+
+    #[tokio::test]
+    async fn email_change_store() {
+        email_change_store_suite(Arc::new(HashmapEmailChangeStore::new(two_fa_code_secret()))).await;
+    }
+
+    #[tokio::test]
+    async fn audit_log() {
+        audit_log_suite(Arc::new(VecAuditLog::default())).await;
+    }
+}
+
+mod cached {
+    use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+    use auth_service::services::data_stores::{
+        cached_user_store::CachedUserStore, hashmap_user_store::HashmapUserStore,
+    };
+
+    use super::*;
+
+    // Small enough for the suite to evict users
+    #[tokio::test]
+    async fn user_store() {
+        let store = CachedUserStore::new(HashmapUserStore::new(password_hasher()),
+                                         NonZeroUsize::new(2).unwrap(),
+                                         Duration::from_secs(60));
+        user_store_suite(Arc::new(store)).await;
+    }
+
+    #[tokio::test]
+    async fn user_store_with_expired_entries() {
+        let store = CachedUserStore::new(HashmapUserStore::new(password_hasher()),
+                                         NonZeroUsize::new(100).unwrap(),
+                                         Duration::ZERO);
+        user_store_suite(Arc::new(store)).await;
+    }
+}
+
+mod sqlite {
+    use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+    use auth_service::{
+        domain::UserStore,
+        services::data_stores::{
+            cached_user_store::CachedUserStore,
+            sqlite_banned_token_store::SqliteBannedTokenStore, sqlite_two_fa_store::SqliteTwoFACodeStore,
+            sqlite_user_store::SqliteUserStore,
+        },
+    };
+
+    use super::*;
+    use crate::helpers::TestSqliteDb;
+
+    #[tokio::test]
+    async fn user_store() {
+        let db = TestSqliteDb::new().await;
+        user_store_suite(Arc::new(SqliteUserStore::new(db.pool.clone(), password_hasher()))).await;
+    }
+
+    #[tokio::test]
+    async fn banned_token_store() {
+        let db = TestSqliteDb::new().await;
+        banned_token_store_suite(Arc::new(SqliteBannedTokenStore::new(db.pool.clone()))).await;
+    }
+
+    #[tokio::test]
+    async fn cached_user_store_serves_repeat_lookups() {
+        let db = TestSqliteDb::new().await;
+        let store = CachedUserStore::new(SqliteUserStore::new(db.pool.clone(), password_hasher()),
+                                         NonZeroUsize::new(10).unwrap(),
+                                         Duration::from_secs(60));
+        let uncached = SqliteUserStore::new(db.pool.clone(), password_hasher());
+        let user = User::new(random_email(), true);
+        store.add_user(user.clone(), None).await.unwrap();
+        assert!(store.get_user(&user.email).await.unwrap().requires_2fa);
+
+        // Writes that bypass the cache are only seen once the entry expires
+        uncached.update_requires_2fa(&user.id, false).await.unwrap();
+        assert!(store.get_user(&user.email).await.unwrap().requires_2fa);
+        assert!(store.get_user_by_id(&user.id).await.unwrap().requires_2fa);
+
+        store.update_requires_2fa(&user.id, true).await.unwrap();
+        uncached.update_display_name(&user.email, Some(DisplayName::parse("Ada").unwrap())).await.unwrap();
+        assert_eq!(store.get_user(&user.email).await.unwrap().display_name, None);
+    }
+
+    #[tokio::test]
+    async fn cached_user_store_validates_past_the_cache() {
+        let db = TestSqliteDb::new().await;
+        let store = CachedUserStore::new(SqliteUserStore::new(db.pool.clone(), password_hasher()),
+                                         NonZeroUsize::new(10).unwrap(),
+                                         Duration::from_secs(60));
+        let uncached = SqliteUserStore::new(db.pool.clone(), password_hasher());
+        let user = User::new(random_email(), true);
+        store.add_user(user.clone(), Some(password("password123"))).await.unwrap();
+        assert!(store.get_user(&user.email).await.unwrap().requires_2fa);
+
+        // As auth-admin would, through its own store
+        for _ in 0..3 {
+            uncached.record_failed_login(&user.email, 3, Utc::now() + Duration::from_secs(60)).await.unwrap();
+        }
+        assert_eq!(store.validate_user(&user.email, &password("password123")).await,
+                   Err(UserStoreError::UserLocked));
+
+        uncached.unlock_user(&user.id).await.unwrap();
+        uncached.update_requires_2fa(&user.id, false).await.unwrap();
+        let fresh = store.validate_user(&user.email, &password("password123")).await.unwrap();
+        assert!(!fresh.requires_2fa);
+        // And the cache now holds what was read
+        assert!(!store.get_user(&user.email).await.unwrap().requires_2fa);
+    }
+
+    #[tokio::test]
+    async fn two_fa_code_store() {
+        let db = TestSqliteDb::new().await;
+        let store = SqliteTwoFACodeStore::new(db.pool.clone(), max_pending_two_fa_codes(), two_fa_code_secret());
+        two_fa_code_store_suite(Arc::new(store)).await;
+    }
+}
+
+mod postgres {
+    use std::sync::Arc;
+
+    use auth_service::{
+        get_postgres_pool,
+        services::data_stores::{
+            postgres_audit_log::PostgresAuditLog, postgres_banned_token_store::PostgresBannedTokenStore, postgres_two_fa_store::PostgresTwoFACodeStore,
+            postgres_user_store::PostgresUserStore,
+        },
+        utils::constants::DATABASE_URL,
+    };
+    use sqlx::PgPool;
+
+    use super::*;
+
+    async fn pool() -> PgPool {
+        let pool = get_postgres_pool(&DATABASE_URL)
+            .await
+            .expect("Failed to create Postgres connection pool");
+        sqlx::migrate!()
+            .run(&pool)
+            .await
+            .expect("Failed to run migrations");
+        pool
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Postgres, run with DATABASE_URL set"]
+    async fn user_store() {
+        user_store_suite(Arc::new(PostgresUserStore::new(pool().await, password_hasher()))).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Postgres, run with DATABASE_URL set"]
+    async fn banned_token_store() {
+        banned_token_store_suite(Arc::new(PostgresBannedTokenStore::new(pool().await))).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Postgres, run with DATABASE_URL set"]
+    async fn two_fa_code_store() {
+        let store = PostgresTwoFACodeStore::new(pool().await, max_pending_two_fa_codes(), two_fa_code_secret());
+        two_fa_code_store_suite(Arc::new(store)).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Postgres, run with DATABASE_URL set"]
+    async fn audit_log() {
+        audit_log_suite(Arc::new(PostgresAuditLog::new(pool().await))).await;
+    }
+}
+
+mod redis {
+    use std::sync::Arc;
+
+    use auth_service::{
+        get_redis_client,
+        services::data_stores::{
+            redis_banned_token_store::RedisBannedTokenStore, redis_email_change_store::RedisEmailChangeStore,
+            redis_two_fa_store::RedisTwoFACodeStore,
+        },
+        utils::constants::REDIS_HOST_NAME,
+    };
+    use ::redis::aio::ConnectionManager;
+
+    use super::*;
+
+    async fn connection() -> ConnectionManager {
+        let client = get_redis_client(REDIS_HOST_NAME.to_owned()).expect("Failed to create Redis client");
+        ConnectionManager::new(client).await.expect("Failed to connect to Redis")
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Redis, run with REDIS_HOST_NAME set"]
+    async fn banned_token_store() {
+        banned_token_store_suite(Arc::new(RedisBannedTokenStore::new(connection().await))).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Redis, run with REDIS_HOST_NAME set"]
+    async fn two_fa_code_store() {
+        let store = RedisTwoFACodeStore::new(connection().await, max_pending_two_fa_codes(), two_fa_code_secret());
+        two_fa_code_store_suite(Arc::new(store)).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Redis, run with REDIS_HOST_NAME set"]
+    async fn email_change_store() {
+        let store = RedisEmailChangeStore::new(connection().await, two_fa_code_secret());
+        email_change_store_suite(Arc::new(store)).await;
+    }
+}